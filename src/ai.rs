@@ -0,0 +1,444 @@
+use super::game::*;
+use super::pieces::*;
+use super::tetromino::*;
+use rand::Rng;
+
+const TOP_OUT_PENALTY: f32 = 1000.0;
+const MISTAKE_SPREAD: usize = 6;
+const MAX_MOVE_ATTEMPTS: u32 = 20;
+
+// Weights for the placement evaluator. Positive weights reward a feature,
+// negative weights punish it. The defaults follow the well known
+// Dellacherie/El-Tetris style evaluators.
+#[derive(Clone, Copy)]
+pub struct Ai_Weights {
+    pub aggregate_height: f32,
+    pub lines: f32,
+    pub holes: f32,
+    pub bumpiness: f32,
+    pub wells: f32,
+}
+
+pub const DEFAULT_WEIGHTS: Ai_Weights = Ai_Weights {
+    aggregate_height: -0.510066,
+    lines: 0.760666,
+    holes: -0.35663,
+    bumpiness: -0.184483,
+    wells: -0.1,
+};
+
+// pieces_per_second of 0 means the AI drops as fast as the inputs allow.
+#[derive(Clone, Copy)]
+pub struct Ai_Difficulty {
+    pub pieces_per_second: f32,
+    pub mistake_chance: f32,
+}
+
+pub const AI_EASY: Ai_Difficulty = Ai_Difficulty {
+    pieces_per_second: 1.0,
+    mistake_chance: 0.25,
+};
+
+pub const AI_MEDIUM: Ai_Difficulty = Ai_Difficulty {
+    pieces_per_second: 2.0,
+    mistake_chance: 0.08,
+};
+
+pub const AI_HARD: Ai_Difficulty = Ai_Difficulty {
+    pieces_per_second: 4.0,
+    mistake_chance: 0.0,
+};
+
+pub const AI_MAX: Ai_Difficulty = Ai_Difficulty {
+    pieces_per_second: 0.0,
+    mistake_chance: 0.0,
+};
+
+pub fn difficulty_from_name(name: &str) -> Option<Ai_Difficulty> {
+    match name {
+        "easy" => return Some(AI_EASY),
+        "medium" => return Some(AI_MEDIUM),
+        "hard" => return Some(AI_HARD),
+        "max" => return Some(AI_MAX),
+        _ => {}
+    }
+    return None;
+}
+
+#[derive(Clone, Copy)]
+pub struct Placement {
+    pub rotation: i32,
+    pub col: i32,
+    pub score: f32,
+}
+
+fn piece_fits(
+    board: &Board,
+    pieces: &Piece_Set,
+    index: u8,
+    rotation: i32,
+    row: i32,
+    col: i32,
+) -> bool {
+    let tetromino: Tetromino = pieces.shape(index);
+    for r in 0..tetromino.side {
+        for c in 0..tetromino.side {
+            if tetromino.tetromino_get(r, c, rotation) > 0 {
+                let board_row: i32 = row + r;
+                let board_col: i32 = col + c;
+                if !board.contains(board_row, board_col) {
+                    return false;
+                }
+                if board.get(board_row, board_col) != 0 {
+                    return false;
+                }
+            }
+        }
+    }
+    return true;
+}
+
+// Returns the row the piece lands on when it is rotated at the spawn
+// column, shifted to `col` and hard dropped, or None if it cannot get there.
+pub fn find_landing_row(
+    board: &Board,
+    pieces: &Piece_Set,
+    index: u8,
+    rotation: i32,
+    col: i32,
+) -> Option<i32> {
+    let spawn_col: i32 = pieces.spawn_col(index, board.width());
+    let spawn_row: i32 = pieces.spawn_row(index);
+    for r in 0..=rotation {
+        if !piece_fits(board, pieces, index, r, spawn_row, spawn_col) {
+            return None;
+        }
+    }
+    let step: i32 = if col >= spawn_col { 1 } else { -1 };
+    let mut c: i32 = spawn_col;
+    while c != col {
+        c += step;
+        if !piece_fits(board, pieces, index, rotation, spawn_row, c) {
+            return None;
+        }
+    }
+    let mut row: i32 = spawn_row;
+    while piece_fits(board, pieces, index, rotation, row + 1, col) {
+        row += 1;
+    }
+    return Some(row);
+}
+
+fn place_piece(
+    board: &mut Board,
+    pieces: &Piece_Set,
+    index: u8,
+    rotation: i32,
+    row: i32,
+    col: i32,
+) {
+    let tetromino: Tetromino = pieces.shape(index);
+    for r in 0..tetromino.side {
+        for c in 0..tetromino.side {
+            let value: u8 = tetromino.tetromino_get(r, c, rotation);
+            if value > 0 {
+                board.set(row + r, col + c, value);
+            }
+        }
+    }
+}
+
+fn clear_full_rows(board: &mut Board) -> i32 {
+    let width: i32 = board.width();
+    let mut cleared: i32 = 0;
+    let mut dst_row: i32 = board.rows() - 1;
+    for src_row in (0..board.rows()).rev() {
+        if board.row(src_row).iter().all(|v| *v != 0) {
+            cleared += 1;
+            continue;
+        }
+        if dst_row != src_row {
+            let start: usize = (src_row * width) as usize;
+            let dst_start: usize = (dst_row * width) as usize;
+            board
+                .cells
+                .copy_within(start..start + width as usize, dst_start);
+        }
+        dst_row -= 1;
+    }
+    let end: usize = ((dst_row + 1) * width) as usize;
+    board.cells[..end].fill(0);
+    return cleared;
+}
+
+pub struct Board_Features {
+    pub aggregate_height: i32,
+    pub max_height: i32,
+    pub holes: i32,
+    pub bumpiness: i32,
+    pub wells: i32,
+}
+
+pub fn board_features(board: &Board) -> Board_Features {
+    let width: usize = board.width() as usize;
+    let rows: i32 = board.rows();
+    let mut heights: Vec<i32> = vec![0; width];
+    let mut holes: i32 = 0;
+    for col in 0..width as i32 {
+        let mut seen_block: bool = false;
+        for row in 0..rows {
+            if board.get(row, col) != 0 {
+                if !seen_block {
+                    heights[col as usize] = rows - row;
+                    seen_block = true;
+                }
+            } else if seen_block {
+                holes += 1;
+            }
+        }
+    }
+    let mut features: Board_Features = Board_Features {
+        aggregate_height: 0,
+        max_height: 0,
+        holes: holes,
+        bumpiness: 0,
+        wells: 0,
+    };
+    for col in 0..width {
+        features.aggregate_height += heights[col];
+        features.max_height = max(features.max_height, heights[col]);
+        if col + 1 < width {
+            features.bumpiness += (heights[col] - heights[col + 1]).abs();
+        }
+        let left: i32 = if col > 0 { heights[col - 1] } else { rows };
+        let right: i32 = if col + 1 < width {
+            heights[col + 1]
+        } else {
+            rows
+        };
+        let depth: i32 = min(left, right) - heights[col];
+        if depth > 0 {
+            features.wells += depth * (depth + 1) / 2;
+        }
+    }
+    return features;
+}
+
+pub fn evaluate_board(board: &Board, lines: i32, weights: &Ai_Weights) -> f32 {
+    let features: Board_Features = board_features(board);
+    let mut score: f32 = weights.aggregate_height * features.aggregate_height as f32
+        + weights.lines * lines as f32
+        + weights.holes * features.holes as f32
+        + weights.bumpiness * features.bumpiness as f32
+        + weights.wells * features.wells as f32;
+    let hidden_cells: usize = (HIDDEN_ROWS * board.width()) as usize;
+    if board.cells[..hidden_cells].iter().any(|v| *v != 0) {
+        score -= TOP_OUT_PENALTY;
+    }
+    return score;
+}
+
+// Calls `visit` with every reachable placement of the piece and the board
+// that results from it.
+fn for_each_placement(
+    board: &Board,
+    pieces: &Piece_Set,
+    index: u8,
+    mut visit: impl FnMut(i32, i32, &Board, i32),
+) {
+    let side: i32 = pieces.shape(index).side;
+    for rotation in 0..4 {
+        for col in -side..board.width() {
+            if let Some(row) = find_landing_row(board, pieces, index, rotation, col) {
+                let mut result: Board = board.clone();
+                place_piece(&mut result, pieces, index, rotation, row, col);
+                let lines: i32 = clear_full_rows(&mut result);
+                visit(rotation, col, &result, lines);
+            }
+        }
+    }
+}
+
+// Scores every placement of `current`, looking one piece ahead with `next`.
+// The result is sorted from best to worst.
+pub fn find_placements(
+    board: &Board,
+    pieces: &Piece_Set,
+    current: u8,
+    next: u8,
+    weights: &Ai_Weights,
+) -> Vec<Placement> {
+    let mut placements: Vec<Placement> = Vec::new();
+    for_each_placement(board, pieces, current, |rotation, col, result, lines| {
+        let mut best: f32 = f32::NEG_INFINITY;
+        for_each_placement(result, pieces, next, |_, _, second, second_lines| {
+            let score: f32 = evaluate_board(second, lines + second_lines, weights);
+            if score > best {
+                best = score;
+            }
+        });
+        if best == f32::NEG_INFINITY {
+            best = evaluate_board(result, lines, weights) - TOP_OUT_PENALTY;
+        }
+        placements.push(Placement {
+            rotation: rotation,
+            col: col,
+            score: best,
+        });
+    });
+    placements.sort_by(|a, b| b.score.total_cmp(&a.score));
+    return placements;
+}
+
+// Turns a target placement into the same per-frame Input_State a keyboard
+// would produce: rotate, shift, then hard drop. Buttons are released every
+// other frame so each press registers as a new edge.
+pub struct Placement_Driver {
+    target: Option<Placement>,
+    move_attempts: u32,
+    prev_input: Input_State,
+}
+
+impl Placement_Driver {
+    pub fn new() -> Self {
+        Placement_Driver {
+            target: None,
+            move_attempts: 0,
+            prev_input: Input_State::new(),
+        }
+    }
+    pub fn set_target(&mut self, target: Option<Placement>) {
+        self.target = target;
+        self.move_attempts = 0;
+    }
+    pub fn has_target(&self) -> bool {
+        return self.target.is_some();
+    }
+    // Without a target the piece is dropped where it is. `drop_allowed`
+    // holds the piece once it is lined up.
+    pub fn next_input(&mut self, game: &Game_State, drop_allowed: bool) -> Input_State {
+        let mut input: Input_State = Input_State::new();
+        let prev: Input_State = self.prev_input;
+        let released: bool =
+            prev.left == 0 && prev.right == 0 && prev.up == 0 && prev.down == 0 && prev.a == 0;
+        if game.phase == Game_Phase::GAME_PHASE_PLAY && released {
+            let piece: &Piece_State = &game.piece;
+            let mut target_rotation: i32 = piece.get_rotation();
+            let mut target_col: i32 = piece.get_offset_col();
+            if let Some(target) = self.target {
+                target_rotation = target.rotation;
+                target_col = target.col;
+            }
+            if self.move_attempts > MAX_MOVE_ATTEMPTS {
+                target_rotation = piece.get_rotation();
+                target_col = piece.get_offset_col();
+            }
+            if piece.get_rotation() != target_rotation {
+                input.up = 1;
+                self.move_attempts += 1;
+            } else if piece.get_offset_col() < target_col {
+                input.right = 1;
+                self.move_attempts += 1;
+            } else if piece.get_offset_col() > target_col {
+                input.left = 1;
+                self.move_attempts += 1;
+            } else if drop_allowed {
+                input.a = 1;
+            }
+        }
+        input.update_edges(&prev);
+        self.prev_input = input;
+        return input;
+    }
+}
+
+// Plays the game through a Placement_Driver, so it follows the same rules
+// as a human player.
+pub struct Ai_Player {
+    pub weights: Ai_Weights,
+    pub difficulty: Ai_Difficulty,
+    driver: Placement_Driver,
+    planned_piece: u32,
+    last_drop_time: f32,
+}
+
+impl Ai_Player {
+    pub fn new(weights: Ai_Weights, difficulty: Ai_Difficulty) -> Self {
+        Ai_Player {
+            weights: weights,
+            difficulty: difficulty,
+            driver: Placement_Driver::new(),
+            planned_piece: 0,
+            last_drop_time: 0.0,
+        }
+    }
+    fn plan(&mut self, game: &Game_State) {
+        let placements: Vec<Placement> = find_placements(
+            game.get_board(),
+            game.pieces(),
+            game.piece.get_tetr_index(),
+            game.piece_next.get_tetr_index(),
+            &self.weights,
+        );
+        self.planned_piece = game.piece_count;
+        if placements.is_empty() {
+            self.driver.set_target(None);
+            return;
+        }
+        let mut rng = rand::rng();
+        let mut choice: usize = 0;
+        if rng.random::<f32>() < self.difficulty.mistake_chance {
+            let spread: usize = placements.len().min(MISTAKE_SPREAD);
+            choice = rng.random_range(0..spread);
+        }
+        self.driver.set_target(Some(placements[choice]));
+    }
+    fn ready_to_drop(&self, game: &Game_State) -> bool {
+        if self.difficulty.pieces_per_second <= 0.0 {
+            return true;
+        }
+        return game.get_time() >= self.last_drop_time + 1.0 / self.difficulty.pieces_per_second;
+    }
+    pub fn next_input(&mut self, game: &Game_State) -> Input_State {
+        if game.phase == Game_Phase::GAME_PHASE_PLAY && self.planned_piece != game.piece_count {
+            self.plan(game);
+        }
+        let input: Input_State = self.driver.next_input(game, self.ready_to_drop(game));
+        if input.da > 0 {
+            self.last_drop_time = game.get_time();
+        }
+        return input;
+    }
+}
+
+pub struct Headless_Result {
+    pub lines: i32,
+    pub pieces: u32,
+    pub frames: u32,
+}
+
+// Plays a whole seeded game without a window, stepping the clock one frame
+// at a time, until the game is over or `max_pieces` have been spawned.
+pub fn play_headless_game(seed: u64, weights: Ai_Weights, max_pieces: u32) -> Headless_Result {
+    let mut game: Game_State = Game_State::new_seeded(seed);
+    let mut player: Ai_Player = Ai_Player::new(weights, AI_MAX);
+    game.start_level = 0;
+    game.start();
+    let max_frames: u32 = max_pieces * 600;
+    let mut frame: u32 = 0;
+    while game.phase != Game_Phase::GAME_PHASE_GAMEOVER
+        && game.piece_count < max_pieces
+        && frame < max_frames
+    {
+        game.set_time(frame as f32 * TARGET_SECONDS_PER_FRAME);
+        let input: Input_State = player.next_input(&game);
+        game.update(&input);
+        game.drain_events();
+        frame += 1;
+    }
+    return Headless_Result {
+        lines: game.line_count,
+        pieces: game.piece_count,
+        frames: frame,
+    };
+}
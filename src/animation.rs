@@ -0,0 +1,266 @@
+// Visual effects started by game events: cleared rows bursting into
+// particles, hard drop trails, a flash when a piece locks, a shake on a
+// tetris and a banner on level up. They only decorate the board and never
+// change how a game plays.
+//
+// Everything is timed with the game clock. Positions are in cells from the
+// top left corner of the visible board.
+use super::game::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+pub const PARTICLE_TIME: f32 = 0.8;
+pub const TRAIL_TIME: f32 = 0.25;
+pub const LOCK_FLASH_TIME: f32 = 0.15;
+pub const SHAKE_TIME: f32 = 0.4;
+pub const BANNER_TIME: f32 = 1.5;
+// How long a sideways move takes to slide into place when motion is
+// smoothed.
+pub const SHIFT_EASE_TIME: f32 = 0.06;
+
+const PARTICLES_PER_CELL: usize = 3;
+// In cells per second, and cells per second squared.
+const PARTICLE_SPEED: f32 = 8.0;
+const PARTICLE_GRAVITY: f32 = 30.0;
+// Largest shake, in cells.
+const SHAKE_AMPLITUDE: f32 = 0.3;
+
+#[derive(Clone, Copy)]
+pub struct Particle {
+    pub x: f32,
+    pub y: f32,
+    pub vx: f32,
+    pub vy: f32,
+    // The cell value it came from, for its color.
+    pub value: u8,
+    pub start: f32,
+}
+
+impl Particle {
+    pub fn position(&self, time: f32) -> (f32, f32) {
+        let t: f32 = time - self.start;
+        return (
+            self.x + self.vx * t,
+            self.y + self.vy * t + 0.5 * PARTICLE_GRAVITY * t * t,
+        );
+    }
+}
+
+// The piece where it landed and how far it fell.
+#[derive(Clone, Copy)]
+pub struct Drop_Trail {
+    pub piece: Piece_State,
+    pub distance: i32,
+    pub start: f32,
+}
+
+#[derive(Clone, Copy)]
+pub struct Lock_Flash {
+    pub piece: Piece_State,
+    pub start: f32,
+}
+
+#[derive(Clone, Copy)]
+pub struct Level_Banner {
+    pub level: i32,
+    pub start: f32,
+}
+
+// How far an animation of `duration` seconds that started at `start` has
+// got by `time`, from 0 to 1.
+pub fn progress(start: f32, duration: f32, time: f32) -> f32 {
+    return ((time - start) / duration).clamp(0.0, 1.0);
+}
+
+// The animations running on one board.
+pub struct Animator {
+    // A disabled animator ignores events and plays nothing.
+    pub enabled: bool,
+    pub particles: Vec<Particle>,
+    pub trails: Vec<Drop_Trail>,
+    pub flashes: Vec<Lock_Flash>,
+    pub banner: Option<Level_Banner>,
+    shake_start: Option<f32>,
+    rng: StdRng,
+}
+
+impl Animator {
+    pub fn new() -> Self {
+        Animator {
+            enabled: true,
+            particles: Vec::new(),
+            trails: Vec::new(),
+            flashes: Vec::new(),
+            banner: None,
+            shake_start: None,
+            rng: StdRng::seed_from_u64(0),
+        }
+    }
+    // Ends everything that is still playing.
+    pub fn skip(&mut self) {
+        self.particles.clear();
+        self.trails.clear();
+        self.flashes.clear();
+        self.banner = None;
+        self.shake_start = None;
+    }
+    // Call with the events drained after Game_State::update, while the
+    // board still holds the rows that are being cleared.
+    pub fn handle_events(&mut self, events: &[Game_Event], game: &Game_State) {
+        if !self.enabled {
+            return;
+        }
+        let time: f32 = game.get_time();
+        for event in events.iter() {
+            match *event {
+                Game_Event::EVENT_GAME_START => self.skip(),
+                Game_Event::EVENT_HARD_DROP { piece, distance } if distance > 0 => {
+                    self.trails.push(Drop_Trail {
+                        piece: piece,
+                        distance: distance,
+                        start: time,
+                    });
+                }
+                Game_Event::EVENT_PIECE_LOCKED { .. } => {
+                    if let Some(piece) = game.last_locked {
+                        self.flashes.push(Lock_Flash {
+                            piece: piece,
+                            start: time,
+                        });
+                    }
+                    if game.phase == Game_Phase::GAME_PHASE_LINE {
+                        self.burst_rows(game, time);
+                        if game.pending_line_count >= 4 {
+                            self.shake_start = Some(time);
+                        }
+                    }
+                }
+                Game_Event::EVENT_LEVEL_UP { level } => {
+                    self.banner = Some(Level_Banner {
+                        level: level,
+                        start: time,
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+    // Throws particles up and out of every cell in the rows being cleared.
+    fn burst_rows(&mut self, game: &Game_State, time: f32) {
+        let size: Board_Size = game.board_size();
+        for row in 0..size.rows() {
+            if game.lines[row as usize] == 0 {
+                continue;
+            }
+            for col in 0..size.width {
+                let value: u8 = game.matrix_get(row, col);
+                if value == 0 {
+                    continue;
+                }
+                for _ in 0..PARTICLES_PER_CELL {
+                    let angle: f32 = self.rng.random_range(0.0..std::f32::consts::TAU);
+                    let speed: f32 = self.rng.random_range(0.3..1.0) * PARTICLE_SPEED;
+                    self.particles.push(Particle {
+                        x: col as f32 + 0.5,
+                        y: (row - HIDDEN_ROWS) as f32 + 0.5,
+                        vx: angle.cos() * speed,
+                        vy: angle.sin() * speed - PARTICLE_SPEED / 2.0,
+                        value: value,
+                        start: time,
+                    });
+                }
+            }
+        }
+    }
+    // Drops animations that have finished. Nothing plays over the pause
+    // screen.
+    pub fn update(&mut self, game: &Game_State) {
+        if !self.enabled || game.phase == Game_Phase::GAME_PHASE_PAUSE {
+            self.skip();
+            return;
+        }
+        let time: f32 = game.get_time();
+        self.particles.retain(|p| time - p.start < PARTICLE_TIME);
+        self.trails.retain(|t| time - t.start < TRAIL_TIME);
+        self.flashes.retain(|f| time - f.start < LOCK_FLASH_TIME);
+        if let Some(banner) = self.banner
+            && time - banner.start >= BANNER_TIME
+        {
+            self.banner = None;
+        }
+        if let Some(start) = self.shake_start
+            && time - start >= SHAKE_TIME
+        {
+            self.shake_start = None;
+        }
+    }
+    // How far the board is pushed from its place, in cells. The shake
+    // dies down over SHAKE_TIME.
+    pub fn shake_offset(&self, time: f32) -> (f32, f32) {
+        let Some(start) = self.shake_start else {
+            return (0.0, 0.0);
+        };
+        let t: f32 = time - start;
+        if !(0.0..SHAKE_TIME).contains(&t) {
+            return (0.0, 0.0);
+        }
+        let strength: f32 = SHAKE_AMPLITUDE * (1.0 - t / SHAKE_TIME);
+        return ((t * 90.0).sin() * strength, (t * 70.0).cos() * strength);
+    }
+}
+
+// Where the falling piece is drawn when motion is smoothed. The game still
+// moves it a whole cell at a time; this only eases the drawing between
+// cells.
+pub struct Piece_Motion {
+    // Columns the drawn piece was behind the real one at shift_start.
+    shift_from: f32,
+    shift_start: f32,
+}
+
+impl Piece_Motion {
+    pub fn new() -> Self {
+        Piece_Motion {
+            shift_from: 0.0,
+            shift_start: 0.0,
+        }
+    }
+    pub fn handle_events(&mut self, events: &[Game_Event], game: &Game_State) {
+        let time: f32 = game.get_time();
+        for event in events.iter() {
+            match *event {
+                Game_Event::EVENT_PIECE_MOVED { step_col, .. } if step_col != 0 => {
+                    let lag: f32 = self.column_lag(time) - step_col as f32;
+                    let width: f32 = game.board_size().width as f32;
+                    self.shift_from = lag.clamp(-width, width);
+                    self.shift_start = time;
+                }
+                // Anything else that moves the piece snaps it into place.
+                Game_Event::EVENT_GAME_START
+                | Game_Event::EVENT_PIECE_SPAWNED { .. }
+                | Game_Event::EVENT_PIECE_ROTATED { .. }
+                | Game_Event::EVENT_HARD_DROP { .. }
+                | Game_Event::EVENT_HOLD_USED { .. } => self.shift_from = 0.0,
+                _ => {}
+            }
+        }
+    }
+    // Columns the drawn piece is behind the real one, easing out to 0.
+    pub fn column_lag(&self, time: f32) -> f32 {
+        let left: f32 = 1.0 - progress(self.shift_start, SHIFT_EASE_TIME, time);
+        return self.shift_from * left * left;
+    }
+    // Rows below its cell the piece is drawn: how far it is towards the
+    // next gravity step, or 0 while it rests on the stack.
+    pub fn row_offset(&self, game: &Game_State) -> f32 {
+        if game.phase != Game_Phase::GAME_PHASE_PLAY {
+            return 0.0;
+        }
+        let mut below: Piece_State = game.piece;
+        below.move_down();
+        if !below.check_piece_valid(game) {
+            return 0.0;
+        }
+        return game.drop_progress();
+    }
+}
@@ -0,0 +1,239 @@
+// Decides which sounds a game makes, independent of how they are played.
+// Audio_Player turns game events and the game state into calls on an
+// Audio_Backend: SDL_mixer in the game, or a null or recording backend for
+// tests and machines without a sound device.
+use super::game::*;
+use super::settings::*;
+
+// The music speeds up once the stack comes within this many rows of the
+// top of the board, hidden rows included. It slows down again once the
+// stack is two rows lower.
+pub const MUSIC_FAST_GAP: i32 = 8;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Sound_Effect {
+    SFX_MOVE,
+    SFX_ROTATE,
+    SFX_LOCK,
+    SFX_LINE_CLEAR,
+    SFX_TETRIS,
+    SFX_LEVEL_UP,
+    SFX_GAME_OVER,
+}
+
+pub const SOUND_EFFECT_COUNT: usize = 7;
+
+pub static SOUND_EFFECTS: [Sound_Effect; SOUND_EFFECT_COUNT] = [
+    Sound_Effect::SFX_MOVE,
+    Sound_Effect::SFX_ROTATE,
+    Sound_Effect::SFX_LOCK,
+    Sound_Effect::SFX_LINE_CLEAR,
+    Sound_Effect::SFX_TETRIS,
+    Sound_Effect::SFX_LEVEL_UP,
+    Sound_Effect::SFX_GAME_OVER,
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Music_Track {
+    MUSIC_NORMAL,
+    MUSIC_FAST,
+}
+
+pub trait Audio_Backend {
+    // Called once per frame before anything is played.
+    fn start_frame(&mut self, _frame: u64) {}
+    fn play_effect(&mut self, effect: Sound_Effect);
+    // Starts the track from the beginning and loops it. None stops the
+    // music.
+    fn play_music(&mut self, track: Option<Music_Track>);
+    fn pause_music(&mut self, paused: bool);
+    // Volumes run from 0 to MAX_VOLUME.
+    fn set_volumes(&mut self, music_volume: i32, sfx_volume: i32, muted: bool);
+}
+
+// Lets the game pick its backend at run time.
+impl<T: Audio_Backend + ?Sized> Audio_Backend for Box<T> {
+    fn start_frame(&mut self, frame: u64) {
+        (**self).start_frame(frame);
+    }
+    fn play_effect(&mut self, effect: Sound_Effect) {
+        (**self).play_effect(effect);
+    }
+    fn play_music(&mut self, track: Option<Music_Track>) {
+        (**self).play_music(track);
+    }
+    fn pause_music(&mut self, paused: bool) {
+        (**self).pause_music(paused);
+    }
+    fn set_volumes(&mut self, music_volume: i32, sfx_volume: i32, muted: bool) {
+        (**self).set_volumes(music_volume, sfx_volume, muted);
+    }
+}
+
+// Plays nothing.
+pub struct Null_Audio;
+
+impl Audio_Backend for Null_Audio {
+    fn play_effect(&mut self, _effect: Sound_Effect) {}
+    fn play_music(&mut self, _track: Option<Music_Track>) {}
+    fn pause_music(&mut self, _paused: bool) {}
+    fn set_volumes(&mut self, _music_volume: i32, _sfx_volume: i32, _muted: bool) {}
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Audio_Call {
+    CALL_EFFECT(Sound_Effect),
+    CALL_MUSIC(Option<Music_Track>),
+    CALL_PAUSE(bool),
+    CALL_VOLUMES(i32, i32, bool),
+}
+
+// Logs every call with the frame it was made in.
+pub struct Recording_Audio {
+    pub frame: u64,
+    pub calls: Vec<(u64, Audio_Call)>,
+}
+
+impl Recording_Audio {
+    pub fn new() -> Self {
+        Recording_Audio {
+            frame: 0,
+            calls: Vec::new(),
+        }
+    }
+    pub fn effects(&self) -> Vec<(u64, Sound_Effect)> {
+        let mut effects: Vec<(u64, Sound_Effect)> = Vec::new();
+        for (frame, call) in self.calls.iter() {
+            if let Audio_Call::CALL_EFFECT(effect) = call {
+                effects.push((*frame, *effect));
+            }
+        }
+        return effects;
+    }
+}
+
+impl Audio_Backend for Recording_Audio {
+    fn start_frame(&mut self, frame: u64) {
+        self.frame = frame;
+    }
+    fn play_effect(&mut self, effect: Sound_Effect) {
+        self.calls
+            .push((self.frame, Audio_Call::CALL_EFFECT(effect)));
+    }
+    fn play_music(&mut self, track: Option<Music_Track>) {
+        self.calls.push((self.frame, Audio_Call::CALL_MUSIC(track)));
+    }
+    fn pause_music(&mut self, paused: bool) {
+        self.calls
+            .push((self.frame, Audio_Call::CALL_PAUSE(paused)));
+    }
+    fn set_volumes(&mut self, music_volume: i32, sfx_volume: i32, muted: bool) {
+        self.calls.push((
+            self.frame,
+            Audio_Call::CALL_VOLUMES(music_volume, sfx_volume, muted),
+        ));
+    }
+}
+
+// Which effect an event plays, if any. Pieces falling by themselves are
+// silent, only sideways moves click.
+pub fn event_effect(event: &Game_Event) -> Option<Sound_Effect> {
+    match *event {
+        Game_Event::EVENT_PIECE_MOVED { step_col, .. } if step_col != 0 => {
+            return Some(Sound_Effect::SFX_MOVE);
+        }
+        Game_Event::EVENT_PIECE_ROTATED { .. } => return Some(Sound_Effect::SFX_ROTATE),
+        Game_Event::EVENT_PIECE_LOCKED { .. } => return Some(Sound_Effect::SFX_LOCK),
+        Game_Event::EVENT_LINES_CLEARED { kind, .. } => {
+            if kind == Clear_Kind::CLEAR_TETRIS {
+                return Some(Sound_Effect::SFX_TETRIS);
+            }
+            return Some(Sound_Effect::SFX_LINE_CLEAR);
+        }
+        Game_Event::EVENT_LEVEL_UP { .. } => return Some(Sound_Effect::SFX_LEVEL_UP),
+        Game_Event::EVENT_GAME_OVER { .. } => return Some(Sound_Effect::SFX_GAME_OVER),
+        _ => return None,
+    }
+}
+
+// Height of the highest occupied cell, counted in rows from the floor.
+pub fn stack_height(game: &Game_State) -> i32 {
+    let size: Board_Size = game.board_size();
+    for row in 0..size.rows() {
+        for col in 0..size.width {
+            if game.matrix_get(row, col) > 0 {
+                return size.rows() - row;
+            }
+        }
+    }
+    return 0;
+}
+
+pub struct Audio_Player<B: Audio_Backend> {
+    pub backend: B,
+    pub frame: u64,
+    music: Option<Music_Track>,
+    paused: bool,
+    volumes: Option<(i32, i32, bool)>,
+}
+
+impl<B: Audio_Backend> Audio_Player<B> {
+    pub fn new(backend: B) -> Self {
+        Audio_Player {
+            backend: backend,
+            frame: 0,
+            music: None,
+            paused: false,
+            volumes: None,
+        }
+    }
+    pub fn start_frame(&mut self) {
+        self.frame += 1;
+        self.backend.start_frame(self.frame);
+    }
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        let volumes: (i32, i32, bool) =
+            (settings.music_volume, settings.sfx_volume, settings.muted);
+        if self.volumes == Some(volumes) {
+            return;
+        }
+        self.volumes = Some(volumes);
+        self.backend.set_volumes(volumes.0, volumes.1, volumes.2);
+    }
+    pub fn handle_events(&mut self, events: &[Game_Event]) {
+        for event in events.iter() {
+            if let Some(effect) = event_effect(event) {
+                self.backend.play_effect(effect);
+            }
+        }
+    }
+    // The music plays during a game, pauses with it and stops when it
+    // ends.
+    pub fn update_music(&mut self, game: &Game_State) {
+        let paused: bool = game.phase == Game_Phase::GAME_PHASE_PAUSE;
+        let track: Option<Music_Track> = match game.phase {
+            Game_Phase::GAME_PHASE_PLAY | Game_Phase::GAME_PHASE_LINE => {
+                let gap: i32 = game.board_size().rows() - stack_height(game);
+                let fast: bool = match self.music {
+                    Some(Music_Track::MUSIC_FAST) => gap <= MUSIC_FAST_GAP + 2,
+                    _ => gap <= MUSIC_FAST_GAP,
+                };
+                if fast {
+                    Some(Music_Track::MUSIC_FAST)
+                } else {
+                    Some(Music_Track::MUSIC_NORMAL)
+                }
+            }
+            Game_Phase::GAME_PHASE_PAUSE => self.music,
+            _ => None,
+        };
+        if paused != self.paused {
+            self.paused = paused;
+            self.backend.pause_music(paused);
+        }
+        if track != self.music {
+            self.music = track;
+            self.backend.play_music(track);
+        }
+    }
+}
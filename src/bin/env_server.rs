@@ -0,0 +1,256 @@
+// JSON-lines bridge to the vectorized environment so trainers in other
+// languages can drive it over stdin/stdout. One request per line:
+//
+//   {"type": "config", "rewards": {"lines": [0, 1, 3, 5, 8], "game_over": -10}, "frames_per_step": 1}
+//   {"type": "config", "width": 10, "height": 20, "pieces": "pentomino"}
+//   {"type": "reset", "seed": 1, "envs": 8, "vector": false}
+//   {"type": "step", "actions": [0, 5, 1, 2, 3, 4, 0, 5]}
+//
+// Actions are indices into env::ACTIONS, one for every environment. A
+// reset makes from 1 to MAX_ENVS environments. Every request gets one reply
+// line, an error for a request that is invalid in any part, and then none
+// of it is applied. The server stops when either end of the pipe closes.
+// Board cells are 0 when empty, the piece index plus one, or 255 for
+// garbage. Piece sets are looked up by name like in the game.
+use serde_json::Value;
+use serde_json::json;
+use std::io::BufRead;
+use std::io::Write;
+use tetris::env::*;
+use tetris::game::*;
+use tetris::pieces::*;
+
+// Keeps one reset from asking for more memory than a trainer needs.
+const MAX_ENVS: i64 = 4096;
+
+// Settings for the next reset. A config request is checked in full
+// before any of it is applied.
+#[derive(Clone)]
+struct Server_Config {
+    rewards: Reward_Config,
+    frames_per_step: u32,
+    board_size: Board_Size,
+    pieces: Piece_Set,
+}
+
+fn number(config: &Value, name: &str) -> Result<Option<f32>, String> {
+    if config[name].is_null() {
+        return Ok(None);
+    }
+    let value: f64 = config[name]
+        .as_f64()
+        .ok_or(format!("{} must be a number", name))?;
+    return Ok(Some(value as f32));
+}
+
+fn parse_rewards(rewards: &mut Reward_Config, config: &Value) -> Result<(), String> {
+    if config.is_null() {
+        return Ok(());
+    }
+    if !config.is_object() {
+        return Err("rewards must be an object".to_string());
+    }
+    if !config["lines"].is_null() {
+        let lines: &Vec<Value> = config["lines"]
+            .as_array()
+            .filter(|l| l.len() <= rewards.lines.len())
+            .ok_or(format!(
+                "lines must be a list of up to {} numbers",
+                rewards.lines.len()
+            ))?;
+        for (i, value) in lines.iter().enumerate() {
+            rewards.lines[i] = value.as_f64().ok_or("lines must be numbers")? as f32;
+        }
+    }
+    let fields: [(&str, &mut f32); 5] = [
+        ("step", &mut rewards.step),
+        ("game_over", &mut rewards.game_over),
+        ("hole", &mut rewards.hole),
+        ("height", &mut rewards.height),
+        ("bumpiness", &mut rewards.bumpiness),
+    ];
+    for (name, field) in fields {
+        if let Some(value) = number(config, name)? {
+            *field = value;
+        }
+    }
+    return Ok(());
+}
+
+// An integer in `min..=max` if `name` is set.
+fn integer(request: &Value, name: &str, min: i64, max: i64) -> Result<Option<i64>, String> {
+    if request[name].is_null() {
+        return Ok(None);
+    }
+    let value: i64 = request[name]
+        .as_i64()
+        .filter(|v| (min..=max).contains(v))
+        .ok_or(format!(
+            "{} must be a whole number from {} to {}",
+            name, min, max
+        ))?;
+    return Ok(Some(value));
+}
+
+fn parse_config(current: &Server_Config, request: &Value) -> Result<Server_Config, String> {
+    let mut config: Server_Config = current.clone();
+    parse_rewards(&mut config.rewards, &request["rewards"])?;
+    if let Some(frames) = integer(request, "frames_per_step", 1, u32::MAX as i64)? {
+        config.frames_per_step = frames as u32;
+    }
+    // The number of visible rows; observations count the hidden rows too.
+    if let Some(width) = integer(
+        request,
+        "width",
+        MIN_BOARD_WIDTH as i64,
+        MAX_BOARD_WIDTH as i64,
+    )? {
+        config.board_size.width = width as i32;
+    }
+    if let Some(height) = integer(
+        request,
+        "height",
+        MIN_BOARD_HEIGHT as i64,
+        MAX_BOARD_HEIGHT as i64,
+    )? {
+        config.board_size.height = height as i32;
+    }
+    if !request["pieces"].is_null() {
+        let name: &str = request["pieces"]
+            .as_str()
+            .ok_or("pieces must be a piece set name")?;
+        config.pieces = find_piece_set(name).ok_or(format!("unknown piece set {}", name))?;
+    }
+    if !config.pieces.fits(config.board_size) {
+        return Err(format!(
+            "the {} pieces don't fit a {} board",
+            config.pieces.name,
+            config.board_size.name()
+        ));
+    }
+    return Ok(config);
+}
+
+// One valid action per environment.
+fn parse_actions(request: &Value, count: usize) -> Result<Vec<Env_Action>, String> {
+    let values: &Vec<Value> = request["actions"]
+        .as_array()
+        .ok_or("actions must be a list")?;
+    if values.len() != count {
+        return Err(format!(
+            "got {} actions for {} environments",
+            values.len(),
+            count
+        ));
+    }
+    let mut actions: Vec<Env_Action> = Vec::new();
+    for value in values.iter() {
+        let action: Option<Env_Action> = value.as_u64().and_then(|i| action_from_index(i as usize));
+        actions.push(action.ok_or(format!("bad action {}", value))?);
+    }
+    return Ok(actions);
+}
+
+// The environment count, first seed and observation format of a reset.
+fn parse_reset(request: &Value) -> Result<(usize, u64, bool), String> {
+    let count: i64 = integer(request, "envs", 1, MAX_ENVS)?.unwrap_or(1);
+    let mut seed: u64 = 0;
+    if !request["seed"].is_null() {
+        seed = request["seed"]
+            .as_u64()
+            .ok_or("seed must be a whole number from 0 to 2^64 - 1")?;
+    }
+    let mut vector: bool = false;
+    if !request["vector"].is_null() {
+        vector = request["vector"]
+            .as_bool()
+            .ok_or("vector must be true or false")?;
+    }
+    return Ok((count as usize, seed, vector));
+}
+
+fn send(stdout: &mut dyn Write, reply: &Value) -> std::io::Result<()> {
+    writeln!(stdout, "{}", reply)?;
+    return stdout.flush();
+}
+
+fn observation_json(observation: &Observation, vector: bool) -> Value {
+    if vector {
+        return json!(observation.to_vector());
+    }
+    return observation.to_json();
+}
+
+fn main() {
+    let mut config: Server_Config = Server_Config {
+        rewards: DEFAULT_REWARDS,
+        frames_per_step: 1,
+        board_size: DEFAULT_BOARD_SIZE,
+        pieces: standard_pieces(),
+    };
+    let mut vector: bool = false;
+    let mut vec_env: Vec_Env = Vec_Env::new(1, config.rewards);
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout().lock();
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: Value = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                let reply: Value = json!({"type": "error", "reason": e.to_string()});
+                if send(&mut stdout, &reply).is_err() {
+                    break;
+                }
+                continue;
+            }
+        };
+        let reply: Value = match request["type"].as_str().unwrap_or("") {
+            "config" => match parse_config(&config, &request) {
+                Ok(parsed) => {
+                    config = parsed;
+                    json!({"type": "ok"})
+                }
+                Err(e) => json!({"type": "error", "reason": e}),
+            },
+            "reset" => match parse_reset(&request) {
+                Ok((count, seed, as_vector)) => {
+                    vector = as_vector;
+                    vec_env = Vec_Env::new(count, config.rewards);
+                    for env in vec_env.envs.iter_mut() {
+                        env.frames_per_step = config.frames_per_step;
+                        env.board_size = config.board_size;
+                        env.pieces = config.pieces.clone();
+                    }
+                    let observations: Vec<Value> = vec_env
+                        .reset(seed)
+                        .iter()
+                        .map(|o| observation_json(o, vector))
+                        .collect();
+                    json!({"type": "reset", "observations": observations})
+                }
+                Err(e) => json!({"type": "error", "reason": e}),
+            },
+            "step" => match parse_actions(&request, vec_env.envs.len()) {
+                Ok(actions) => {
+                    let results = vec_env.step(&actions);
+                    json!({
+                        "type": "step",
+                        "observations": results.iter().map(|r| observation_json(&r.0, vector)).collect::<Vec<Value>>(),
+                        "rewards": results.iter().map(|r| r.1).collect::<Vec<f32>>(),
+                        "dones": results.iter().map(|r| r.2).collect::<Vec<bool>>(),
+                    })
+                }
+                Err(e) => json!({"type": "error", "reason": e}),
+            },
+            other => json!({"type": "error", "reason": format!("unknown request {}", other)}),
+        };
+        if send(&mut stdout, &reply).is_err() {
+            break;
+        }
+    }
+}
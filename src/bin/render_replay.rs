@@ -0,0 +1,9 @@
+// Replay clips without SDL, for machines that only export them:
+//   cargo run --no-default-features --bin render_replay -- in.replay out.gif
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = tetris::export::run_render_replay(&args) {
+        eprintln!("render-replay: {}", e);
+        std::process::exit(1);
+    }
+}
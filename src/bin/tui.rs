@@ -0,0 +1,15 @@
+// The terminal frontend on its own, so it can be built without SDL:
+//   cargo run --no-default-features --bin tui
+#[cfg(unix)]
+fn main() {
+    if let Err(e) = tetris::tui::run_tui() {
+        eprintln!("tui: {}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("tui: the terminal frontend needs a Unix terminal");
+    std::process::exit(1);
+}
@@ -0,0 +1,205 @@
+use rand::Rng;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::time::Instant;
+use tetris::ai::*;
+
+const WEIGHT_COUNT: usize = 5;
+
+struct Tune_Options {
+    generations: u32,
+    population: usize,
+    games: u32,
+    max_pieces: u32,
+    threads: usize,
+    seed: u64,
+}
+
+#[derive(Clone, Copy)]
+struct Candidate {
+    weights: [f32; WEIGHT_COUNT],
+    fitness: f32,
+}
+
+const TUNE_USAGE: &str = "usage: tune [--generations N] [--population N] [--games N] \
+[--pieces N] [--threads N] [--seed N]";
+
+// A number above 0 for `flag`.
+fn positive<T: std::str::FromStr + PartialOrd + From<u8>>(
+    flag: &str,
+    value: Option<&String>,
+) -> Result<T, String> {
+    return value
+        .and_then(|v| v.parse().ok())
+        .filter(|n: &T| *n > T::from(0))
+        .ok_or(format!("{} needs a whole number above 0", flag));
+}
+
+fn parse_options() -> Result<Tune_Options, String> {
+    let mut options: Tune_Options = Tune_Options {
+        generations: 20,
+        population: 50,
+        games: 20,
+        max_pieces: 500,
+        threads: std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(4),
+        seed: 1,
+    };
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut i: usize = 0;
+    while i < args.len() {
+        let flag: &str = args[i].as_str();
+        let value: Option<&String> = args.get(i + 1);
+        match flag {
+            "--generations" => options.generations = positive(flag, value)?,
+            "--population" => options.population = positive(flag, value)?,
+            "--games" => options.games = positive(flag, value)?,
+            "--pieces" => options.max_pieces = positive(flag, value)?,
+            "--threads" => options.threads = positive(flag, value)?,
+            "--seed" => {
+                options.seed = value
+                    .and_then(|v| v.parse().ok())
+                    .ok_or("--seed needs a whole number")?
+            }
+            other => return Err(format!("unknown option {}", other)),
+        }
+        i += 2;
+    }
+    return Ok(options);
+}
+
+fn to_ai_weights(weights: &[f32; WEIGHT_COUNT]) -> Ai_Weights {
+    return Ai_Weights {
+        aggregate_height: weights[0],
+        lines: weights[1],
+        holes: weights[2],
+        bumpiness: weights[3],
+        wells: weights[4],
+    };
+}
+
+fn normalize(weights: &mut [f32; WEIGHT_COUNT]) {
+    let length: f32 = weights.iter().map(|w| w * w).sum::<f32>().sqrt();
+    if length > 0.0 {
+        for w in weights.iter_mut() {
+            *w /= length;
+        }
+    }
+}
+
+fn random_candidate(rng: &mut StdRng) -> Candidate {
+    let mut weights: [f32; WEIGHT_COUNT] = [0.0; WEIGHT_COUNT];
+    for w in weights.iter_mut() {
+        *w = rng.random_range(-1.0..1.0);
+    }
+    normalize(&mut weights);
+    return Candidate {
+        weights: weights,
+        fitness: 0.0,
+    };
+}
+
+// Every candidate of a generation plays the same seeds so their average
+// line counts are comparable.
+fn evaluate_population(population: &mut [Candidate], seeds: &[u64], options: &Tune_Options) {
+    let chunk_size: usize = population.len().div_ceil(options.threads.max(1));
+    std::thread::scope(|scope| {
+        for chunk in population.chunks_mut(chunk_size) {
+            scope.spawn(move || {
+                for candidate in chunk.iter_mut() {
+                    let weights: Ai_Weights = to_ai_weights(&candidate.weights);
+                    let mut total_lines: i32 = 0;
+                    for seed in seeds {
+                        total_lines += play_headless_game(*seed, weights, options.max_pieces).lines;
+                    }
+                    candidate.fitness = total_lines as f32 / seeds.len() as f32;
+                }
+            });
+        }
+    });
+}
+
+fn tournament_select(population: &[Candidate], rng: &mut StdRng) -> Candidate {
+    let size: usize = (population.len() / 10).max(2);
+    let mut best: Candidate = population[rng.random_range(0..population.len())];
+    for _ in 1..size {
+        let other: Candidate = population[rng.random_range(0..population.len())];
+        if other.fitness > best.fitness {
+            best = other;
+        }
+    }
+    return best;
+}
+
+fn crossover(a: &Candidate, b: &Candidate) -> Candidate {
+    let mut weights: [f32; WEIGHT_COUNT] = [0.0; WEIGHT_COUNT];
+    let total: f32 = (a.fitness + b.fitness).max(f32::EPSILON);
+    for i in 0..WEIGHT_COUNT {
+        weights[i] = (a.weights[i] * a.fitness + b.weights[i] * b.fitness) / total;
+    }
+    normalize(&mut weights);
+    return Candidate {
+        weights: weights,
+        fitness: 0.0,
+    };
+}
+
+fn mutate(candidate: &mut Candidate, rng: &mut StdRng) {
+    if rng.random::<f32>() < 0.05 {
+        let index: usize = rng.random_range(0..WEIGHT_COUNT);
+        candidate.weights[index] += rng.random_range(-0.2..0.2);
+        normalize(&mut candidate.weights);
+    }
+}
+
+fn main() {
+    let options: Tune_Options = match parse_options() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("tune: {}\n{}", e, TUNE_USAGE);
+            std::process::exit(1);
+        }
+    };
+    let mut rng: StdRng = StdRng::seed_from_u64(options.seed);
+    let mut population: Vec<Candidate> = (0..options.population)
+        .map(|_| random_candidate(&mut rng))
+        .collect();
+    let start: Instant = Instant::now();
+    let mut games_played: u64 = 0;
+    for generation in 0..options.generations {
+        let seeds: Vec<u64> = (0..options.games).map(|_| rng.random()).collect();
+        evaluate_population(&mut population, &seeds, &options);
+        games_played += (population.len() * seeds.len()) as u64;
+        population.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+
+        let average: f32 =
+            population.iter().map(|c| c.fitness).sum::<f32>() / population.len() as f32;
+        let best: &Candidate = &population[0];
+        println!(
+            "generation {}: best {:.1} lines, average {:.1} lines, weights {:?}",
+            generation, best.fitness, average, best.weights
+        );
+
+        // Replace the worst 30% with offspring of tournament winners.
+        let offspring_count: usize = population.len() * 3 / 10;
+        let mut offspring: Vec<Candidate> = Vec::new();
+        for _ in 0..offspring_count {
+            let a: Candidate = tournament_select(&population, &mut rng);
+            let b: Candidate = tournament_select(&population, &mut rng);
+            let mut child: Candidate = crossover(&a, &b);
+            mutate(&mut child, &mut rng);
+            offspring.push(child);
+        }
+        let keep: usize = population.len() - offspring_count;
+        population.truncate(keep);
+        population.extend(offspring);
+    }
+    let seconds: f32 = start.elapsed().as_secs_f32();
+    println!(
+        "{} games in {:.1}s ({:.1} games/s)",
+        games_played,
+        seconds,
+        games_played as f32 / seconds
+    );
+}
@@ -0,0 +1,154 @@
+use sdl2::keyboard::Scancode;
+use tetris::config::*;
+use tetris::game::*;
+
+pub const BINDINGS_FILE: &str = "bindings.toml";
+
+// Keys bound to each action, indexed like GAME_ACTIONS. Keys are stored
+// in the config file by their SDL scancode names.
+#[derive(Clone)]
+pub struct Key_Bindings {
+    pub keys: Vec<Vec<Scancode>>,
+}
+
+fn default_keys(action: Game_Action) -> Vec<Scancode> {
+    match action {
+        Game_Action::ACTION_MOVE_LEFT => return vec![Scancode::Left],
+        Game_Action::ACTION_MOVE_RIGHT => return vec![Scancode::Right],
+        Game_Action::ACTION_SOFT_DROP => return vec![Scancode::Down],
+        Game_Action::ACTION_HARD_DROP => return vec![Scancode::Space],
+        Game_Action::ACTION_ROTATE_CW => return vec![Scancode::Up, Scancode::X],
+        Game_Action::ACTION_ROTATE_CCW => return vec![Scancode::Z],
+        Game_Action::ACTION_ROTATE_180 => return vec![Scancode::A],
+        Game_Action::ACTION_HOLD => return vec![Scancode::C, Scancode::LShift],
+        Game_Action::ACTION_PAUSE => return vec![Scancode::P],
+    }
+}
+
+pub fn action_label(action: Game_Action) -> String {
+    return action.name().replace('_', " ").to_uppercase();
+}
+
+pub fn keys_label(keys: &[Scancode]) -> String {
+    if keys.is_empty() {
+        return "-".to_string();
+    }
+    let names: Vec<String> = keys.iter().map(|k| k.name().to_uppercase()).collect();
+    return names.join(", ");
+}
+
+impl Key_Bindings {
+    pub fn new() -> Self {
+        Key_Bindings {
+            keys: GAME_ACTIONS.iter().map(|a| default_keys(*a)).collect(),
+        }
+    }
+    // Actions missing from the file keep their default keys.
+    pub fn load() -> Self {
+        let mut bindings: Key_Bindings = Key_Bindings::new();
+        let Ok(contents) = std::fs::read_to_string(config_path(BINDINGS_FILE)) else {
+            return bindings;
+        };
+        let table: toml::Table = match contents.parse() {
+            Ok(table) => table,
+            Err(e) => {
+                eprintln!("{}: {}", BINDINGS_FILE, e);
+                return bindings;
+            }
+        };
+        for (name, value) in table.iter() {
+            let Some(action) = Game_Action::from_name(name) else {
+                eprintln!("{}: unknown action {}", BINDINGS_FILE, name);
+                continue;
+            };
+            let names: Vec<&str> = match value {
+                toml::Value::String(key) => vec![key.as_str()],
+                toml::Value::Array(keys) => keys.iter().filter_map(|k| k.as_str()).collect(),
+                _ => Vec::new(),
+            };
+            let mut keys: Vec<Scancode> = Vec::new();
+            for key_name in names {
+                match Scancode::from_name(key_name) {
+                    Some(scancode) => keys.push(scancode),
+                    None => eprintln!("{}: unknown key {}", BINDINGS_FILE, key_name),
+                }
+            }
+            bindings.keys[action as usize] = keys;
+        }
+        return bindings;
+    }
+    pub fn save(&self) -> std::io::Result<()> {
+        let mut table: toml::Table = toml::Table::new();
+        for action in GAME_ACTIONS.iter() {
+            let names: Vec<toml::Value> = self.keys[*action as usize]
+                .iter()
+                .map(|k| toml::Value::String(k.name().to_string()))
+                .collect();
+            table.insert(action.name().to_string(), toml::Value::Array(names));
+        }
+        let contents: String = format!("# Keys are SDL scancode names.\n{}", table);
+        return write_config_file(BINDINGS_FILE, &contents);
+    }
+    pub fn actions_for(&self, scancode: Scancode) -> Vec<Game_Action> {
+        return GAME_ACTIONS
+            .iter()
+            .filter(|a| self.keys[**a as usize].contains(&scancode))
+            .copied()
+            .collect();
+    }
+}
+
+// Walks through the actions one at a time. Every key pressed is added to
+// the current action, Return moves on to the next one and keeps the old
+// keys if none were pressed.
+pub struct Rebind_Screen {
+    pub bindings: Key_Bindings,
+    pub action_index: usize,
+    pub pressed: Vec<Scancode>,
+}
+
+impl Rebind_Screen {
+    pub fn new(bindings: &Key_Bindings) -> Self {
+        Rebind_Screen {
+            bindings: bindings.clone(),
+            action_index: 0,
+            pressed: Vec::new(),
+        }
+    }
+    // Returns true once every action has been bound.
+    pub fn key_pressed(&mut self, scancode: Scancode) -> bool {
+        if scancode != Scancode::Return {
+            if !self.pressed.contains(&scancode) {
+                self.pressed.push(scancode);
+            }
+            return false;
+        }
+        if !self.pressed.is_empty() {
+            self.bindings.keys[self.action_index] = self.pressed.clone();
+            self.pressed.clear();
+        }
+        self.action_index += 1;
+        return self.action_index >= GAME_ACTION_COUNT;
+    }
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        for (i, action) in GAME_ACTIONS.iter().enumerate() {
+            if i == self.action_index {
+                lines.push(format!(
+                    "> {}: {}",
+                    action_label(*action),
+                    keys_label(&self.pressed)
+                ));
+            } else {
+                lines.push(format!(
+                    "{}: {}",
+                    action_label(*action),
+                    keys_label(&self.bindings.keys[i])
+                ));
+            }
+        }
+        lines.push(String::new());
+        lines.push("ENTER: NEXT  ESC: CANCEL".to_string());
+        return lines;
+    }
+}
@@ -0,0 +1,33 @@
+// Where the game keeps its files: $XDG_CONFIG_HOME/tetris, %APPDATA%\tetris
+// on Windows, or ~/.config/tetris.
+use std::path::PathBuf;
+
+pub fn config_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME") {
+        return PathBuf::from(dir).join("tetris");
+    }
+    if let Some(dir) = std::env::var_os("APPDATA") {
+        return PathBuf::from(dir).join("tetris");
+    }
+    if let Some(home) = std::env::var_os("HOME") {
+        return PathBuf::from(home).join(".config").join("tetris");
+    }
+    return PathBuf::from(".");
+}
+
+pub fn config_path(file_name: &str) -> PathBuf {
+    return config_dir().join(file_name);
+}
+
+// Writes the file, creating its directory if needed. `file_name` may have
+// a subdirectory. The contents go to a temporary file first, so a crash
+// never leaves a half written file.
+pub fn write_config_file(file_name: &str, contents: &str) -> std::io::Result<()> {
+    let path: PathBuf = config_path(file_name);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let temp_path: PathBuf = config_path(&format!("{}.tmp", file_name));
+    std::fs::write(&temp_path, contents)?;
+    return std::fs::rename(temp_path, path);
+}
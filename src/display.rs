@@ -1,327 +1,245 @@
-use super::game::*;
-use super::tetromino::*;
 use sdl2::pixels::Color;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
 use sdl2::render::*;
+use sdl2::surface::Surface;
 use sdl2::video::*;
-
-const fn color(r: u8, g: u8, b: u8, a: u8) -> Color {
-    let result: Color = Color {
-        r: r,
-        g: g,
-        b: b,
-        a: a,
-    };
-    return result;
+use std::collections::HashMap;
+use tetris::framebuffer::*;
+use tetris::layout::*;
+use tetris::render::*;
+use tetris::theme::*;
+
+fn to_sdl(color: Draw_Color) -> Color {
+    return Color::RGBA(color.r, color.g, color.b, color.a);
 }
 
-static BASE_COLORS: [Color; 8] = [
-    color(0x28, 0x28, 0x28, 0xFF),
-    color(0x2D, 0x99, 0x99, 0xFF),
-    color(0x99, 0x99, 0x2D, 0xFF),
-    color(0x99, 0x2D, 0x99, 0xFF),
-    color(0x2D, 0x99, 0x51, 0xFF),
-    color(0x99, 0x2D, 0x2D, 0xFF),
-    color(0x2D, 0x63, 0x99, 0xFF),
-    color(0x99, 0x63, 0x2D, 0xFF),
-];
-
-static LIGHT_COLORS: [Color; 8] = [
-    color(0x28, 0x28, 0x28, 0xFF),
-    color(0x44, 0xE5, 0xE5, 0xFF),
-    color(0xE5, 0xE5, 0x44, 0xFF),
-    color(0xE5, 0x44, 0xE5, 0xFF),
-    color(0x44, 0xE5, 0x7A, 0xFF),
-    color(0xE5, 0x44, 0x44, 0xFF),
-    color(0x44, 0x95, 0xE5, 0xFF),
-    color(0xE5, 0x95, 0x44, 0xFF),
-];
-
-static DARK_COLORS: [Color; 8] = [
-    color(0x28, 0x28, 0x28, 0xFF),
-    color(0x1E, 0x66, 0x66, 0xFF),
-    color(0x66, 0x66, 0x1E, 0xFF),
-    color(0x66, 0x1E, 0x66, 0xFF),
-    color(0x1E, 0x66, 0x36, 0xFF),
-    color(0x66, 0x1E, 0x1E, 0xFF),
-    color(0x1E, 0x42, 0x66, 0xFF),
-    color(0x66, 0x42, 0x1E, 0xFF),
-];
-
-enum Text_Allignment {
-    TEXT_ALLIGN_LEFT,
-    TEXT_ALLIGN_CENTER,
-    TEXT_ALLIGN_RIGHT,
+pub fn sdl_color(color: Theme_Color) -> Color {
+    return to_sdl(theme_color(color));
 }
 
-fn draw_string(
-    canvas: &mut Canvas<Window>,
-    font: &sdl2::ttf::Font,
-    text: &str,
-    x: i32,
-    y: i32,
-    allignment: Text_Allignment,
-    color: Color,
-) {
-    let texture_creator: TextureCreator<WindowContext> = canvas.texture_creator();
-
-    let surface = font
-        .render(text)
-        .blended(color)
-        .map_err(|e| e.to_string())
-        .unwrap();
+fn sdl_rect(rect: Layout_Rect) -> Rect {
+    return Rect::new(rect.x, rect.y, rect.w.max(0) as u32, rect.h.max(0) as u32);
+}
 
-    let texture = texture_creator
-        .create_texture_from_surface(&surface)
-        .map_err(|e| e.to_string())
-        .unwrap();
+// Falls back to the default font if the theme's font can't be loaded.
+pub fn load_font<'ttf>(
+    ttf_context: &'ttf sdl2::ttf::Sdl2TtfContext,
+    theme: &Theme,
+    size: u16,
+) -> sdl2::ttf::Font<'ttf, 'static> {
+    match ttf_context.load_font(&theme.font, size) {
+        Ok(font) => return font,
+        Err(e) => {
+            eprintln!("{}: {}", theme.font, e);
+            return ttf_context.load_font(DEFAULT_FONT, size).unwrap();
+        }
+    }
+}
 
-    let mut rect = sdl2::rect::Rect::new(0, 0, 0, 0);
+// Reads a theme's skin image. A missing or broken skin falls back to the
+// bevel style.
+pub fn load_skin<'a>(
+    texture_creator: &'a TextureCreator<WindowContext>,
+    theme: &Theme,
+) -> Option<Texture<'a>> {
+    let path: &std::path::Path = theme.skin.as_ref()?;
+    let skin = sdl2::surface::Surface::load_bmp(path)
+        .and_then(|surface| {
+            texture_creator
+                .create_texture_from_surface(&surface)
+                .map_err(|e| e.to_string())
+        })
+        .map_err(|e| eprintln!("{}: {}", path.display(), e))
+        .ok();
+    return skin;
+}
 
-    rect.w = surface.width() as i32;
-    rect.h = surface.height() as i32;
-    rect.y = y;
-    match allignment {
-        Text_Allignment::TEXT_ALLIGN_LEFT => rect.x = x,
-        Text_Allignment::TEXT_ALLIGN_CENTER => rect.x = x - surface.width() as i32 / 2,
-        Text_Allignment::TEXT_ALLIGN_RIGHT => rect.x = x - surface.width() as i32,
-    }
-    canvas.copy(&texture, None, Some(rect)).unwrap();
+struct Cached_Texture<'a> {
+    texture: Texture<'a>,
+    last_used: u64,
 }
 
-fn draw_cell(
-    canvas: &mut Canvas<Window>,
-    row: i32,
-    col: i32,
-    value: u8,
-    offset_x: i32,
-    offset_y: i32,
-    outline: bool,
-) {
-    let base_color: Color = BASE_COLORS[value as usize];
-    let light_color: Color = LIGHT_COLORS[value as usize];
-    let dark_color: Color = DARK_COLORS[value as usize];
-    let edge: i32 = GRID_SIZE as i32 / 8;
-    let x: i32 = col * GRID_SIZE as i32 + offset_x;
-    let y: i32 = row * GRID_SIZE as i32 + offset_y;
-    if outline {
-        canvas.set_draw_color(dark_color);
-        canvas
-            .draw_rect(sdl2::rect::Rect::new(
-                x,
-                y,
-                GRID_SIZE as u32,
-                GRID_SIZE as u32,
-            ))
-            .unwrap();
-        return;
-    }
-    canvas.set_draw_color(dark_color);
-    canvas
-        .fill_rect(sdl2::rect::Rect::new(
-            x,
-            y,
-            GRID_SIZE as u32,
-            GRID_SIZE as u32,
-        ))
-        .unwrap();
-    canvas.set_draw_color(light_color);
-    canvas
-        .fill_rect(sdl2::rect::Rect::new(
-            x + edge,
-            y,
-            GRID_SIZE as u32 - edge as u32,
-            GRID_SIZE as u32 - edge as u32,
-        ))
-        .unwrap();
-    canvas.set_draw_color(base_color);
-    canvas
-        .fill_rect(sdl2::rect::Rect::new(
-            x + edge,
-            y + edge,
-            GRID_SIZE as u32 - edge as u32 * 2,
-            GRID_SIZE as u32 - edge as u32 * 2,
-        ))
-        .unwrap();
+// Textures kept from one frame to the next: rendered text by color and
+// string, and cells by color, size and look. Anything not drawn during a
+// frame is dropped at the end of it, so a changing score or clock doesn't
+// pile up textures.
+pub struct Render_Cache<'a> {
+    texture_creator: &'a TextureCreator<WindowContext>,
+    font: sdl2::ttf::Font<'a, 'static>,
+    frame: u64,
+    text: HashMap<Color, HashMap<String, Cached_Texture<'a>>>,
+    cells: HashMap<([Color; 3], i32, Cell_Look), Cached_Texture<'a>>,
 }
 
-fn draw_piece(
-    canvas: &mut Canvas<Window>,
-    piece: &Piece_State,
-    offset_x: i32,
-    offset_y: i32,
-    outline: bool,
-) {
-    let tetromino: &Tetromino = &TETROMINOS[piece.get_tetr_index() as usize];
-    for row in 0..tetromino.side {
-        for col in 0..tetromino.side {
-            let value: u8 = tetromino.tetromino_get(row, col, piece.get_rotation());
-            if value != 0 {
-                draw_cell(
-                    canvas,
-                    row + piece.get_offset_row(),
-                    col + piece.get_offset_col(),
-                    value,
-                    offset_x,
-                    offset_y,
-                    outline,
-                );
-            }
+impl<'a> Render_Cache<'a> {
+    pub fn new(
+        texture_creator: &'a TextureCreator<WindowContext>,
+        font: sdl2::ttf::Font<'a, 'static>,
+    ) -> Self {
+        Render_Cache {
+            texture_creator: texture_creator,
+            font: font,
+            frame: 0,
+            text: HashMap::new(),
+            cells: HashMap::new(),
         }
     }
-}
-
-fn draw_next_piece(canvas: &mut Canvas<Window>, piece_next: &Piece_State, outline: bool) {
-    let tetromino: &Tetromino = &TETROMINOS[piece_next.get_tetr_index() as usize];
-    for row in 0..tetromino.side {
-        for col in 0..tetromino.side {
-            let value: u8 = tetromino.tetromino_get(row, col, piece_next.get_rotation());
-            if value != 0 {
-                draw_cell(canvas, row + 1, col + 5, value, 0, 0, outline);
-            }
+    // Text rendered with the old font is thrown away.
+    pub fn set_font(&mut self, font: sdl2::ttf::Font<'a, 'static>) {
+        self.font = font;
+        self.text.clear();
+    }
+    // Space between two lines of text.
+    pub fn line_height(&self) -> i32 {
+        return self.font.recommended_line_spacing().max(1);
+    }
+    // The size text comes out at, without rendering it.
+    fn text_size(&self, text: &str) -> (u32, u32) {
+        return self.font.size_of(text).unwrap_or((0, 0));
+    }
+    pub fn end_frame(&mut self) {
+        let frame: u64 = self.frame;
+        for strings in self.text.values_mut() {
+            strings.retain(|_, entry| entry.last_used == frame);
         }
+        self.text.retain(|_, strings| !strings.is_empty());
+        self.cells.retain(|_, entry| entry.last_used == frame);
+        self.frame += 1;
+    }
+    fn text_texture(&mut self, text: &str, color: Color) -> &Texture<'a> {
+        let strings = self.text.entry(color).or_default();
+        if !strings.contains_key(text) {
+            let surface = self
+                .font
+                .render(text)
+                .blended(color)
+                .map_err(|e| e.to_string())
+                .unwrap();
+            let texture = self
+                .texture_creator
+                .create_texture_from_surface(&surface)
+                .map_err(|e| e.to_string())
+                .unwrap();
+            strings.insert(
+                text.to_string(),
+                Cached_Texture {
+                    texture: texture,
+                    last_used: 0,
+                },
+            );
+        }
+        let entry: &mut Cached_Texture<'a> = strings.get_mut(text).unwrap();
+        entry.last_used = self.frame;
+        return &entry.texture;
+    }
+    // Colors are base, light and dark.
+    fn cell_texture(&mut self, colors: [Color; 3], size: i32, look: Cell_Look) -> &Texture<'a> {
+        let texture_creator: &'a TextureCreator<WindowContext> = self.texture_creator;
+        let entry = self.cells.entry((colors, size, look)).or_insert_with(|| {
+            let surface: Surface = render_cell(colors, size, look);
+            let mut texture = texture_creator
+                .create_texture_from_surface(&surface)
+                .map_err(|e| e.to_string())
+                .unwrap();
+            texture.set_blend_mode(BlendMode::Blend);
+            return Cached_Texture {
+                texture: texture,
+                last_used: 0,
+            };
+        });
+        entry.last_used = self.frame;
+        return &entry.texture;
     }
 }
 
-fn draw_board(
-    canvas: &mut Canvas<Window>,
-    game: &Game_State,
-    width: i32,
-    height: i32,
-    offset_x: i32,
-    offset_y: i32,
-) {
-    canvas.set_draw_color(BASE_COLORS[0]);
-    canvas
-        .fill_rect(sdl2::rect::Rect::new(
-            offset_x,
-            offset_y,
-            width as u32 * GRID_SIZE as u32,
-            height as u32 * GRID_SIZE as u32,
-        ))
-        .unwrap();
-    for row in 0..height {
-        for col in 0..width {
-            let value: u8 = game.matrix_get(width, row, col);
-            if value != 0 {
-                draw_cell(canvas, row, col, value, offset_x, offset_y, false);
-            }
-        }
+// Cells are drawn once into a texture and copied from then on.
+fn render_cell(colors: [Color; 3], size: i32, look: Cell_Look) -> Surface<'static> {
+    let size: u32 = size.max(1) as u32;
+    let mut surface = Surface::new(size, size, PixelFormatEnum::ARGB8888).unwrap();
+    surface.fill_rect(None, Color::RGBA(0, 0, 0, 0)).unwrap();
+    for (rect, index) in cell_fills(size as i32, look) {
+        surface.fill_rect(sdl_rect(rect), colors[index]).unwrap();
     }
+    return surface;
 }
 
-pub fn render_game(game: &mut Game_State, canvas: &mut Canvas<Window>, font: &sdl2::ttf::Font) {
-    let highlight_color = color(0xFF, 0xFF, 0xFF, 0xFF);
-    let padding_y: i32 = 60;
-    draw_board(canvas, &game, WIDTH as i32, HEIGHT as i32, 0, padding_y);
-    if game.phase == Game_Phase::GAME_PHASE_PLAY {
-        draw_piece(canvas, &game.piece, 0, padding_y, false);
-        let mut piece: Piece_State = game.piece;
-        while piece.check_piece_valid(&game, WIDTH as i32, HEIGHT as i32) {
-            piece.move_down();
+// Draws onto the window, through the texture cache.
+pub struct Sdl_Target<'r, 'a> {
+    pub canvas: &'r mut Canvas<Window>,
+    pub cache: &'r mut Render_Cache<'a>,
+    pub skin: Option<&'r Texture<'a>>,
+}
+
+impl Draw_Target for Sdl_Target<'_, '_> {
+    fn size(&self) -> (u32, u32) {
+        return self.canvas.output_size().unwrap();
+    }
+    fn fill_rects(&mut self, rects: &[Layout_Rect], color: Draw_Color) {
+        if color.a == 0xFF {
+            self.canvas.set_blend_mode(BlendMode::None);
+        } else {
+            self.canvas.set_blend_mode(BlendMode::Blend);
         }
-        piece.move_up();
-        draw_piece(canvas, &piece, 0, padding_y, true);
+        let rects: Vec<Rect> = rects.iter().map(|r| sdl_rect(*r)).collect();
+        self.canvas.set_draw_color(to_sdl(color));
+        self.canvas.fill_rects(&rects).unwrap();
+        self.canvas.set_blend_mode(BlendMode::None);
     }
-    if game.phase == Game_Phase::GAME_PHASE_LINE {
-        for row in 0..HEIGHT {
-            if game.lines[row] > 0 {
-                let x: i32 = 0;
-                let y: i32 = row as i32 * GRID_SIZE as i32 + padding_y;
-
-                canvas.set_draw_color(highlight_color);
-                let _ = canvas
-                    .fill_rect(sdl2::rect::Rect::new(
-                        x,
-                        y,
-                        WIDTH as u32 * GRID_SIZE as u32,
-                        GRID_SIZE as u32,
-                    ))
-                    .unwrap();
-            }
+    fn draw_cell(&mut self, rect: Layout_Rect, colors: [Draw_Color; 3], look: Cell_Look) {
+        let colors: [Color; 3] = colors.map(to_sdl);
+        let texture: &Texture = self.cache.cell_texture(colors, rect.w, look);
+        self.canvas
+            .copy(texture, None, Some(sdl_rect(rect)))
+            .unwrap();
+    }
+    fn draw_skin_tile(&mut self, rect: Layout_Rect, value: u8) -> bool {
+        let Some(skin) = self.skin else {
+            return false;
+        };
+        let tile: u32 = skin.query().height;
+        if (value as u32 + 1) * tile > skin.query().width {
+            return false;
         }
-    } else if game.phase == Game_Phase::GAME_PHASE_GAMEOVER {
-        let x: i32 = WIDTH as i32 * GRID_SIZE as i32 / 2;
-        let y: i32 = (HEIGHT as i32 * GRID_SIZE as i32 + padding_y) / 2;
-        draw_string(
-            canvas,
-            font,
-            "GAME OVER",
-            x,
-            y,
-            Text_Allignment::TEXT_ALLIGN_CENTER,
-            highlight_color,
-        );
-    } else if game.phase == Game_Phase::GAME_PHASE_START {
-        let x: i32 = WIDTH as i32 * GRID_SIZE as i32 / 2;
-        let y: i32 = (HEIGHT as i32 * GRID_SIZE as i32 + padding_y) / 2;
-        draw_string(
-            canvas,
-            font,
-            "PRESS START",
-            x,
-            y,
-            Text_Allignment::TEXT_ALLIGN_CENTER,
-            highlight_color,
-        );
-        let start_level_text = format!("STARTING LEVEL: {}", game.start_level);
-        draw_string(
-            canvas,
-            font,
-            &start_level_text,
-            x,
-            y + 32,
-            Text_Allignment::TEXT_ALLIGN_CENTER,
-            highlight_color,
-        );
+        let source = Rect::new(value as i32 * tile as i32, 0, tile, tile);
+        self.canvas
+            .copy(skin, Some(source), Some(sdl_rect(rect)))
+            .unwrap();
+        return true;
+    }
+    fn set_clip(&mut self, clip: Option<Layout_Rect>) {
+        self.canvas.set_clip_rect(clip.map(sdl_rect));
+    }
+    fn line_height(&self) -> i32 {
+        return self.cache.line_height();
+    }
+    fn text_size(&mut self, text: &str) -> (i32, i32) {
+        let (width, height) = self.cache.text_size(text);
+        return (width as i32, height as i32);
+    }
+    fn draw_text(&mut self, text: &str, x: i32, y: i32, color: Draw_Color) {
+        let texture: &Texture = self.cache.text_texture(text, to_sdl(color));
+        let query = texture.query();
+        let rect = Rect::new(x, y, query.width, query.height);
+        self.canvas.copy(texture, None, Some(rect)).unwrap();
+    }
+}
+
+// Saves what has been drawn this frame as a PNG in the screenshot
+// directory. Call before presenting.
+pub fn save_screenshot(canvas: &Canvas<Window>) {
+    let (width, height) = canvas.output_size().unwrap();
+    let saved: Result<std::path::PathBuf, String> = canvas
+        .read_pixels(None, PixelFormatEnum::RGBA32)
+        .and_then(|pixels| {
+            let path: std::path::PathBuf = screenshot_path().map_err(|e| e.to_string())?;
+            Frame_Buffer::from_rgba(width, height, pixels)
+                .save_png(&path)
+                .map_err(|e| e.to_string())?;
+            return Ok(path);
+        });
+    match saved {
+        Ok(path) => println!("screenshot saved to {}", path.display()),
+        Err(e) => eprintln!("screenshot: {}", e),
     }
-    let margin_y: i32 = 60;
-    canvas.set_draw_color(color(0x00, 0x00, 0x00, 0x00));
-    let _ = canvas
-        .fill_rect(sdl2::rect::Rect::new(
-            0,
-            margin_y,
-            WIDTH as u32 * GRID_SIZE as u32,
-            (HEIGHT as u32 - VISIBLE_HEIGHT as u32) * GRID_SIZE as u32,
-        ))
-        .unwrap();
-    draw_next_piece(canvas, &game.piece_next, false);
-    let level_string = format!("LEVEL: {}", game.level);
-    draw_string(
-        canvas,
-        font,
-        &level_string,
-        5,
-        5,
-        Text_Allignment::TEXT_ALLIGN_LEFT,
-        highlight_color,
-    );
-    let score_string = format!("SCORE: {}", game.points);
-    draw_string(
-        canvas,
-        font,
-        &score_string,
-        5,
-        40,
-        Text_Allignment::TEXT_ALLIGN_LEFT,
-        highlight_color,
-    );
-    let lines_string = format!("LINES: {}", game.line_count);
-    draw_string(
-        canvas,
-        font,
-        &lines_string,
-        5,
-        75,
-        Text_Allignment::TEXT_ALLIGN_LEFT,
-        highlight_color,
-    );
-    draw_string(
-        canvas,
-        font,
-        "NEXT",
-        150,
-        5,
-        Text_Allignment::TEXT_ALLIGN_LEFT,
-        highlight_color,
-    );
 }
@@ -0,0 +1,266 @@
+// Gym-style reinforcement learning interface over Game_State:
+// reset(seed) -> observation and step(action) -> (observation, reward, done).
+use super::ai::*;
+use super::game::*;
+use super::pieces::*;
+use serde_json::Value;
+use serde_json::json;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Env_Action {
+    ACTION_NONE,
+    ACTION_LEFT,
+    ACTION_RIGHT,
+    ACTION_ROTATE,
+    ACTION_SOFT_DROP,
+    ACTION_HARD_DROP,
+}
+
+pub const ACTION_COUNT: usize = 6;
+
+pub static ACTIONS: [Env_Action; ACTION_COUNT] = [
+    Env_Action::ACTION_NONE,
+    Env_Action::ACTION_LEFT,
+    Env_Action::ACTION_RIGHT,
+    Env_Action::ACTION_ROTATE,
+    Env_Action::ACTION_SOFT_DROP,
+    Env_Action::ACTION_HARD_DROP,
+];
+
+pub fn action_from_index(index: usize) -> Option<Env_Action> {
+    return ACTIONS.get(index).copied();
+}
+
+// Each field is added to the reward whenever its event happens. The
+// penalties are applied to the change of the board feature, so a step that
+// fills a hole is rewarded as much as one that makes a hole is punished.
+#[derive(Clone, Copy)]
+pub struct Reward_Config {
+    pub lines: [f32; 5],
+    pub step: f32,
+    pub game_over: f32,
+    pub hole: f32,
+    pub height: f32,
+    pub bumpiness: f32,
+}
+
+pub const DEFAULT_REWARDS: Reward_Config = Reward_Config {
+    lines: [0.0, 1.0, 3.0, 5.0, 8.0],
+    step: 0.0,
+    game_over: -10.0,
+    hole: -0.35,
+    height: -0.05,
+    bumpiness: -0.02,
+};
+
+#[derive(Clone)]
+pub struct Observation {
+    pub board: Board,
+    pub piece: u8,
+    // How many pieces the set has, for the one-hot piece features.
+    pub piece_count: usize,
+    pub rotation: i32,
+    pub offset_row: i32,
+    pub offset_col: i32,
+    pub next: u8,
+    pub level: i32,
+    pub lines: i32,
+    pub points: i32,
+}
+
+impl Observation {
+    fn from_game(game: &Game_State) -> Self {
+        Observation {
+            board: game.get_board().clone(),
+            piece: game.piece.get_tetr_index(),
+            piece_count: game.pieces().len(),
+            rotation: game.piece.get_rotation(),
+            offset_row: game.piece.get_offset_row(),
+            offset_col: game.piece.get_offset_col(),
+            next: game.piece_next.get_tetr_index(),
+            level: game.level,
+            lines: game.line_count,
+            points: game.points,
+        }
+    }
+    // Flat feature vector: the board as 0/1 cells, one-hot current and next
+    // piece, then the piece position scaled to the board.
+    pub fn to_vector(&self) -> Vec<f32> {
+        let mut features: Vec<f32> =
+            Vec::with_capacity(self.board.cells.len() + 2 * self.piece_count + 3);
+        features.extend(self.board.cells.iter().map(|v| (*v != 0) as u8 as f32));
+        for i in 0..self.piece_count {
+            features.push((self.piece as usize == i) as u8 as f32);
+        }
+        for i in 0..self.piece_count {
+            features.push((self.next as usize == i) as u8 as f32);
+        }
+        features.push(self.rotation as f32 / 4.0);
+        features.push(self.offset_row as f32 / self.board.rows() as f32);
+        features.push(self.offset_col as f32 / self.board.width() as f32);
+        return features;
+    }
+    pub fn to_json(&self) -> Value {
+        return json!({
+            "board": self.board.cells,
+            "width": self.board.width(),
+            "height": self.board.rows(),
+            "piece": self.piece,
+            "piece_count": self.piece_count,
+            "rotation": self.rotation,
+            "offset_row": self.offset_row,
+            "offset_col": self.offset_col,
+            "next": self.next,
+            "level": self.level,
+            "lines": self.lines,
+            "points": self.points,
+        });
+    }
+}
+
+pub struct Tetris_Env {
+    pub rewards: Reward_Config,
+    pub frames_per_step: u32,
+    pub max_frames: u32,
+    pub start_level: i32,
+    pub board_size: Board_Size,
+    pub pieces: Piece_Set,
+    game: Game_State,
+    frame: u32,
+    prev_lines: i32,
+    prev_features: Board_Features,
+}
+
+impl Tetris_Env {
+    pub fn new(rewards: Reward_Config) -> Self {
+        let game: Game_State = Game_State::new_seeded(0);
+        let features: Board_Features = board_features(game.get_board());
+        Tetris_Env {
+            rewards: rewards,
+            frames_per_step: 1,
+            max_frames: 60 * 60 * 60,
+            start_level: 0,
+            board_size: DEFAULT_BOARD_SIZE,
+            pieces: standard_pieces(),
+            game: game,
+            frame: 0,
+            prev_lines: 0,
+            prev_features: features,
+        }
+    }
+    pub fn get_game(&self) -> &Game_State {
+        return &self.game;
+    }
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.game = Game_State::new_seeded(seed);
+        self.game.start_level = self.start_level;
+        self.game.set_board_size(self.board_size);
+        self.game.set_piece_set(self.pieces.clone());
+        self.game.set_time(0.0);
+        self.game.start();
+        self.frame = 0;
+        self.prev_lines = 0;
+        self.prev_features = board_features(self.game.get_board());
+        return Observation::from_game(&self.game);
+    }
+    // The action is pressed on the first frame of the step and released for
+    // the rest, so repeating an action repeats the key press.
+    pub fn step(&mut self, action: Env_Action) -> (Observation, f32, bool) {
+        let mut input: Input_State = Input_State::new();
+        match action {
+            Env_Action::ACTION_NONE => {}
+            Env_Action::ACTION_LEFT => {
+                input.left = 1;
+                input.dleft = 1;
+            }
+            Env_Action::ACTION_RIGHT => {
+                input.right = 1;
+                input.dright = 1;
+            }
+            Env_Action::ACTION_ROTATE => {
+                input.up = 1;
+                input.dup = 1;
+            }
+            Env_Action::ACTION_SOFT_DROP => {
+                input.down = 1;
+                input.ddown = 1;
+            }
+            Env_Action::ACTION_HARD_DROP => {
+                input.a = 1;
+                input.da = 1;
+            }
+        }
+        for i in 0..self.frames_per_step.max(1) {
+            if self.game.phase == Game_Phase::GAME_PHASE_GAMEOVER {
+                break;
+            }
+            self.frame += 1;
+            self.game
+                .set_time(self.frame as f32 * TARGET_SECONDS_PER_FRAME);
+            if i == 0 {
+                self.game.update(&input);
+            } else {
+                self.game.update(&Input_State::new());
+            }
+        }
+        // Nothing here reacts to the events, but they still have to go.
+        self.game.drain_events();
+
+        let mut reward: f32 = self.rewards.step;
+        let cleared: i32 = self.game.line_count - self.prev_lines;
+        self.prev_lines = self.game.line_count;
+        reward += self.rewards.lines[cleared.clamp(0, 4) as usize];
+        let features: Board_Features = board_features(self.game.get_board());
+        reward += self.rewards.hole * (features.holes - self.prev_features.holes) as f32;
+        reward += self.rewards.height
+            * (features.aggregate_height - self.prev_features.aggregate_height) as f32;
+        reward +=
+            self.rewards.bumpiness * (features.bumpiness - self.prev_features.bumpiness) as f32;
+        self.prev_features = features;
+
+        let game_over: bool = self.game.phase == Game_Phase::GAME_PHASE_GAMEOVER;
+        if game_over {
+            reward += self.rewards.game_over;
+        }
+        let done: bool = game_over || self.frame >= self.max_frames;
+        return (Observation::from_game(&self.game), reward, done);
+    }
+}
+
+// Many environments stepped together. A finished environment is reset
+// with the next seed straight away, and its returned observation is the
+// first one of the new episode.
+pub struct Vec_Env {
+    pub envs: Vec<Tetris_Env>,
+    next_seed: u64,
+}
+
+impl Vec_Env {
+    pub fn new(count: usize, rewards: Reward_Config) -> Self {
+        Vec_Env {
+            envs: (0..count).map(|_| Tetris_Env::new(rewards)).collect(),
+            next_seed: 0,
+        }
+    }
+    pub fn reset(&mut self, seed: u64) -> Vec<Observation> {
+        self.next_seed = seed;
+        let mut observations: Vec<Observation> = Vec::new();
+        for env in self.envs.iter_mut() {
+            observations.push(env.reset(self.next_seed));
+            self.next_seed = self.next_seed.wrapping_add(1);
+        }
+        return observations;
+    }
+    pub fn step(&mut self, actions: &[Env_Action]) -> Vec<(Observation, f32, bool)> {
+        let mut results: Vec<(Observation, f32, bool)> = Vec::new();
+        for (env, action) in self.envs.iter_mut().zip(actions.iter()) {
+            let (mut observation, reward, done) = env.step(*action);
+            if done {
+                observation = env.reset(self.next_seed);
+                self.next_seed = self.next_seed.wrapping_add(1);
+            }
+            results.push((observation, reward, done));
+        }
+        return results;
+    }
+}
@@ -0,0 +1,252 @@
+// Clips of replays to share: an animated GIF, or raw RGBA frames to pipe
+// into a video encoder. Both are drawn offscreen in a frame buffer, so no
+// window is needed.
+use super::framebuffer::*;
+use super::render::*;
+use super::replay::*;
+use super::settings::*;
+use super::theme::*;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+pub const EXPORT_USAGE: &str =
+    "usage: tetris render-replay IN OUT [--scale F] [--frame-step N] [--no-hud]
+  OUT ending in .gif writes an animated GIF. Anything else gets raw RGBA
+  frames, and - writes them to stdout for an encoder.";
+
+// How long the last frame stays up before the GIF loops, in 1/100 s.
+const GIF_END_DELAY: u16 = 200;
+
+#[derive(Clone, Copy)]
+pub struct Export_Options {
+    // Cell size relative to the grid size setting.
+    pub scale: f32,
+    // One frame in `frame_step` of the 60 a second is kept.
+    pub frame_step: u32,
+    // The hold and next pieces and the score panel around the board.
+    pub hud: bool,
+}
+
+impl Export_Options {
+    pub fn new() -> Self {
+        Export_Options {
+            scale: 1.0,
+            frame_step: 3,
+            hud: true,
+        }
+    }
+}
+
+fn export_cell(grid_size: i32, options: &Export_Options) -> i32 {
+    return ((grid_size as f32 * options.scale).round() as i32).max(4);
+}
+
+// GIF delays are in hundredths of a second, so they are rounded from the
+// frame numbers to keep the clip in time. Browsers slow down anything
+// faster than 1/50 s.
+fn gif_delay(from_frame: u32, to_frame: u32) -> u16 {
+    let centiseconds = |frame: u32| (frame as u64 * 100 + 30) / 60;
+    let delay: u64 = centiseconds(to_frame) - centiseconds(from_frame);
+    return delay.clamp(2, u16::MAX as u64) as u16;
+}
+
+// GIF sizes are 16 bits, and an empty frame has nothing to encode.
+fn gif_size(width: u32, height: u32) -> std::io::Result<(u16, u16)> {
+    if width == 0 || height == 0 || width > u16::MAX as u32 || height > u16::MAX as u32 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("a {}x{} frame can't be saved as a GIF", width, height),
+        ));
+    }
+    return Ok((width as u16, height as u16));
+}
+
+// The palette is the frame's 256 most common colors. The rest are the
+// few pixels at antialiased text edges and blended effects, and take the
+// closest color in it. Much faster than a general quantizer, and exact
+// for the flat colors that make up most of a frame.
+fn gif_frame(buffer: &Frame_Buffer) -> std::io::Result<gif::Frame<'static>> {
+    let (width, height) = gif_size(buffer.width, buffer.height)?;
+    // Rows are mostly runs of one color, so runs are counted at once.
+    let mut counts: HashMap<[u8; 3], u32> = HashMap::new();
+    let mut run: ([u8; 3], u32) = ([0; 3], 0);
+    for pixel in buffer.pixels.chunks(4) {
+        let rgb: [u8; 3] = [pixel[0], pixel[1], pixel[2]];
+        if rgb != run.0 {
+            *counts.entry(run.0).or_insert(0) += run.1;
+            run = (rgb, 0);
+        }
+        run.1 += 1;
+    }
+    *counts.entry(run.0).or_insert(0) += run.1;
+    counts.retain(|_, count| *count > 0);
+    let mut colors: Vec<([u8; 3], u32)> = counts.into_iter().collect();
+    colors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let palette: Vec<[u8; 3]> = colors.iter().take(256).map(|c| c.0).collect();
+    let distance = |a: [u8; 3], b: [u8; 3]| -> i32 {
+        return (0..3).map(|i| (a[i] as i32 - b[i] as i32).pow(2)).sum();
+    };
+    let mut index_of: HashMap<[u8; 3], u8> = HashMap::new();
+    for (rgb, _) in colors.iter() {
+        let index: usize = (0..palette.len())
+            .min_by_key(|i| distance(*rgb, palette[*i]))
+            .unwrap_or(0);
+        index_of.insert(*rgb, index as u8);
+    }
+    let mut indices: Vec<u8> = Vec::with_capacity(buffer.pixels.len() / 4);
+    let mut last: ([u8; 3], u8) = (palette[0], 0);
+    for pixel in buffer.pixels.chunks(4) {
+        let rgb: [u8; 3] = [pixel[0], pixel[1], pixel[2]];
+        if rgb != last.0 {
+            last = (rgb, index_of[&rgb]);
+        }
+        indices.push(last.1);
+    }
+    return Ok(gif::Frame::from_palette_pixels(
+        width,
+        height,
+        indices,
+        palette.concat(),
+        None,
+    ));
+}
+
+// Writes the replay as a looping GIF. Returns how many frames it has.
+pub fn write_replay_gif<W: Write>(
+    replay: &Replay,
+    view: &View,
+    cell: i32,
+    options: &Export_Options,
+    writer: W,
+) -> std::io::Result<u32> {
+    let (width, height) = replay_frame_size(cell, options.hud, replay.board_size);
+    let (width, height) = gif_size(width, height)?;
+    let mut encoder =
+        gif::Encoder::new(writer, width, height, &[]).map_err(std::io::Error::other)?;
+    encoder
+        .set_repeat(gif::Repeat::Infinite)
+        .map_err(std::io::Error::other)?;
+    // A frame's delay is known once the next one comes.
+    let mut pending: Option<(u32, gif::Frame)> = None;
+    let count: u32 = render_replay_frames(
+        replay,
+        view,
+        cell,
+        options.frame_step,
+        options.hud,
+        &mut |frame, buffer| {
+            if let Some((previous, mut image)) = pending.take() {
+                image.delay = gif_delay(previous, frame);
+                encoder.write_frame(&image).map_err(std::io::Error::other)?;
+            }
+            pending = Some((frame, gif_frame(buffer)?));
+            return Ok(());
+        },
+    )?;
+    if let Some((_, mut image)) = pending {
+        image.delay = GIF_END_DELAY;
+        encoder.write_frame(&image).map_err(std::io::Error::other)?;
+    }
+    return Ok(count);
+}
+
+// Writes the frames one after another as rows of RGBA pixels, with no
+// header. Returns how many were written.
+pub fn write_replay_raw<W: Write>(
+    replay: &Replay,
+    view: &View,
+    cell: i32,
+    options: &Export_Options,
+    mut writer: W,
+) -> std::io::Result<u32> {
+    let count: u32 = render_replay_frames(
+        replay,
+        view,
+        cell,
+        options.frame_step,
+        options.hud,
+        &mut |_, buffer| writer.write_all(&buffer.pixels),
+    )?;
+    writer.flush()?;
+    return Ok(count);
+}
+
+// `tetris render-replay IN OUT [options]`, with the arguments after the
+// command name. Uses the theme and view settings the game would.
+pub fn run_render_replay(args: &[String]) -> Result<(), String> {
+    let mut options: Export_Options = Export_Options::new();
+    let mut paths: Vec<&str> = Vec::new();
+    let mut i: usize = 0;
+    while i < args.len() {
+        let value: Option<&String> = args.get(i + 1);
+        match args[i].as_str() {
+            "--scale" => {
+                options.scale = value
+                    .and_then(|v| v.parse().ok())
+                    .filter(|scale: &f32| *scale > 0.0)
+                    .ok_or("--scale needs a number above 0")?;
+                i += 1;
+            }
+            "--frame-step" => {
+                options.frame_step = value
+                    .and_then(|v| v.parse().ok())
+                    .filter(|step: &u32| *step > 0)
+                    .ok_or("--frame-step needs a whole number above 0")?;
+                i += 1;
+            }
+            "--no-hud" => options.hud = false,
+            "-" => paths.push("-"),
+            arg if arg.starts_with("--") => {
+                return Err(format!("unknown option {}\n{}", arg, EXPORT_USAGE));
+            }
+            arg => paths.push(arg),
+        }
+        i += 1;
+    }
+    let [input, output] = paths[..] else {
+        return Err(EXPORT_USAGE.to_string());
+    };
+    let replay: Replay = Replay::load(Path::new(input)).map_err(|e| format!("{}: {}", input, e))?;
+    let settings: Settings = Settings::load();
+    let themes: Vec<Theme> = load_themes();
+    let view: View = View::new(&settings, &themes[theme_index(&themes, &settings.theme)]);
+    let cell: i32 = export_cell(settings.grid_size, &options);
+    let (width, height) = replay_frame_size(cell, options.hud, replay.board_size);
+    if output.ends_with(".gif") {
+        let file: std::fs::File =
+            std::fs::File::create(output).map_err(|e| format!("{}: {}", output, e))?;
+        let writer = std::io::BufWriter::new(file);
+        let count: u32 = write_replay_gif(&replay, &view, cell, &options, writer)
+            .map_err(|e| format!("{}: {}", output, e))?;
+        println!(
+            "saved {} frames of {}x{} to {}",
+            count, width, height, output
+        );
+        return Ok(());
+    }
+    // Raw frames go to stdout for an encoder, or to a file.
+    let mut writer: Box<dyn Write> = Box::new(std::io::stdout().lock());
+    if output != "-" {
+        let file: std::fs::File =
+            std::fs::File::create(output).map_err(|e| format!("{}: {}", output, e))?;
+        writer = Box::new(file);
+    }
+    let count: u32 = write_replay_raw(
+        &replay,
+        &view,
+        cell,
+        &options,
+        std::io::BufWriter::new(writer),
+    )
+    .map_err(|e| format!("{}: {}", output, e))?;
+    // Stdout may be the video, so the encoder settings go to stderr.
+    eprintln!(
+        "wrote {} frames of {}x{} RGBA at {} frames a second",
+        count,
+        width,
+        height,
+        60.0 / options.frame_step as f32
+    );
+    return Ok(());
+}
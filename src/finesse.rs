@@ -0,0 +1,183 @@
+// Finesse trainer: compares the key presses spent on each piece with the
+// fewest presses that reach the same final placement.
+use super::game::*;
+use super::pieces::*;
+use super::tetromino::*;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+
+// How long the per-piece result stays on screen, in seconds.
+pub const FINESSE_OVERLAY_TIME: f32 = 1.0;
+
+// Occupied cells of a piece with its top row moved to 0. Placements that
+// cover the same cells count as the same placement whatever the rotation.
+fn placement_key(pieces: &Piece_Set, piece: &Piece_State) -> Vec<(i32, i32)> {
+    let tetromino: Tetromino = pieces.shape(piece.get_tetr_index());
+    let mut cells: Vec<(i32, i32)> = Vec::new();
+    for row in 0..tetromino.side {
+        for col in 0..tetromino.side {
+            if tetromino.tetromino_get(row, col, piece.get_rotation()) > 0 {
+                cells.push((row, col + piece.get_offset_col()));
+            }
+        }
+    }
+    let top: i32 = cells.iter().map(|c| c.0).min().unwrap_or(0);
+    for cell in cells.iter_mut() {
+        cell.0 -= top;
+    }
+    cells.sort();
+    return cells;
+}
+
+// Breadth first search over move and rotate presses from the spawn
+// position, keeping the piece at its spawn row. Holding left or right until
+// the piece reaches the wall counts as one press, and 180 rotation is only
+// offered where the ruleset has it. Returns the fewest presses needed for
+// every reachable placement.
+pub fn find_optimal_inputs(
+    game: &Game_State,
+    spawn: &Piece_State,
+) -> HashMap<Vec<(i32, i32)>, u32> {
+    let mut distances: HashMap<Vec<(i32, i32)>, u32> = HashMap::new();
+    let mut visited: HashMap<(i32, i32), u32> = HashMap::new();
+    let mut queue: VecDeque<Piece_State> = VecDeque::new();
+    if !spawn.check_piece_valid(game) {
+        return distances;
+    }
+    visited.insert((spawn.get_rotation(), spawn.get_offset_col()), 0);
+    queue.push_back(*spawn);
+    while let Some(piece) = queue.pop_front() {
+        let dist: u32 = visited[&(piece.get_rotation(), piece.get_offset_col())];
+        let key: Vec<(i32, i32)> = placement_key(game.pieces(), &piece);
+        let best: &mut u32 = distances.entry(key).or_insert(dist);
+        if dist < *best {
+            *best = dist;
+        }
+        let mut moves: Vec<Piece_State> = Vec::new();
+        for step in [-1, 1] {
+            let mut moved: Piece_State = piece;
+            moved.set_offset_col(piece.get_offset_col() + step);
+            moves.push(moved);
+            let mut wall: Piece_State = piece;
+            while moved.check_piece_valid(game) {
+                wall = moved;
+                moved.set_offset_col(moved.get_offset_col() + step);
+            }
+            moves.push(wall);
+        }
+        let mut turns: Vec<i32> = vec![1, 3];
+        if game.ruleset == Game_Ruleset::RULESET_MODERN {
+            turns.push(2);
+        }
+        for turns in turns {
+            if let Some(rotated) = game.rotated(&piece, turns) {
+                moves.push(rotated);
+            }
+        }
+        for next in moves {
+            let state: (i32, i32) = (next.get_rotation(), next.get_offset_col());
+            if visited.contains_key(&state) || !next.check_piece_valid(game) {
+                continue;
+            }
+            visited.insert(state, dist + 1);
+            queue.push_back(next);
+        }
+    }
+    return distances;
+}
+
+pub struct Finesse_Tracker {
+    in_play: bool,
+    held: Option<u8>,
+    presses: u32,
+    optimal: HashMap<Vec<(i32, i32)>, u32>,
+    pub last_faults: u32,
+    pub last_lock_time: f32,
+    pub pieces: u32,
+    pub faulted_pieces: u32,
+    pub total_faults: u32,
+}
+
+impl Finesse_Tracker {
+    pub fn new() -> Self {
+        Finesse_Tracker {
+            in_play: false,
+            held: None,
+            presses: 0,
+            optimal: HashMap::new(),
+            last_faults: 0,
+            last_lock_time: f32::NEG_INFINITY,
+            pieces: 0,
+            faulted_pieces: 0,
+            total_faults: 0,
+        }
+    }
+    // Call after every Game_State::update with its input and the events it
+    // queued. The input is handled before anything the update did to the
+    // piece, so its presses count towards the piece that was in play. A
+    // piece is judged when it locks, and a new piece, from the queue or the
+    // hold, starts with no presses and is searched from its spawn position.
+    pub fn handle_update(&mut self, game: &Game_State, input: &Input_State, events: &[Game_Event]) {
+        if self.in_play && game.phase != Game_Phase::GAME_PHASE_PAUSE {
+            self.presses += (input.dleft > 0) as u32
+                + (input.dright > 0) as u32
+                + (input.dup > 0) as u32
+                + (input.dccw > 0) as u32
+                + (input.dflip > 0) as u32;
+        }
+        for event in events.iter() {
+            match *event {
+                Game_Event::EVENT_GAME_START => {
+                    self.in_play = false;
+                    self.held = None;
+                    self.pieces = 0;
+                    self.faulted_pieces = 0;
+                    self.total_faults = 0;
+                    self.last_lock_time = f32::NEG_INFINITY;
+                }
+                Game_Event::EVENT_PIECE_SPAWNED { tetromino_index } => {
+                    self.track(game, tetromino_index);
+                }
+                // A piece from the queue was already spawned, one from the
+                // hold comes in at its spawn position too.
+                Game_Event::EVENT_HOLD_USED { tetromino_index } => {
+                    if let Some(held) = self.held {
+                        self.track(game, held);
+                    }
+                    self.held = Some(tetromino_index);
+                }
+                // An update locks at most one piece, so the last one is
+                // the one this event is about.
+                Game_Event::EVENT_PIECE_LOCKED { .. } => {
+                    self.in_play = false;
+                    if let Some(locked) = game.last_locked {
+                        self.judge(&locked, game);
+                    }
+                }
+                Game_Event::EVENT_GAME_OVER { .. } => {
+                    self.in_play = false;
+                }
+                _ => {}
+            }
+        }
+    }
+    fn track(&mut self, game: &Game_State, index: u8) {
+        self.in_play = true;
+        self.presses = 0;
+        self.optimal = find_optimal_inputs(game, &game.spawn_state(index));
+    }
+    // Placements the search can't reach, like tucks under an overhang,
+    // are not judged.
+    fn judge(&mut self, locked: &Piece_State, game: &Game_State) {
+        let Some(optimal) = self.optimal.get(&placement_key(game.pieces(), locked)) else {
+            return;
+        };
+        self.last_faults = self.presses.saturating_sub(*optimal);
+        self.last_lock_time = game.get_time();
+        self.pieces += 1;
+        self.total_faults += self.last_faults;
+        if self.last_faults > 0 {
+            self.faulted_pieces += 1;
+        }
+    }
+}
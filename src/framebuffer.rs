@@ -0,0 +1,411 @@
+// An RGBA image in memory that the renderer can draw into without SDL or a
+// window: for screenshots, PNG frames of replays and golden-image tests on
+// headless machines. It draws the same rectangles and cells as the window.
+// Text is rasterized with ab_glyph from the theme's font file, so glyph
+// edges can differ slightly from SDL_ttf.
+use super::animation::*;
+use super::config::*;
+use super::game::*;
+use super::highscores::*;
+use super::layout::*;
+use super::render::*;
+use super::replay::*;
+use super::theme::*;
+use ab_glyph::{Font, FontVec, GlyphId, PxScale, ScaleFont};
+use std::path::{Path, PathBuf};
+
+pub const SCREENSHOT_DIR: &str = "screenshots";
+
+struct Raster_Font {
+    font: FontVec,
+    scale: PxScale,
+}
+
+pub struct Frame_Buffer {
+    pub width: u32,
+    pub height: u32,
+    // Rows of RGBA pixels from the top left.
+    pub pixels: Vec<u8>,
+    clip: Option<Layout_Rect>,
+    font: Option<Raster_Font>,
+    skin: Option<Box<Frame_Buffer>>,
+}
+
+impl Frame_Buffer {
+    // Starts out opaque black.
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut pixels: Vec<u8> = vec![0; (width * height * 4) as usize];
+        for pixel in pixels.chunks_mut(4) {
+            pixel[3] = 0xFF;
+        }
+        return Frame_Buffer::from_rgba(width, height, pixels);
+    }
+    pub fn from_rgba(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        Frame_Buffer {
+            width: width,
+            height: height,
+            pixels: pixels,
+            clip: None,
+            font: None,
+            skin: None,
+        }
+    }
+    // Loads the theme's font at the size the window would use for cells of
+    // `cell` pixels, and its skin.
+    pub fn set_theme(&mut self, theme: &Theme, cell: i32) {
+        let size: f32 = font_size(theme, cell) as f32;
+        self.font = None;
+        for path in [theme.font.as_str(), DEFAULT_FONT] {
+            let font = std::fs::read(path)
+                .map_err(|e| e.to_string())
+                .and_then(|data| FontVec::try_from_vec(data).map_err(|e| e.to_string()));
+            match font {
+                Ok(font) => {
+                    // Font sizes are in points at 72 dpi, as in SDL_ttf.
+                    let scale: PxScale = font.pt_to_px_scale(size).unwrap_or(PxScale::from(size));
+                    self.font = Some(Raster_Font {
+                        font: font,
+                        scale: scale,
+                    });
+                    break;
+                }
+                Err(e) => eprintln!("{}: {}", path, e),
+            }
+        }
+        self.skin = None;
+        if let Some(path) = &theme.skin {
+            match load_bmp(path) {
+                Ok(skin) => self.skin = Some(Box::new(skin)),
+                Err(e) => eprintln!("{}: {}", path.display(), e),
+            }
+        }
+    }
+    pub fn clear(&mut self, color: Draw_Color) {
+        for pixel in self.pixels.chunks_mut(4) {
+            pixel.copy_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+    }
+    pub fn pixel(&self, x: u32, y: u32) -> Draw_Color {
+        let i: usize = ((y * self.width + x) * 4) as usize;
+        let p: &[u8] = &self.pixels[i..i + 4];
+        return color(p[0], p[1], p[2], p[3]);
+    }
+    // Blends one pixel over what is there, unless it is off the image or
+    // outside the clip rectangle.
+    fn blend_pixel(&mut self, x: i32, y: i32, color: Draw_Color) {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return;
+        }
+        if let Some(clip) = self.clip
+            && (x < clip.x || y < clip.y || x >= clip.right() || y >= clip.bottom())
+        {
+            return;
+        }
+        let i: usize = ((y as u32 * self.width + x as u32) * 4) as usize;
+        let pixel: &mut [u8] = &mut self.pixels[i..i + 4];
+        let a: u32 = color.a as u32;
+        if a == 0xFF {
+            pixel.copy_from_slice(&[color.r, color.g, color.b, 0xFF]);
+            return;
+        }
+        let blend = |src: u8, dst: u8| (src as u32 * a + dst as u32 * (255 - a) + 127) / 255;
+        pixel[0] = blend(color.r, pixel[0]) as u8;
+        pixel[1] = blend(color.g, pixel[1]) as u8;
+        pixel[2] = blend(color.b, pixel[2]) as u8;
+        pixel[3] = (a + pixel[3] as u32 * (255 - a) / 255) as u8;
+    }
+    fn fill_rect(&mut self, rect: Layout_Rect, color: Draw_Color) {
+        let left: i32 = rect.x.max(0);
+        let top: i32 = rect.y.max(0);
+        let right: i32 = rect.right().min(self.width as i32);
+        let bottom: i32 = rect.bottom().min(self.height as i32);
+        for y in top..bottom {
+            for x in left..right {
+                self.blend_pixel(x, y, color);
+            }
+        }
+    }
+    pub fn write_png<W: std::io::Write>(&self, writer: W) -> std::io::Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(std::io::Error::other)?;
+        writer
+            .write_image_data(&self.pixels)
+            .map_err(std::io::Error::other)?;
+        return Ok(());
+    }
+    pub fn save_png(&self, path: &Path) -> std::io::Result<()> {
+        let file = std::fs::File::create(path)?;
+        return self.write_png(std::io::BufWriter::new(file));
+    }
+}
+
+impl Draw_Target for Frame_Buffer {
+    fn size(&self) -> (u32, u32) {
+        return (self.width, self.height);
+    }
+    fn fill_rects(&mut self, rects: &[Layout_Rect], color: Draw_Color) {
+        for rect in rects {
+            self.fill_rect(*rect, color);
+        }
+    }
+    fn draw_cell(&mut self, rect: Layout_Rect, colors: [Draw_Color; 3], look: Cell_Look) {
+        for (fill, index) in cell_fills(rect.w, look) {
+            let fill: Layout_Rect =
+                Layout_Rect::new(rect.x + fill.x, rect.y + fill.y, fill.w, fill.h);
+            self.fill_rect(fill, colors[index]);
+        }
+    }
+    // Tiles are scaled to the cell by nearest neighbour, like SDL does.
+    fn draw_skin_tile(&mut self, rect: Layout_Rect, value: u8) -> bool {
+        let Some(skin) = self.skin.take() else {
+            return false;
+        };
+        let tile: i32 = skin.height as i32;
+        if (value as i32 + 1) * tile > skin.width as i32 {
+            self.skin = Some(skin);
+            return false;
+        }
+        for dy in 0..rect.h {
+            for dx in 0..rect.w {
+                let sx: i32 = value as i32 * tile + dx * tile / rect.w;
+                let sy: i32 = dy * tile / rect.h;
+                if sx < skin.width as i32 {
+                    self.blend_pixel(rect.x + dx, rect.y + dy, skin.pixel(sx as u32, sy as u32));
+                }
+            }
+        }
+        self.skin = Some(skin);
+        return true;
+    }
+    fn set_clip(&mut self, clip: Option<Layout_Rect>) {
+        self.clip = clip;
+    }
+    // Without a font no text is drawn.
+    fn line_height(&self) -> i32 {
+        let Some(raster) = &self.font else {
+            return 1;
+        };
+        let scaled = raster.font.as_scaled(raster.scale);
+        return ((scaled.height() + scaled.line_gap()).round() as i32).max(1);
+    }
+    fn text_size(&mut self, text: &str) -> (i32, i32) {
+        let Some(raster) = &self.font else {
+            return (0, 0);
+        };
+        let scaled = raster.font.as_scaled(raster.scale);
+        let mut width: f32 = 0.0;
+        let mut previous: Option<GlyphId> = None;
+        for c in text.chars() {
+            let id: GlyphId = scaled.glyph_id(c);
+            if let Some(previous) = previous {
+                width += scaled.kern(previous, id);
+            }
+            width += scaled.h_advance(id);
+            previous = Some(id);
+        }
+        return (width.ceil() as i32, scaled.height().ceil() as i32);
+    }
+    fn draw_text(&mut self, text: &str, x: i32, y: i32, color: Draw_Color) {
+        let Some(raster) = &self.font else {
+            return;
+        };
+        let scaled = raster.font.as_scaled(raster.scale);
+        // Coverage is gathered first, as the font is borrowed from self.
+        let mut coverage: Vec<(i32, i32, f32)> = Vec::new();
+        let mut caret: f32 = x as f32;
+        let mut previous: Option<GlyphId> = None;
+        for c in text.chars() {
+            let id: GlyphId = scaled.glyph_id(c);
+            if let Some(previous) = previous {
+                caret += scaled.kern(previous, id);
+            }
+            let glyph = id.with_scale_and_position(
+                raster.scale,
+                ab_glyph::point(caret, y as f32 + scaled.ascent()),
+            );
+            caret += scaled.h_advance(id);
+            previous = Some(id);
+            let Some(outline) = raster.font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outline.px_bounds();
+            outline.draw(|gx, gy, c| {
+                coverage.push((
+                    bounds.min.x as i32 + gx as i32,
+                    bounds.min.y as i32 + gy as i32,
+                    c,
+                ));
+            });
+        }
+        for (px, py, c) in coverage {
+            let mut pixel_color: Draw_Color = color;
+            pixel_color.a = (color.a as f32 * c.clamp(0.0, 1.0)).round() as u8;
+            if pixel_color.a > 0 {
+                self.blend_pixel(px, py, pixel_color);
+            }
+        }
+    }
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    return Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?));
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    return Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?));
+}
+
+// Reads the uncompressed 24 and 32 bit BMP files that skins are saved as.
+pub fn load_bmp(path: &Path) -> Result<Frame_Buffer, String> {
+    let data: Vec<u8> = std::fs::read(path).map_err(|e| e.to_string())?;
+    let bad = || "not a 24 or 32 bit uncompressed BMP".to_string();
+    if data.get(0..2) != Some(b"BM") {
+        return Err(bad());
+    }
+    let offset: usize = read_u32(&data, 10).ok_or_else(bad)? as usize;
+    let width: i32 = read_u32(&data, 18).ok_or_else(bad)? as i32;
+    let height: i32 = read_u32(&data, 22).ok_or_else(bad)? as i32;
+    let bits: u16 = read_u16(&data, 28).ok_or_else(bad)?;
+    let compression: u32 = read_u32(&data, 30).ok_or_else(bad)?;
+    // Bitfields are only accepted in the usual BGRA order.
+    if width <= 0
+        || height == 0
+        || (bits != 24 && bits != 32)
+        || (compression != 0 && compression != 3)
+    {
+        return Err(bad());
+    }
+    let bytes: usize = bits as usize / 8;
+    let stride: usize = (width as usize * bytes).div_ceil(4) * 4;
+    let rows: u32 = height.unsigned_abs();
+    let mut buffer: Frame_Buffer = Frame_Buffer::new(width as u32, rows);
+    for row in 0..rows {
+        // Rows are stored bottom up unless the height is negative.
+        let mut source_row: u32 = rows - 1 - row;
+        if height < 0 {
+            source_row = row;
+        }
+        for col in 0..width as usize {
+            let at: usize = offset + source_row as usize * stride + col * bytes;
+            let p: &[u8] = data.get(at..at + 3).ok_or_else(bad)?;
+            let i: usize = ((row * width as u32 + col as u32) * 4) as usize;
+            buffer.pixels[i..i + 4].copy_from_slice(&[p[2], p[1], p[0], 0xFF]);
+        }
+    }
+    return Ok(buffer);
+}
+
+// Reads an 8 bit RGB or RGBA PNG, such as the ones save_png writes.
+pub fn load_png(path: &Path) -> Result<Frame_Buffer, String> {
+    let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let decoder = png::Decoder::new(std::io::BufReader::new(file));
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut data: Vec<u8> = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).map_err(|e| e.to_string())?;
+    data.truncate(info.buffer_size());
+    if info.bit_depth != png::BitDepth::Eight {
+        return Err("not an 8 bit PNG".to_string());
+    }
+    let pixels: Vec<u8> = match info.color_type {
+        png::ColorType::Rgba => data,
+        png::ColorType::Rgb => data
+            .chunks(3)
+            .flat_map(|p| [p[0], p[1], p[2], 0xFF])
+            .collect(),
+        _ => return Err("not an RGB or RGBA PNG".to_string()),
+    };
+    return Ok(Frame_Buffer::from_rgba(info.width, info.height, pixels));
+}
+
+// A new file in the screenshot directory, named by date.
+pub fn screenshot_path() -> std::io::Result<PathBuf> {
+    let dir: PathBuf = config_path(SCREENSHOT_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let date: String = today();
+    let mut n: u32 = 1;
+    loop {
+        let path: PathBuf = dir.join(format!("{}-{:03}.png", date, n));
+        if !path.exists() {
+            return Ok(path);
+        }
+        n += 1;
+    }
+}
+
+// The size of the frames render_replay_frames draws for a board of `board`.
+pub fn replay_frame_size(cell: i32, hud: bool, board: Board_Size) -> (u32, u32) {
+    if hud {
+        return layout_size(1, cell, board);
+    }
+    return ((board.width * cell) as u32, (board.height * cell) as u32);
+}
+
+// Plays a replay back at 60 frames a second, as the window would show it
+// with cells of `cell` pixels, and hands every `step`th frame and the last
+// one to `on_frame` with its frame number. Without the HUD the image is just
+// the board. Returns how many frames were handed over.
+pub fn render_replay_frames(
+    replay: &Replay,
+    view: &View,
+    cell: i32,
+    step: u32,
+    hud: bool,
+    on_frame: &mut dyn FnMut(u32, &Frame_Buffer) -> std::io::Result<()>,
+) -> std::io::Result<u32> {
+    let (width, height) = layout_size(1, cell, replay.board_size);
+    let mut layout: Board_Layout = compute_layout(width, height, 1, replay.board_size)[0];
+    if !hud {
+        // The panels fall outside the image and are clipped away.
+        layout = layout.offset(-layout.board.x, -layout.board.y);
+    }
+    let (width, height) = replay_frame_size(cell, hud, replay.board_size);
+    let mut buffer: Frame_Buffer = Frame_Buffer::new(width, height);
+    buffer.set_theme(view.theme, cell);
+    let mut player: Replay_Player = Replay_Player::new(replay.clone());
+    let mut game: Game_State = replay.start_game();
+    let mut animator: Animator = Animator::new();
+    animator.enabled = view.animations;
+    let mut motion: Piece_Motion = Piece_Motion::new();
+    let mut frame: u32 = 0;
+    let mut rendered: u32 = 0;
+    loop {
+        player.update(&mut game, frame as f32 * TARGET_SECONDS_PER_FRAME);
+        let events: Vec<Game_Event> = game.drain_events();
+        animator.handle_events(&events, &game);
+        animator.update(&game);
+        motion.handle_events(&events, &game);
+        let last: bool = player.is_finished();
+        if frame.is_multiple_of(step.max(1)) || last {
+            buffer.clear(theme_color(view.theme.background));
+            let (shake_x, shake_y) = animator.shake_offset(game.get_time());
+            let layout: Board_Layout = layout.offset(
+                (shake_x * cell as f32) as i32,
+                (shake_y * cell as f32) as i32,
+            );
+            render_game(&game, &mut buffer, view, &motion, &layout);
+            render_animations(&mut buffer, view, &animator, &game, &layout);
+            on_frame(frame, &buffer)?;
+            rendered += 1;
+        }
+        if last {
+            return Ok(rendered);
+        }
+        frame += 1;
+    }
+}
+
+// Saves the frames of a replay as numbered PNG files in `dir`.
+pub fn save_replay_frames(
+    replay: &Replay,
+    view: &View,
+    cell: i32,
+    dir: &Path,
+    step: u32,
+) -> std::io::Result<u32> {
+    std::fs::create_dir_all(dir)?;
+    return render_replay_frames(replay, view, cell, step, true, &mut |frame, buffer| {
+        return buffer.save_png(&dir.join(format!("{:06}.png", frame)));
+    });
+}
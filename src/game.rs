@@ -1,9 +1,25 @@
+use super::pieces::*;
 use super::tetromino::*;
 use rand::Rng;
-pub const WIDTH: usize = 10;
-pub const HEIGHT: usize = 22;
-pub const VISIBLE_HEIGHT: usize = 20;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::collections::VecDeque;
+// Pieces spawn in rows hidden above the visible part of the board.
+pub const HIDDEN_ROWS: i32 = 2;
+pub const DEFAULT_BOARD_SIZE: Board_Size = Board_Size {
+    width: 10,
+    height: 20,
+};
+pub const MIN_BOARD_WIDTH: i32 = 4;
+pub const MAX_BOARD_WIDTH: i32 = 40;
+pub const MIN_BOARD_HEIGHT: i32 = 8;
+// Cleared rows are reported as bits of a u64, hidden rows included.
+pub const MAX_BOARD_HEIGHT: i32 = 60;
 pub const GRID_SIZE: usize = 30;
+// Above the value of any piece.
+pub const GARBAGE_CELL: u8 = 255;
+// How long cleared rows stay on the board before they are removed.
+pub const LINE_CLEAR_TIME: f32 = 0.5;
 
 pub static FRAMES_PER_DROP: [u32; 30] = [
     48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
@@ -12,9 +28,8 @@ pub static FRAMES_PER_DROP: [u32; 30] = [
 
 pub const TARGET_SECONDS_PER_FRAME: f32 = 1.0 / 60.0;
 
-pub fn random_int(min: u8, max: u8) -> u8 {
+pub fn random_int(rng: &mut StdRng, min: u8, max: u8) -> u8 {
     let range: u8 = max - min;
-    let mut rng = rand::rng();
     let random_num: u8 = rng.random();
     return min + random_num % range;
 }
@@ -35,15 +50,222 @@ pub fn max(x: i32, y: i32) -> i32 {
     }
 }
 
+// Columns and visible rows of a board.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Board_Size {
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Board_Size {
+    // Clamped to the sizes the game supports.
+    pub fn new(width: i32, height: i32) -> Self {
+        Board_Size {
+            width: width.clamp(MIN_BOARD_WIDTH, MAX_BOARD_WIDTH),
+            height: height.clamp(MIN_BOARD_HEIGHT, MAX_BOARD_HEIGHT),
+        }
+    }
+    // All rows, the hidden ones included.
+    pub fn rows(&self) -> i32 {
+        return self.height + HIDDEN_ROWS;
+    }
+    // Written as WIDTHxHEIGHT, like 10x20.
+    pub fn name(&self) -> String {
+        return format!("{}x{}", self.width, self.height);
+    }
+    pub fn from_name(name: &str) -> Option<Board_Size> {
+        let (width, height) = name.split_once('x')?;
+        return Some(Board_Size::new(width.parse().ok()?, height.parse().ok()?));
+    }
+}
+
+// The cells of a board row by row from the top, hidden rows included.
+#[derive(Clone, PartialEq)]
+pub struct Board {
+    pub size: Board_Size,
+    pub cells: Vec<u8>,
+}
+
+impl Board {
+    pub fn new(size: Board_Size) -> Self {
+        Board {
+            size: size,
+            cells: vec![0; (size.width * size.rows()) as usize],
+        }
+    }
+    pub fn width(&self) -> i32 {
+        return self.size.width;
+    }
+    pub fn rows(&self) -> i32 {
+        return self.size.rows();
+    }
+    pub fn contains(&self, row: i32, col: i32) -> bool {
+        return row >= 0 && row < self.rows() && col >= 0 && col < self.width();
+    }
+    pub fn get(&self, row: i32, col: i32) -> u8 {
+        return self.cells[(row * self.width() + col) as usize];
+    }
+    pub fn set(&mut self, row: i32, col: i32, value: u8) {
+        let width: i32 = self.width();
+        self.cells[(row * width + col) as usize] = value;
+    }
+    pub fn row(&self, row: i32) -> &[u8] {
+        let start: usize = (row * self.width()) as usize;
+        return &self.cells[start..start + self.width() as usize];
+    }
+}
+
 #[derive(PartialEq, Default)]
 pub enum Game_Phase {
     #[default]
     GAME_PHASE_START,
     GAME_PHASE_PLAY,
     GAME_PHASE_LINE,
+    GAME_PHASE_PAUSE,
     GAME_PHASE_GAMEOVER,
 }
 
+// Marathon goes on until the stack tops out, sprint ends after
+// SPRINT_LINES lines and ultra after ULTRA_TIME seconds.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Game_Mode {
+    MODE_MARATHON,
+    MODE_SPRINT,
+    MODE_ULTRA,
+}
+
+pub static GAME_MODES: [Game_Mode; 3] = [
+    Game_Mode::MODE_MARATHON,
+    Game_Mode::MODE_SPRINT,
+    Game_Mode::MODE_ULTRA,
+];
+
+pub static GAME_MODE_NAMES: [&str; 3] = ["marathon", "sprint", "ultra"];
+
+pub const SPRINT_LINES: i32 = 40;
+pub const ULTRA_TIME: f32 = 120.0;
+
+// Minutes, seconds and hundredths, e.g. 1:05.42.
+pub fn format_time(seconds: f32) -> String {
+    let hundredths: u32 = (seconds.max(0.0) * 100.0) as u32;
+    return format!(
+        "{}:{:02}.{:02}",
+        hundredths / 6000,
+        hundredths / 100 % 60,
+        hundredths % 100
+    );
+}
+
+impl Game_Mode {
+    pub fn name(&self) -> &'static str {
+        return GAME_MODE_NAMES[*self as usize];
+    }
+    pub fn from_name(name: &str) -> Option<Game_Mode> {
+        return GAME_MODE_NAMES
+            .iter()
+            .position(|n| *n == name)
+            .map(|i| GAME_MODES[i]);
+    }
+}
+
+// The classic ruleset plays like the original: no hold, no hard drop and no
+// 180 degree rotation.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Game_Ruleset {
+    RULESET_MODERN,
+    RULESET_CLASSIC,
+}
+
+pub static GAME_RULESETS: [Game_Ruleset; 2] =
+    [Game_Ruleset::RULESET_MODERN, Game_Ruleset::RULESET_CLASSIC];
+
+pub static GAME_RULESET_NAMES: [&str; 2] = ["modern", "classic"];
+
+impl Game_Ruleset {
+    pub fn name(&self) -> &'static str {
+        return GAME_RULESET_NAMES[*self as usize];
+    }
+    pub fn from_name(name: &str) -> Option<Game_Ruleset> {
+        return GAME_RULESET_NAMES
+            .iter()
+            .position(|n| *n == name)
+            .map(|i| GAME_RULESETS[i]);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Clear_Kind {
+    CLEAR_SINGLE,
+    CLEAR_DOUBLE,
+    CLEAR_TRIPLE,
+    CLEAR_TETRIS,
+}
+
+impl Clear_Kind {
+    // Pieces taller than four cells can clear more lines at once, which
+    // counts as a tetris.
+    pub fn from_line_count(count: i32) -> Option<Clear_Kind> {
+        match count {
+            1 => return Some(Clear_Kind::CLEAR_SINGLE),
+            2 => return Some(Clear_Kind::CLEAR_DOUBLE),
+            3 => return Some(Clear_Kind::CLEAR_TRIPLE),
+            4.. => return Some(Clear_Kind::CLEAR_TETRIS),
+            _ => return None,
+        }
+    }
+    pub fn line_count(&self) -> i32 {
+        return *self as i32 + 1;
+    }
+}
+
+// Things that happen during Game_State::update. They are queued for whoever
+// wants to react to them and drained with drain_events once per frame, or
+// passed straight to an observer set with set_observer. Loops that run a
+// game for a long time must drain the queue even if they ignore it.
+#[derive(Clone, Copy)]
+pub enum Game_Event {
+    EVENT_GAME_START,
+    EVENT_PIECE_SPAWNED {
+        tetromino_index: u8,
+    },
+    // The piece after a successful shift or drop by one cell, and the
+    // direction it moved in.
+    EVENT_PIECE_MOVED {
+        piece: Piece_State,
+        step_col: i32,
+        step_row: i32,
+    },
+    EVENT_PIECE_ROTATED {
+        piece: Piece_State,
+    },
+    // Where a hard drop will land the piece and how many rows it falls.
+    // Sent before the piece moves.
+    EVENT_HARD_DROP {
+        piece: Piece_State,
+        distance: i32,
+    },
+    EVENT_PIECE_LOCKED {
+        tetromino_index: u8,
+    },
+    // Bit r of `rows` is set for every cleared board row r.
+    EVENT_LINES_CLEARED {
+        rows: u64,
+        kind: Clear_Kind,
+    },
+    EVENT_LEVEL_UP {
+        level: i32,
+    },
+    // The piece that went into the hold slot.
+    EVENT_HOLD_USED {
+        tetromino_index: u8,
+    },
+    EVENT_GAME_OVER {
+        finished: bool,
+    },
+}
+
+pub type Game_Observer = Box<dyn FnMut(&Game_Event)>;
+
 struct IndexBuffer {
     current: u8,
     next: u8,
@@ -62,7 +284,7 @@ impl Piece_State {
         Piece_State {
             tetromino_index: index,
             offset_row: 0,
-            offset_col: WIDTH as i32 / 2,
+            offset_col: 0,
             rotation: 0,
         }
     }
@@ -78,24 +300,30 @@ impl Piece_State {
     pub fn get_offset_row(&self) -> i32 {
         return self.offset_row;
     }
+    pub fn set_rotation(&mut self, rotation: i32) {
+        self.rotation = rotation;
+    }
+    pub fn set_offset_col(&mut self, offset_col: i32) {
+        self.offset_col = offset_col;
+    }
     pub fn move_up(&mut self) {
         self.offset_row -= 1;
     }
     pub fn move_down(&mut self) {
         self.offset_row += 1;
     }
-    pub fn check_piece_valid(&self, game: &Game_State, width: i32, height: i32) -> bool {
-        let tetromino: &Tetromino = &TETROMINOS[(self.tetromino_index) as usize];
+    pub fn check_piece_valid(&self, game: &Game_State) -> bool {
+        let tetromino: Tetromino = game.pieces.shape(self.tetromino_index);
         for row in 0..tetromino.side {
             for col in 0..tetromino.side {
                 let value: u8 = tetromino.tetromino_get(row, col, self.rotation);
                 if value > 0 {
                     let board_row: i32 = self.offset_row + row;
                     let board_col: i32 = self.offset_col + col;
-                    if board_row < 0 || board_row >= height || board_col < 0 || board_col >= width {
+                    if !game.board.contains(board_row, board_col) {
                         return false;
                     }
-                    if game.matrix_get(width, board_row, board_col) != 0 {
+                    if game.matrix_get(board_row, board_col) != 0 {
                         return false;
                     }
                 }
@@ -106,81 +334,193 @@ impl Piece_State {
 }
 
 pub struct Game_State {
-    board: [u8; WIDTH * HEIGHT],
-    pub lines: [u8; HEIGHT],
+    board: Board,
+    pieces: Piece_Set,
+    // One entry per row, hidden rows included: 1 for a row being cleared.
+    pub lines: Vec<u8>,
     pub pending_line_count: i32,
     pub line_count: i32,
     pub points: i32,
     pub piece: Piece_State,
     pub piece_next: Piece_State,
+    pub piece_hold: Option<u8>,
+    pub hold_used: bool,
     pub phase: Game_Phase,
     pub start_level: i32,
     pub level: i32,
     pub next_drop_time: f32,
     pub highlight_end_time: f32,
+    pub pause_time: f32,
+    pub piece_count: u32,
+    pub last_locked: Option<Piece_State>,
+    pub garbage_received: u32,
+    pub mode: Game_Mode,
+    pub ruleset: Game_Ruleset,
+    // Delayed auto shift: how long left or right is held before the piece
+    // starts to repeat, then the auto repeat rate, both in seconds. An
+    // auto repeat rate of 0 moves the piece straight to the wall.
+    pub das: f32,
+    pub arr: f32,
+    // Set when a sprint or ultra game ends by reaching its goal rather than
+    // by topping out.
+    pub finished: bool,
+    pub game_seed: u64,
+    start_time: f32,
+    end_time: f32,
+    shift_dir: i32,
+    next_shift_time: f32,
+    events: Vec<Game_Event>,
+    observer: Option<Game_Observer>,
     time: f32,
     index_buffer: IndexBuffer,
+    rng: StdRng,
 }
 
 impl Game_State {
     pub fn new() -> Self {
+        return Game_State::new_seeded(rand::random());
+    }
+    pub fn new_seeded(seed: u64) -> Self {
+        let mut rng: StdRng = StdRng::seed_from_u64(seed);
+        let pieces: Piece_Set = standard_pieces();
+        let current: u8 = random_int(&mut rng, 0, pieces.len() as u8);
+        let next: u8 = random_int(&mut rng, 0, pieces.len() as u8);
         Game_State {
-            board: [0; WIDTH * HEIGHT],
-            lines: [0; HEIGHT],
+            board: Board::new(DEFAULT_BOARD_SIZE),
+            pieces: pieces,
+            lines: vec![0; DEFAULT_BOARD_SIZE.rows() as usize],
             pending_line_count: 0,
             line_count: 0,
             points: 0,
             piece: Piece_State::new(0),
             piece_next: Piece_State::new(1),
+            piece_hold: None,
+            hold_used: false,
             phase: Game_Phase::GAME_PHASE_START,
             start_level: 1,
             level: 1,
             next_drop_time: 0.0,
             highlight_end_time: 0.0,
+            pause_time: 0.0,
+            piece_count: 0,
+            last_locked: None,
+            garbage_received: 0,
+            mode: Game_Mode::MODE_MARATHON,
+            ruleset: Game_Ruleset::RULESET_MODERN,
+            das: 0.167,
+            arr: 0.033,
+            finished: false,
+            game_seed: 0,
+            start_time: 0.0,
+            end_time: 0.0,
+            shift_dir: 0,
+            next_shift_time: 0.0,
+            events: Vec::new(),
+            observer: None,
             time: 0.0,
             index_buffer: IndexBuffer {
-                current: random_int(0, 7),
-                next: random_int(0, 7),
+                current: current,
+                next: next,
             },
+            rng: rng,
         }
     }
     pub fn set_time(&mut self, value: f32) {
         self.time = value;
     }
-    pub fn matrix_get(&self, width: i32, row: i32, col: i32) -> u8 {
-        let index: i32 = row * width + col;
-        return self.board[index as usize];
+    pub fn get_time(&self) -> f32 {
+        return self.time;
     }
-    fn matrix_set(&mut self, width: i32, row: i32, col: i32, value: u8) {
-        let index: i32 = row * width + col;
-        self.board[index as usize] = value;
+    // How far the piece is towards its next gravity step, from 0 just after
+    // a step to 1 right before the next.
+    pub fn drop_progress(&self) -> f32 {
+        let left: f32 = (self.next_drop_time - self.time) / self.get_time_to_next_drop();
+        return 1.0 - left.clamp(0.0, 1.0);
     }
-    fn check_row_filled(&self, width: i32, row: i32) -> u8 {
-        for col in 0..width {
-            if self.matrix_get(width, row, col) == 0 {
-                return 0;
-            }
+    pub fn drain_events(&mut self) -> Vec<Game_Event> {
+        return std::mem::take(&mut self.events);
+    }
+    // While an observer is set, events go to it instead of the queue.
+    pub fn set_observer(&mut self, observer: Option<Game_Observer>) {
+        self.observer = observer;
+    }
+    fn emit(&mut self, event: Game_Event) {
+        match self.observer.as_mut() {
+            Some(observer) => observer(&event),
+            None => self.events.push(event),
+        }
+    }
+    pub fn get_start_time(&self) -> f32 {
+        return self.start_time;
+    }
+    // Seconds since the game started, not counting pauses.
+    pub fn get_play_time(&self) -> f32 {
+        match self.phase {
+            Game_Phase::GAME_PHASE_START => return 0.0,
+            Game_Phase::GAME_PHASE_PAUSE => return self.pause_time - self.start_time,
+            Game_Phase::GAME_PHASE_GAMEOVER => return self.end_time - self.start_time,
+            _ => {}
+        }
+        return self.time - self.start_time;
+    }
+    pub fn get_board(&self) -> &Board {
+        return &self.board;
+    }
+    pub fn board_size(&self) -> Board_Size {
+        return self.board.size;
+    }
+    // Empties the board and resizes it. Meant for between games.
+    pub fn set_board_size(&mut self, size: Board_Size) {
+        let size: Board_Size = Board_Size::new(size.width, size.height);
+        self.board = Board::new(size);
+        self.lines = vec![0; size.rows() as usize];
+    }
+    pub fn pieces(&self) -> &Piece_Set {
+        return &self.pieces;
+    }
+    // Like the board size, meant for between games. The pieces shown on the
+    // start screen are swapped for ones from the new set.
+    pub fn set_piece_set(&mut self, pieces: Piece_Set) {
+        self.pieces = pieces;
+        self.piece = Piece_State::new(0);
+        self.piece_next = Piece_State::new(1 % self.pieces.len() as u8);
+        self.piece_hold = None;
+        self.index_buffer.current %= self.pieces.len() as u8;
+        self.index_buffer.next %= self.pieces.len() as u8;
+    }
+    fn random_piece(&mut self) -> u8 {
+        return random_int(&mut self.rng, 0, self.pieces.len() as u8);
+    }
+    pub fn matrix_get(&self, row: i32, col: i32) -> u8 {
+        return self.board.get(row, col);
+    }
+    fn matrix_set(&mut self, row: i32, col: i32, value: u8) {
+        self.board.set(row, col, value);
+    }
+    fn check_row_filled(&self, row: i32) -> u8 {
+        if self.board.row(row).contains(&0) {
+            return 0;
         }
         return 1;
     }
-    fn check_row_empty(&self, width: i32, row: i32) -> u8 {
-        for col in 0..width {
-            if self.matrix_get(width, row, col) != 0 {
-                return 0;
-            }
+    fn check_row_empty(&self, row: i32) -> u8 {
+        if self.board.row(row).iter().any(|v| *v != 0) {
+            return 0;
         }
         return 1;
     }
-    fn find_lines(&mut self, width: i32, height: i32) -> i32 {
+    fn find_lines(&mut self) -> i32 {
         let mut count: i32 = 0;
-        for row in 0..height {
-            let filled: u8 = self.check_row_filled(width, row);
+        for row in 0..self.board.rows() {
+            let filled: u8 = self.check_row_filled(row);
             self.lines[row as usize] = filled;
             count += filled as i32;
         }
         return count;
     }
-    fn clear_lines(&mut self, width: i32, height: i32) {
+    fn clear_lines(&mut self) {
+        let width: i32 = self.board.width();
+        let height: i32 = self.board.rows();
         let mut src_row: i32 = height - 1;
         for dst_row in (1..height).rev() {
             while src_row >= 0 && self.lines[src_row as usize] != 0 {
@@ -189,39 +529,59 @@ impl Game_State {
             if src_row < 0 {
                 let start: usize = (dst_row * width - 1) as usize;
                 let end: usize = (dst_row * width + width - 1) as usize;
-                self.board[start..=end].fill(0);
+                self.board.cells[start..=end].fill(0);
             } else {
                 if src_row != dst_row {
                     let dst_start: usize = (dst_row * width) as usize;
-                    let dst_end: usize = (dst_row * width + width) as usize;
                     let src_start: usize = (src_row * width) as usize;
                     let src_end: usize = (src_row * width + width) as usize;
-                    let slice: &Vec<u8> = &self.board[src_start..src_end].to_vec();
-                    self.board[dst_start..dst_end].copy_from_slice(slice);
+                    self.board.cells.copy_within(src_start..src_end, dst_start);
                 }
                 src_row -= 1;
             }
         }
     }
     fn merge_piece(&mut self) {
-        let tetromino: &Tetromino = &TETROMINOS[self.piece.tetromino_index as usize];
+        let tetromino: Tetromino = self.pieces.shape(self.piece.tetromino_index);
         for row in 0..tetromino.side {
             for col in 0..tetromino.side {
                 let value: u8 = tetromino.tetromino_get(row, col, self.piece.rotation);
                 if value != 0 {
                     let board_row: i32 = self.piece.offset_row + row;
                     let board_col: i32 = self.piece.offset_col + col;
-                    if board_row >= 0
-                        && board_row < HEIGHT as i32
-                        && board_col >= 0
-                        && board_col < WIDTH as i32
-                    {
-                        self.matrix_set(WIDTH as i32, board_row, board_col, value);
+                    if self.board.contains(board_row, board_col) {
+                        self.board.set(board_row, board_col, value);
                     }
                 }
             }
         }
-        self.index_buffer.next = random_int(0, 7);
+        self.index_buffer.next = self.random_piece();
+        self.last_locked = Some(self.piece);
+        self.emit(Game_Event::EVENT_PIECE_LOCKED {
+            tetromino_index: self.piece.tetromino_index,
+        });
+    }
+    // Pushes the stack up by `count` rows of garbage with a single gap at
+    // `hole_col`, lifting the falling piece out of the way if it can.
+    pub fn add_garbage(&mut self, count: i32, hole_col: i32) {
+        let width: i32 = self.board.width();
+        let height: i32 = self.board.rows();
+        let count: i32 = min(count, height);
+        if count <= 0 {
+            return;
+        }
+        let shift: usize = (count * width) as usize;
+        self.board.cells.copy_within(shift.., 0);
+        for row in (height - count)..height {
+            for col in 0..width {
+                let value: u8 = if col == hole_col { 0 } else { GARBAGE_CELL };
+                self.matrix_set(row, col, value);
+            }
+        }
+        while self.piece.offset_row > 0 && !self.piece.check_piece_valid(self) {
+            self.piece.move_up();
+        }
+        self.garbage_received += count as u32;
     }
     fn get_time_to_next_drop(&self) -> f32 {
         let mut faux_level = 0;
@@ -233,29 +593,134 @@ impl Game_State {
         return (FRAMES_PER_DROP[faux_level as usize]) as f32 * TARGET_SECONDS_PER_FRAME;
     }
 
+    // A piece at its spawn position, which the piece set decides.
+    pub fn spawn_state(&self, index: u8) -> Piece_State {
+        let mut piece: Piece_State = Piece_State::new(index);
+        piece.offset_col = self.pieces.spawn_col(index, self.board.width());
+        piece.offset_row = self.pieces.spawn_row(index);
+        return piece;
+    }
     fn spawn_piece(&mut self) {
         self.piece_next = Piece_State::new(self.index_buffer.next);
-        self.piece = Piece_State::new(self.index_buffer.current);
+        self.piece = self.spawn_state(self.index_buffer.current);
         self.index_buffer.current = self.index_buffer.next;
-        self.piece.offset_col =
-            (WIDTH as i32) / 2 - (TETROMINOS[self.piece.tetromino_index as usize].side as i32 / 2);
         self.next_drop_time = self.time + self.get_time_to_next_drop();
+        self.hold_used = false;
+        self.piece_count += 1;
+        self.emit(Game_Event::EVENT_PIECE_SPAWNED {
+            tetromino_index: self.piece.tetromino_index,
+        });
+    }
+    // Swaps the falling piece with the held one, or stores it and brings in
+    // the next piece. Only allowed once per piece.
+    fn hold_piece(&mut self) {
+        if self.hold_used {
+            return;
+        }
+        let current: u8 = self.piece.tetromino_index;
+        match self.piece_hold {
+            None => {
+                self.index_buffer.next = self.random_piece();
+                self.spawn_piece();
+            }
+            Some(held) => {
+                self.piece = self.spawn_state(held);
+                self.next_drop_time = self.time + self.get_time_to_next_drop();
+                self.piece_count += 1;
+            }
+        }
+        self.piece_hold = Some(current);
+        self.hold_used = true;
+        self.emit(Game_Event::EVENT_HOLD_USED {
+            tetromino_index: current,
+        });
+    }
+    fn shift_piece(&mut self, dir: i32) -> bool {
+        self.piece.offset_col += dir;
+        if !self.piece.check_piece_valid(self) {
+            self.piece.offset_col -= dir;
+            return false;
+        }
+        self.emit(Game_Event::EVENT_PIECE_MOVED {
+            piece: self.piece,
+            step_col: dir,
+            step_row: 0,
+        });
+        return true;
+    }
+    // Repeats the last pressed direction while it is held, after the DAS
+    // delay. A blocked piece stays charged and moves as soon as it can.
+    fn auto_shift(&mut self, input: &Input_State) {
+        let held: bool =
+            (self.shift_dir < 0 && input.left > 0) || (self.shift_dir > 0 && input.right > 0);
+        if !held {
+            self.shift_dir = 0;
+            return;
+        }
+        while self.time >= self.next_shift_time {
+            if !self.shift_piece(self.shift_dir) {
+                self.next_shift_time = self.time;
+                return;
+            }
+            if self.arr > 0.0 {
+                self.next_shift_time += self.arr;
+            }
+        }
+    }
+    // The piece turned clockwise by `turns` quarter turns and moved by the
+    // first of its kicks that lets it fit, or None if it doesn't fit.
+    pub fn rotated(&self, piece: &Piece_State, turns: i32) -> Option<Piece_State> {
+        let mut turned: Piece_State = *piece;
+        turned.rotation = (piece.rotation + turns) % 4;
+        if turned.check_piece_valid(self) {
+            return Some(turned);
+        }
+        for (col, row) in self.pieces.kicks(piece.tetromino_index).iter() {
+            let mut kicked: Piece_State = turned;
+            kicked.offset_col += col;
+            kicked.offset_row += row;
+            if kicked.check_piece_valid(self) {
+                return Some(kicked);
+            }
+        }
+        return None;
+    }
+    fn rotate_piece(&mut self, turns: i32) {
+        if !self.piece.check_piece_valid(self) {
+            return;
+        }
+        let Some(piece) = self.rotated(&self.piece, turns) else {
+            return;
+        };
+        self.piece = piece;
+        self.emit(Game_Event::EVENT_PIECE_ROTATED { piece: self.piece });
     }
     fn soft_drop(&mut self) -> bool {
         self.piece.offset_row += 1;
-        if !self
-            .piece
-            .check_piece_valid(&self, WIDTH as i32, HEIGHT as i32)
-        {
+        if !self.piece.check_piece_valid(self) {
             self.piece.offset_row -= 1;
             self.merge_piece();
             self.spawn_piece();
             return false;
         }
         self.next_drop_time = self.time + self.get_time_to_next_drop();
+        self.emit(Game_Event::EVENT_PIECE_MOVED {
+            piece: self.piece,
+            step_col: 0,
+            step_row: 1,
+        });
         return true;
     }
     fn hard_drop(&mut self) {
+        let mut piece: Piece_State = self.piece;
+        while piece.check_piece_valid(self) {
+            piece.move_down();
+        }
+        piece.move_up();
+        self.emit(Game_Event::EVENT_HARD_DROP {
+            piece: piece,
+            distance: piece.offset_row - self.piece.offset_row,
+        });
         while self.soft_drop() {}
     }
     fn compute_points(&self, line_count: i32) -> i32 {
@@ -263,7 +728,7 @@ impl Game_State {
             1 => return 40 * (self.level + 1),
             2 => return 100 * (self.level + 1),
             3 => return 300 * (self.level + 1),
-            4 => return 1200 * (self.level + 1),
+            4.. => return 1200 * (self.level + 1),
             _ => {}
         }
         return 0;
@@ -287,12 +752,54 @@ impl Game_State {
             self.start_level -= 1;
         }
         if input.da > 0 {
-            self.board.fill(0);
-            self.level = self.start_level;
-            self.line_count = 0;
-            self.points = 0;
-            self.spawn_piece();
-            self.phase = Game_Phase::GAME_PHASE_PLAY;
+            self.start();
+        }
+    }
+    // Every game draws its own seed, so it can be replayed from the seed and
+    // the inputs alone.
+    pub fn start(&mut self) {
+        let seed: u64 = self.rng.random();
+        self.start_seeded(seed);
+    }
+    pub fn start_seeded(&mut self, seed: u64) {
+        self.game_seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+        self.index_buffer.current = self.random_piece();
+        self.index_buffer.next = self.random_piece();
+        self.board.cells.fill(0);
+        self.level = self.start_level;
+        self.line_count = 0;
+        self.points = 0;
+        self.last_locked = None;
+        self.piece_hold = None;
+        self.finished = false;
+        self.shift_dir = 0;
+        self.start_time = self.time;
+        self.emit(Game_Event::EVENT_GAME_START);
+        self.spawn_piece();
+        self.phase = Game_Phase::GAME_PHASE_PLAY;
+    }
+    fn end_game(&mut self, finished: bool) {
+        self.finished = finished;
+        self.end_time = self.time;
+        self.phase = Game_Phase::GAME_PHASE_GAMEOVER;
+        self.emit(Game_Event::EVENT_GAME_OVER { finished: finished });
+    }
+    // Ends a game from outside, as when a match is decided. The game stops
+    // the way a top out does, and one that is already over is left alone.
+    pub fn stop(&mut self) {
+        if self.phase != Game_Phase::GAME_PHASE_GAMEOVER {
+            self.end_game(false);
+        }
+    }
+    fn check_goal(&mut self) {
+        let reached: bool = match self.mode {
+            Game_Mode::MODE_MARATHON => false,
+            Game_Mode::MODE_SPRINT => self.line_count >= SPRINT_LINES,
+            Game_Mode::MODE_ULTRA => self.time - self.start_time >= ULTRA_TIME,
+        };
+        if reached {
+            self.end_game(true);
         }
     }
     fn update_game_gameover(&mut self, input: &Input_State) {
@@ -302,69 +809,97 @@ impl Game_State {
     }
     fn update_game_line(&mut self) {
         if self.time >= self.highlight_end_time {
-            self.clear_lines(WIDTH as i32, HEIGHT as i32);
+            let mut rows: u64 = 0;
+            for (row, line) in self.lines.iter().enumerate() {
+                if *line != 0 {
+                    rows |= 1 << row;
+                }
+            }
+            self.clear_lines();
+            if let Some(kind) = Clear_Kind::from_line_count(self.pending_line_count) {
+                self.emit(Game_Event::EVENT_LINES_CLEARED {
+                    rows: rows,
+                    kind: kind,
+                });
+            }
             self.line_count += self.pending_line_count;
             self.points += self.compute_points(self.pending_line_count);
             let lines_for_next_level = self.get_lines_for_next_level(self.level);
             if self.line_count >= lines_for_next_level {
                 self.level += 1;
+                self.emit(Game_Event::EVENT_LEVEL_UP { level: self.level });
             }
             self.phase = Game_Phase::GAME_PHASE_PLAY;
+            if !self.piece.check_piece_valid(self) {
+                self.end_game(false);
+                return;
+            }
         }
+        self.check_goal();
     }
     fn update_game_play(&mut self, input: &Input_State) {
+        let modern: bool = self.ruleset == Game_Ruleset::RULESET_MODERN;
         if input.dleft > 0 {
-            self.piece.offset_col -= 1;
-            if !self
-                .piece
-                .check_piece_valid(self, WIDTH as i32, HEIGHT as i32)
-            {
-                self.piece.offset_col += 1;
-            }
+            self.shift_piece(-1);
+            self.shift_dir = -1;
+            self.next_shift_time = self.time + self.das;
         }
         if input.dright > 0 {
-            self.piece.offset_col += 1;
-            if !self
-                .piece
-                .check_piece_valid(self, WIDTH as i32, HEIGHT as i32)
-            {
-                self.piece.offset_col -= 1;
-            }
+            self.shift_piece(1);
+            self.shift_dir = 1;
+            self.next_shift_time = self.time + self.das;
         }
-        if input.dup > 0
-            && self
-                .piece
-                .check_piece_valid(self, WIDTH as i32, HEIGHT as i32)
-        {
-            self.piece.rotation = (self.piece.rotation + 1) % 4;
-            if !self
-                .piece
-                .check_piece_valid(self, WIDTH as i32, HEIGHT as i32)
-            {
-                self.piece.rotation = (self.piece.rotation + 3) % 4;
-            }
+        self.auto_shift(input);
+        if input.dup > 0 {
+            self.rotate_piece(1);
+        }
+        if input.dccw > 0 {
+            self.rotate_piece(3);
+        }
+        if input.dflip > 0 && modern {
+            self.rotate_piece(2);
+        }
+        if input.dhold > 0 && modern {
+            self.hold_piece();
         }
-        if self
-            .piece
-            .check_piece_valid(self, WIDTH as i32, HEIGHT as i32)
-        {}
+        if self.piece.check_piece_valid(self) {}
         if input.ddown > 0 {
             self.soft_drop();
         }
-        if input.da > 0 {
+        if input.da > 0 && modern {
             self.hard_drop();
         }
         while self.time >= self.next_drop_time {
             self.soft_drop();
         }
-        self.pending_line_count = self.find_lines(WIDTH as i32, HEIGHT as i32);
+        self.pending_line_count = self.find_lines();
         if self.pending_line_count > 0 {
             self.phase = Game_Phase::GAME_PHASE_LINE;
-            self.highlight_end_time = self.time + 0.5;
+            self.highlight_end_time = self.time + LINE_CLEAR_TIME;
         }
+        // A piece that can't spawn tops out too, unless the lines about to
+        // be cleared make room for it.
         let game_over_row: i32 = 0;
-        if self.check_row_empty(WIDTH as i32, game_over_row) == 0 {
-            self.phase = Game_Phase::GAME_PHASE_GAMEOVER;
+        let blocked: bool =
+            self.phase == Game_Phase::GAME_PHASE_PLAY && !self.piece.check_piece_valid(self);
+        if self.check_row_empty(game_over_row) == 0 || blocked {
+            self.end_game(false);
+        } else if self.phase == Game_Phase::GAME_PHASE_PLAY {
+            self.check_goal();
+        }
+        if input.dpause > 0 && self.phase == Game_Phase::GAME_PHASE_PLAY {
+            self.pause_time = self.time;
+            self.phase = Game_Phase::GAME_PHASE_PAUSE;
+        }
+    }
+    // The clock keeps running while paused, so the game timers are pushed
+    // back by however long the pause lasted.
+    fn update_game_pause(&mut self, input: &Input_State) {
+        if input.dpause > 0 {
+            self.next_drop_time += self.time - self.pause_time;
+            self.next_shift_time += self.time - self.pause_time;
+            self.start_time += self.time - self.pause_time;
+            self.phase = Game_Phase::GAME_PHASE_PLAY;
         }
     }
     pub fn update(&mut self, input: &Input_State) {
@@ -378,6 +913,9 @@ impl Game_State {
             Game_Phase::GAME_PHASE_LINE => {
                 self.update_game_line();
             }
+            Game_Phase::GAME_PHASE_PAUSE => {
+                self.update_game_pause(input);
+            }
             Game_Phase::GAME_PHASE_GAMEOVER => {
                 self.update_game_gameover(input);
             }
@@ -385,6 +923,58 @@ impl Game_State {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Game_Action {
+    ACTION_MOVE_LEFT,
+    ACTION_MOVE_RIGHT,
+    ACTION_SOFT_DROP,
+    ACTION_HARD_DROP,
+    ACTION_ROTATE_CW,
+    ACTION_ROTATE_CCW,
+    ACTION_ROTATE_180,
+    ACTION_HOLD,
+    ACTION_PAUSE,
+}
+
+pub const GAME_ACTION_COUNT: usize = 9;
+
+pub static GAME_ACTIONS: [Game_Action; GAME_ACTION_COUNT] = [
+    Game_Action::ACTION_MOVE_LEFT,
+    Game_Action::ACTION_MOVE_RIGHT,
+    Game_Action::ACTION_SOFT_DROP,
+    Game_Action::ACTION_HARD_DROP,
+    Game_Action::ACTION_ROTATE_CW,
+    Game_Action::ACTION_ROTATE_CCW,
+    Game_Action::ACTION_ROTATE_180,
+    Game_Action::ACTION_HOLD,
+    Game_Action::ACTION_PAUSE,
+];
+
+// Names used in config files, in the order of GAME_ACTIONS.
+pub static GAME_ACTION_NAMES: [&str; GAME_ACTION_COUNT] = [
+    "move_left",
+    "move_right",
+    "soft_drop",
+    "hard_drop",
+    "rotate_cw",
+    "rotate_ccw",
+    "rotate_180",
+    "hold",
+    "pause",
+];
+
+impl Game_Action {
+    pub fn name(&self) -> &'static str {
+        return GAME_ACTION_NAMES[*self as usize];
+    }
+    pub fn from_name(name: &str) -> Option<Game_Action> {
+        return GAME_ACTION_NAMES
+            .iter()
+            .position(|n| *n == name)
+            .map(|i| GAME_ACTIONS[i]);
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct Input_State {
     pub left: u8,
@@ -392,12 +982,20 @@ pub struct Input_State {
     pub up: u8,
     pub down: u8,
     pub a: u8,
+    pub ccw: u8,
+    pub flip: u8,
+    pub hold: u8,
+    pub pause: u8,
 
     pub dleft: i8,
     pub dright: i8,
     pub dup: i8,
     pub ddown: i8,
     pub da: i8,
+    pub dccw: i8,
+    pub dflip: i8,
+    pub dhold: i8,
+    pub dpause: i8,
 }
 
 impl Input_State {
@@ -408,11 +1006,167 @@ impl Input_State {
             up: 0,
             down: 0,
             a: 0,
+            ccw: 0,
+            flip: 0,
+            hold: 0,
+            pause: 0,
             dleft: 0,
             dright: 0,
             dup: 0,
             ddown: 0,
             da: 0,
+            dccw: 0,
+            dflip: 0,
+            dhold: 0,
+            dpause: 0,
+        }
+    }
+    pub fn set_held(&mut self, action: Game_Action, held: bool) {
+        let value: u8 = held as u8;
+        match action {
+            Game_Action::ACTION_MOVE_LEFT => self.left = value,
+            Game_Action::ACTION_MOVE_RIGHT => self.right = value,
+            Game_Action::ACTION_SOFT_DROP => self.down = value,
+            Game_Action::ACTION_HARD_DROP => self.a = value,
+            Game_Action::ACTION_ROTATE_CW => self.up = value,
+            Game_Action::ACTION_ROTATE_CCW => self.ccw = value,
+            Game_Action::ACTION_ROTATE_180 => self.flip = value,
+            Game_Action::ACTION_HOLD => self.hold = value,
+            Game_Action::ACTION_PAUSE => self.pause = value,
+        }
+    }
+    pub fn set_edge(&mut self, action: Game_Action, edge: i8) {
+        match action {
+            Game_Action::ACTION_MOVE_LEFT => self.dleft = edge,
+            Game_Action::ACTION_MOVE_RIGHT => self.dright = edge,
+            Game_Action::ACTION_SOFT_DROP => self.ddown = edge,
+            Game_Action::ACTION_HARD_DROP => self.da = edge,
+            Game_Action::ACTION_ROTATE_CW => self.dup = edge,
+            Game_Action::ACTION_ROTATE_CCW => self.dccw = edge,
+            Game_Action::ACTION_ROTATE_180 => self.dflip = edge,
+            Game_Action::ACTION_HOLD => self.dhold = edge,
+            Game_Action::ACTION_PAUSE => self.dpause = edge,
+        }
+    }
+    pub fn get_held(&self, action: Game_Action) -> bool {
+        match action {
+            Game_Action::ACTION_MOVE_LEFT => return self.left > 0,
+            Game_Action::ACTION_MOVE_RIGHT => return self.right > 0,
+            Game_Action::ACTION_SOFT_DROP => return self.down > 0,
+            Game_Action::ACTION_HARD_DROP => return self.a > 0,
+            Game_Action::ACTION_ROTATE_CW => return self.up > 0,
+            Game_Action::ACTION_ROTATE_CCW => return self.ccw > 0,
+            Game_Action::ACTION_ROTATE_180 => return self.flip > 0,
+            Game_Action::ACTION_HOLD => return self.hold > 0,
+            Game_Action::ACTION_PAUSE => return self.pause > 0,
+        }
+    }
+    pub fn get_edge(&self, action: Game_Action) -> i8 {
+        match action {
+            Game_Action::ACTION_MOVE_LEFT => return self.dleft,
+            Game_Action::ACTION_MOVE_RIGHT => return self.dright,
+            Game_Action::ACTION_SOFT_DROP => return self.ddown,
+            Game_Action::ACTION_HARD_DROP => return self.da,
+            Game_Action::ACTION_ROTATE_CW => return self.dup,
+            Game_Action::ACTION_ROTATE_CCW => return self.dccw,
+            Game_Action::ACTION_ROTATE_180 => return self.dflip,
+            Game_Action::ACTION_HOLD => return self.dhold,
+            Game_Action::ACTION_PAUSE => return self.dpause,
+        }
+    }
+    // Fills in the d* fields from the previous frame's held buttons.
+    pub fn update_edges(&mut self, prev: &Input_State) {
+        self.dleft = self.left as i8 - prev.left as i8;
+        self.dright = self.right as i8 - prev.right as i8;
+        self.dup = self.up as i8 - prev.up as i8;
+        self.ddown = self.down as i8 - prev.down as i8;
+        self.da = self.a as i8 - prev.a as i8;
+        self.dccw = self.ccw as i8 - prev.ccw as i8;
+        self.dflip = self.flip as i8 - prev.flip as i8;
+        self.dhold = self.hold as i8 - prev.hold as i8;
+        self.dpause = self.pause as i8 - prev.pause as i8;
+    }
+}
+
+// A press or release of an action, at a time in seconds on the game clock.
+#[derive(Clone, Copy)]
+pub struct Input_Event {
+    pub action: Game_Action,
+    pub pressed: bool,
+    pub time: f32,
+}
+
+// Presses and releases from every device of one player, kept in the order
+// they happened. Several keys or buttons can hold the same action, and it
+// is only released when the last of them is.
+pub struct Input_Queue {
+    events: VecDeque<Input_Event>,
+    hold_counts: [u32; GAME_ACTION_COUNT],
+    held: Input_State,
+}
+
+impl Input_Queue {
+    pub fn new() -> Self {
+        Input_Queue {
+            events: VecDeque::new(),
+            hold_counts: [0; GAME_ACTION_COUNT],
+            held: Input_State::new(),
+        }
+    }
+    pub fn push(&mut self, event: Input_Event) {
+        self.events.push_back(event);
+    }
+    // Held buttons with no edges, for updates between events.
+    pub fn held(&self) -> Input_State {
+        return self.held;
+    }
+    // Takes the oldest event no later than `time` and returns its time with
+    // the input it produces: the buttons held after it and its own edge.
+    pub fn pop_event(&mut self, time: f32) -> Option<(f32, Input_State)> {
+        if self.events.front()?.time > time {
+            return None;
+        }
+        let event: Input_Event = self.events.pop_front().unwrap();
+        let count: &mut u32 = &mut self.hold_counts[event.action as usize];
+        let was_held: bool = *count > 0;
+        if event.pressed {
+            *count += 1;
+        } else {
+            *count = count.saturating_sub(1);
+        }
+        let is_held: bool = *count > 0;
+        self.held.set_held(event.action, is_held);
+        let mut input: Input_State = self.held;
+        input.set_edge(event.action, is_held as i8 - was_held as i8);
+        return Some((event.time, input));
+    }
+}
+
+impl Game_State {
+    // Runs one update per queued event at the event's own time, so taps
+    // shorter than a frame and several presses in one frame all reach the
+    // game, then a last update at `time` with the buttons still held.
+    // `before_update` sees the game and input before every update, and
+    // `after_update` sees them after it with the events that update queued.
+    pub fn update_queued(
+        &mut self,
+        queue: &mut Input_Queue,
+        time: f32,
+        before_update: &mut dyn FnMut(&Game_State, &Input_State),
+        after_update: &mut dyn FnMut(&Game_State, &Input_State, &[Game_Event]),
+    ) {
+        while let Some((event_time, input)) = queue.pop_event(time) {
+            self.set_time(event_time.max(self.time));
+            before_update(self, &input);
+            let first: usize = self.events.len();
+            self.update(&input);
+            after_update(self, &input, &self.events[first..]);
         }
+        self.set_time(time);
+        let held: Input_State = queue.held();
+        before_update(self, &held);
+        let first: usize = self.events.len();
+        self.update(&held);
+        after_update(self, &held, &self.events[first..]);
     }
 }
@@ -0,0 +1,187 @@
+use sdl2::GameControllerSubsystem;
+use sdl2::controller::Axis;
+use sdl2::controller::Button;
+use sdl2::controller::GameController;
+use sdl2::event::Event;
+use tetris::game::*;
+
+// Stick deflection, out of 32767, below which the stick counts as centered.
+pub const STICK_DEAD_ZONE: i16 = 8000;
+
+static BUTTON_ACTIONS: [(Button, Game_Action); 10] = [
+    (Button::DPadLeft, Game_Action::ACTION_MOVE_LEFT),
+    (Button::DPadRight, Game_Action::ACTION_MOVE_RIGHT),
+    (Button::DPadDown, Game_Action::ACTION_SOFT_DROP),
+    (Button::DPadUp, Game_Action::ACTION_HARD_DROP),
+    (Button::A, Game_Action::ACTION_ROTATE_CW),
+    (Button::B, Game_Action::ACTION_ROTATE_CCW),
+    (Button::Y, Game_Action::ACTION_ROTATE_180),
+    (Button::LeftShoulder, Game_Action::ACTION_HOLD),
+    (Button::RightShoulder, Game_Action::ACTION_HOLD),
+    (Button::Start, Game_Action::ACTION_PAUSE),
+];
+
+// Left stick directions, in the order of Gamepad::stick.
+static STICK_ACTIONS: [Game_Action; 3] = [
+    Game_Action::ACTION_MOVE_LEFT,
+    Game_Action::ACTION_MOVE_RIGHT,
+    Game_Action::ACTION_SOFT_DROP,
+];
+
+pub fn event_time(timestamp: u32) -> f32 {
+    return timestamp as f32 / 1000.0;
+}
+
+struct Gamepad {
+    controller: GameController,
+    stick: [bool; 3],
+    // Actions this controller is holding, released if it is unplugged.
+    held: Vec<Game_Action>,
+}
+
+impl Gamepad {
+    fn send(
+        &mut self,
+        queue: Option<&mut Input_Queue>,
+        action: Game_Action,
+        pressed: bool,
+        time: f32,
+    ) {
+        if pressed {
+            self.held.push(action);
+        } else if let Some(i) = self.held.iter().position(|a| *a == action) {
+            self.held.remove(i);
+        } else {
+            return;
+        }
+        if let Some(queue) = queue {
+            queue.push(Input_Event {
+                action: action,
+                pressed: pressed,
+                time: time,
+            });
+        }
+    }
+    fn move_stick(
+        &mut self,
+        queue: Option<&mut Input_Queue>,
+        direction: usize,
+        pushed: bool,
+        time: f32,
+    ) {
+        if self.stick[direction] == pushed {
+            return;
+        }
+        self.stick[direction] = pushed;
+        self.send(queue, STICK_ACTIONS[direction], pushed, time);
+    }
+}
+
+// Connected controllers by player slot. A controller that is plugged in
+// takes the first free slot and keeps it until it is unplugged, so the other
+// players don't change boards. Presses go to the queue of the same index.
+pub struct Gamepads {
+    subsystem: GameControllerSubsystem,
+    slots: Vec<Option<Gamepad>>,
+}
+
+impl Gamepads {
+    pub fn new(subsystem: GameControllerSubsystem) -> Self {
+        Gamepads {
+            subsystem: subsystem,
+            slots: Vec::new(),
+        }
+    }
+    fn find_slot(&self, instance_id: u32) -> Option<usize> {
+        return self.slots.iter().position(|slot| {
+            slot.as_ref().map(|pad| pad.controller.instance_id()) == Some(instance_id)
+        });
+    }
+    // SDL also sends ControllerDeviceAdded for controllers that were already
+    // connected at startup.
+    pub fn handle_event(&mut self, event: &Event, queues: &mut [Input_Queue]) {
+        match event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                let controller: GameController = match self.subsystem.open(*which) {
+                    Ok(controller) => controller,
+                    Err(e) => {
+                        eprintln!("failed to open controller {}: {}", which, e);
+                        return;
+                    }
+                };
+                if self.find_slot(controller.instance_id()).is_some() {
+                    return;
+                }
+                let pad: Gamepad = Gamepad {
+                    controller: controller,
+                    stick: [false; 3],
+                    held: Vec::new(),
+                };
+                match self.slots.iter().position(|slot| slot.is_none()) {
+                    Some(free) => self.slots[free] = Some(pad),
+                    None => self.slots.push(Some(pad)),
+                }
+            }
+            Event::ControllerDeviceRemoved {
+                timestamp, which, ..
+            } => {
+                let Some(slot) = self.find_slot(*which) else {
+                    return;
+                };
+                let mut pad: Gamepad = self.slots[slot].take().unwrap();
+                for action in pad.held.clone() {
+                    pad.send(queues.get_mut(slot), action, false, event_time(*timestamp));
+                }
+            }
+            Event::ControllerButtonDown {
+                timestamp,
+                which,
+                button,
+            }
+            | Event::ControllerButtonUp {
+                timestamp,
+                which,
+                button,
+            } => {
+                let Some(slot) = self.find_slot(*which) else {
+                    return;
+                };
+                let pressed: bool = matches!(event, Event::ControllerButtonDown { .. });
+                let pad: &mut Gamepad = self.slots[slot].as_mut().unwrap();
+                for (mapped, action) in BUTTON_ACTIONS.iter() {
+                    if mapped == button {
+                        pad.send(
+                            queues.get_mut(slot),
+                            *action,
+                            pressed,
+                            event_time(*timestamp),
+                        );
+                    }
+                }
+            }
+            Event::ControllerAxisMotion {
+                timestamp,
+                which,
+                axis,
+                value,
+            } => {
+                let Some(slot) = self.find_slot(*which) else {
+                    return;
+                };
+                let pad: &mut Gamepad = self.slots[slot].as_mut().unwrap();
+                let time: f32 = event_time(*timestamp);
+                match axis {
+                    Axis::LeftX => {
+                        pad.move_stick(queues.get_mut(slot), 0, *value < -STICK_DEAD_ZONE, time);
+                        pad.move_stick(queues.get_mut(slot), 1, *value > STICK_DEAD_ZONE, time);
+                    }
+                    Axis::LeftY => {
+                        pad.move_stick(queues.get_mut(slot), 2, *value > STICK_DEAD_ZONE, time);
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,243 @@
+// Local high score tables, one per mode and ruleset, kept in a single text
+// file with one entry per line. Every line carries a checksum, so a damaged
+// line is dropped on load without losing the rest of the table.
+use super::config::*;
+use super::game::*;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+pub const HIGH_SCORES_FILE: &str = "highscores.txt";
+pub const MAX_HIGH_SCORES: usize = 10;
+pub const MAX_NAME_LENGTH: usize = 12;
+
+#[derive(Clone)]
+pub struct Score_Entry {
+    pub mode: Game_Mode,
+    pub ruleset: Game_Ruleset,
+    pub name: String,
+    pub date: String,
+    pub points: i32,
+    pub lines: i32,
+    pub level: i32,
+    pub time_ms: u32,
+    // Replay file name in the replay directory, empty if there is none.
+    pub replay: String,
+}
+
+// FNV-1a, enough to notice a damaged line.
+fn checksum(text: &str) -> u32 {
+    let mut hash: u32 = 0x811C9DC5;
+    for byte in text.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    return hash;
+}
+
+// Names are kept to letters, digits and spaces so they can't break the file.
+pub fn clean_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == ' ')
+        .take(MAX_NAME_LENGTH)
+        .collect();
+    return cleaned.trim().to_uppercase();
+}
+
+// Days since 1970-01-01 to a civil date, after Howard Hinnant's
+// civil_from_days.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z: i64 = days + 719468;
+    let era: i64 = z.div_euclid(146097);
+    let doe: i64 = z - era * 146097;
+    let yoe: i64 = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy: i64 = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp: i64 = (5 * doy + 2) / 153;
+    let day: u32 = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month: u32 = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year: i64 = yoe + era * 400 + (month <= 2) as i64;
+    return (year, month, day);
+}
+
+// Today's date in UTC as YYYY-MM-DD.
+pub fn today() -> String {
+    let seconds: u64 = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    return format!("{:04}-{:02}-{:02}", year, month, day);
+}
+
+impl Score_Entry {
+    pub fn from_game(game: &Game_State, name: &str, replay: &str) -> Self {
+        Score_Entry {
+            mode: game.mode,
+            ruleset: game.ruleset,
+            name: clean_name(name),
+            date: today(),
+            points: game.points,
+            lines: game.line_count,
+            level: game.level,
+            time_ms: (game.get_play_time() * 1000.0).round() as u32,
+            replay: replay.to_string(),
+        }
+    }
+    fn to_line(&self) -> String {
+        let fields: String = format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.mode.name(),
+            self.ruleset.name(),
+            self.name,
+            self.date,
+            self.points,
+            self.lines,
+            self.level,
+            self.time_ms,
+            self.replay
+        );
+        return format!("{}\t{:08x}", fields, checksum(&fields));
+    }
+    fn from_line(line: &str) -> Option<Self> {
+        let (fields, sum) = line.rsplit_once('\t')?;
+        if u32::from_str_radix(sum, 16).ok()? != checksum(fields) {
+            return None;
+        }
+        let parts: Vec<&str> = fields.split('\t').collect();
+        if parts.len() != 9 {
+            return None;
+        }
+        return Some(Score_Entry {
+            mode: Game_Mode::from_name(parts[0])?,
+            ruleset: Game_Ruleset::from_name(parts[1])?,
+            name: clean_name(parts[2]),
+            date: parts[3].to_string(),
+            points: parts[4].parse().ok()?,
+            lines: parts[5].parse().ok()?,
+            level: parts[6].parse().ok()?,
+            time_ms: parts[7].parse().ok()?,
+            replay: parts[8].to_string(),
+        });
+    }
+    // Sprint is a race against the clock, every other mode is ranked by
+    // score.
+    pub fn ranks_above(&self, other: &Score_Entry) -> bool {
+        if self.mode == Game_Mode::MODE_SPRINT {
+            return self.time_ms < other.time_ms;
+        }
+        return self.points > other.points;
+    }
+}
+
+// Only finished sprints count, since a sprint that tops out has no time.
+// Games on other board sizes or with other piece sets aren't comparable and
+// stay off the tables.
+pub fn is_eligible(game: &Game_State) -> bool {
+    if game.board_size() != DEFAULT_BOARD_SIZE || !game.pieces().is_standard() {
+        return false;
+    }
+    if game.mode == Game_Mode::MODE_SPRINT {
+        return game.finished;
+    }
+    return game.points > 0;
+}
+
+pub struct High_Scores {
+    pub entries: Vec<Score_Entry>,
+}
+
+impl High_Scores {
+    pub fn new() -> Self {
+        High_Scores {
+            entries: Vec::new(),
+        }
+    }
+    pub fn load() -> Self {
+        let mut scores: High_Scores = High_Scores::new();
+        let Ok(contents) = std::fs::read_to_string(config_path(HIGH_SCORES_FILE)) else {
+            return scores;
+        };
+        let mut damaged: usize = 0;
+        for line in contents.lines() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match Score_Entry::from_line(line) {
+                Some(entry) => scores.entries.push(entry),
+                None => damaged += 1,
+            }
+        }
+        if damaged > 0 {
+            eprintln!("{}: skipped {} damaged entries", HIGH_SCORES_FILE, damaged);
+        }
+        return scores;
+    }
+    pub fn save(&self) -> std::io::Result<()> {
+        let mut contents: String = String::from(
+            "# mode, ruleset, name, date, points, lines, level, time ms, replay, checksum\n",
+        );
+        for entry in self.entries.iter() {
+            contents.push_str(&entry.to_line());
+            contents.push('\n');
+        }
+        return write_config_file(HIGH_SCORES_FILE, &contents);
+    }
+    // Indices of one table's entries, best first. Ties keep the older
+    // entry ahead.
+    fn table_indices(&self, mode: Game_Mode, ruleset: Game_Ruleset) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..self.entries.len())
+            .filter(|i| self.entries[*i].mode == mode && self.entries[*i].ruleset == ruleset)
+            .collect();
+        indices.sort_by(|a, b| {
+            let (a, b) = (&self.entries[*a], &self.entries[*b]);
+            if a.ranks_above(b) {
+                return std::cmp::Ordering::Less;
+            }
+            if b.ranks_above(a) {
+                return std::cmp::Ordering::Greater;
+            }
+            return std::cmp::Ordering::Equal;
+        });
+        return indices;
+    }
+    // Best first, at most MAX_HIGH_SCORES entries.
+    pub fn table(&self, mode: Game_Mode, ruleset: Game_Ruleset) -> Vec<&Score_Entry> {
+        return self
+            .table_indices(mode, ruleset)
+            .iter()
+            .take(MAX_HIGH_SCORES)
+            .map(|i| &self.entries[*i])
+            .collect();
+    }
+    pub fn qualifies(&self, game: &Game_State) -> bool {
+        if !is_eligible(game) {
+            return false;
+        }
+        let table: Vec<&Score_Entry> = self.table(game.mode, game.ruleset);
+        if table.len() < MAX_HIGH_SCORES {
+            return true;
+        }
+        let candidate: Score_Entry = Score_Entry::from_game(game, "", "");
+        return candidate.ranks_above(table[table.len() - 1]);
+    }
+    // Adds the entry and drops whatever falls off the bottom of its table.
+    // Returns the entry's rank, counting from 0, and the dropped entries so
+    // their replays can be removed.
+    pub fn insert(&mut self, entry: Score_Entry) -> (Option<usize>, Vec<Score_Entry>) {
+        let (mode, ruleset) = (entry.mode, entry.ruleset);
+        self.entries.push(entry);
+        let new_index: usize = self.entries.len() - 1;
+        let indices: Vec<usize> = self.table_indices(mode, ruleset);
+        let rank: Option<usize> = indices
+            .iter()
+            .position(|i| *i == new_index)
+            .filter(|rank| *rank < MAX_HIGH_SCORES);
+        let mut dropped_indices: Vec<usize> = indices.into_iter().skip(MAX_HIGH_SCORES).collect();
+        dropped_indices.sort();
+        let mut dropped: Vec<Score_Entry> = Vec::new();
+        for i in dropped_indices.into_iter().rev() {
+            dropped.push(self.entries.remove(i));
+        }
+        return (rank, dropped);
+    }
+}
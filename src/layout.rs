@@ -0,0 +1,128 @@
+// Where each board and its panels go in a window of any size. Everything is
+// measured in cells, so the layout keeps its proportions: the cell size is
+// the largest that fits the window and the boards are centered in it.
+//
+// Each player gets a column of cells: the hold piece and the stats on the
+// left, the board, and the next piece on the right. Its size follows the
+// size of the board.
+use super::game::*;
+
+pub const PANEL_WIDTH: i32 = 5;
+// Rows the hold piece and the stats need, so short boards keep room for
+// them.
+pub const PANEL_HEIGHT: i32 = 14;
+// Space between two players' columns.
+pub const PLAYER_GAP: i32 = 1;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Layout_Rect {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+impl Layout_Rect {
+    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+        Layout_Rect {
+            x: x,
+            y: y,
+            w: w,
+            h: h,
+        }
+    }
+    pub fn center_x(&self) -> i32 {
+        return self.x + self.w / 2;
+    }
+    pub fn center_y(&self) -> i32 {
+        return self.y + self.h / 2;
+    }
+    pub fn right(&self) -> i32 {
+        return self.x + self.w;
+    }
+    pub fn bottom(&self) -> i32 {
+        return self.y + self.h;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Board_Layout {
+    // Cell size in pixels.
+    pub cell: i32,
+    // The visible rows of the board.
+    pub board: Layout_Rect,
+    pub hold: Layout_Rect,
+    pub stats: Layout_Rect,
+    pub next: Layout_Rect,
+}
+
+impl Board_Layout {
+    // The same layout moved by dx, dy pixels.
+    pub fn offset(&self, dx: i32, dy: i32) -> Board_Layout {
+        let shift = |rect: Layout_Rect| Layout_Rect::new(rect.x + dx, rect.y + dy, rect.w, rect.h);
+        return Board_Layout {
+            cell: self.cell,
+            board: shift(self.board),
+            hold: shift(self.hold),
+            stats: shift(self.stats),
+            next: shift(self.next),
+        };
+    }
+}
+
+fn player_width(board: Board_Size) -> i32 {
+    return PANEL_WIDTH * 2 + board.width;
+}
+
+// Every player's column with the gaps between them, by the board's rows
+// or the panels' plus a cell of margin above and below.
+fn grid_cells(players: usize, board: Board_Size) -> (i32, i32) {
+    let players: i32 = players.max(1) as i32;
+    return (
+        players * player_width(board) + (players - 1) * PLAYER_GAP,
+        board.height.max(PANEL_HEIGHT) + 2,
+    );
+}
+
+// The window size that gives cells of `cell` pixels.
+pub fn layout_size(players: usize, cell: i32, board: Board_Size) -> (u32, u32) {
+    let (cells_w, cells_h) = grid_cells(players, board);
+    return ((cells_w * cell) as u32, (cells_h * cell) as u32);
+}
+
+pub fn compute_layout(
+    width: u32,
+    height: u32,
+    players: usize,
+    board_size: Board_Size,
+) -> Vec<Board_Layout> {
+    let (cells_w, cells_h) = grid_cells(players, board_size);
+    let cell: i32 = (width as i32 / cells_w).min(height as i32 / cells_h).max(1);
+    let origin_x: i32 = (width as i32 - cells_w * cell) / 2;
+    let origin_y: i32 = (height as i32 - cells_h * cell) / 2;
+    let mut layouts: Vec<Board_Layout> = Vec::new();
+    for i in 0..players.max(1) as i32 {
+        let left: i32 = origin_x + i * (player_width(board_size) + PLAYER_GAP) * cell;
+        let top: i32 = origin_y + cell;
+        let board: Layout_Rect = Layout_Rect::new(
+            left + PANEL_WIDTH * cell,
+            top,
+            board_size.width * cell,
+            board_size.height * cell,
+        );
+        let panel_height: i32 = board.h.max(PANEL_HEIGHT * cell);
+        layouts.push(Board_Layout {
+            cell: cell,
+            board: board,
+            hold: Layout_Rect::new(left, top, PANEL_WIDTH * cell, 4 * cell),
+            stats: Layout_Rect::new(
+                left,
+                top + 5 * cell,
+                PANEL_WIDTH * cell,
+                panel_height - 5 * cell,
+            ),
+            next: Layout_Rect::new(board.right(), top, PANEL_WIDTH * cell, 4 * cell),
+        });
+    }
+    return layouts;
+}
@@ -0,0 +1,152 @@
+use sdl2::keyboard::Scancode;
+use tetris::game::*;
+use tetris::highscores::*;
+use tetris::replay::*;
+
+fn entry_result(entry: &Score_Entry) -> String {
+    if entry.mode == Game_Mode::MODE_SPRINT {
+        return format_time(entry.time_ms as f32 / 1000.0);
+    }
+    return entry.points.to_string();
+}
+
+// One line per entry, best first, with `highlight` marked.
+pub fn table_lines(
+    scores: &High_Scores,
+    mode: Game_Mode,
+    ruleset: Game_Ruleset,
+    highlight: Option<usize>,
+    count: usize,
+    with_date: bool,
+) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for (i, entry) in scores.table(mode, ruleset).iter().take(count).enumerate() {
+        let mut line: String = format!("{}. {} {}", i + 1, entry.name, entry_result(entry));
+        if with_date {
+            line = format!("{} {}", line, entry.date);
+        }
+        if highlight == Some(i) {
+            line = format!("> {}", line);
+        }
+        lines.push(line);
+    }
+    return lines;
+}
+
+// Asks for a name after a game that made the table. Letters come from SDL
+// text input events.
+pub struct Name_Entry {
+    pub name: String,
+    pub entry: Score_Entry,
+    pub replay: Replay,
+}
+
+impl Name_Entry {
+    pub fn new(game: &Game_State, replay: Replay, last_name: &str) -> Self {
+        Name_Entry {
+            name: last_name.to_string(),
+            entry: Score_Entry::from_game(game, "", ""),
+            replay: replay,
+        }
+    }
+    pub fn text_input(&mut self, text: &str) {
+        let name: String = format!("{}{}", self.name, text);
+        self.name = name.to_uppercase().chars().take(MAX_NAME_LENGTH).collect();
+    }
+    // Returns true once the name is confirmed.
+    pub fn key_pressed(&mut self, scancode: Scancode) -> bool {
+        match scancode {
+            Scancode::Backspace => {
+                self.name.pop();
+            }
+            Scancode::Return => return !clean_name(&self.name).is_empty(),
+            _ => {}
+        }
+        return false;
+    }
+    pub fn lines(&self) -> Vec<String> {
+        return vec![
+            format!(
+                "{} - {}",
+                self.entry.mode.name().to_uppercase(),
+                self.entry.ruleset.name().to_uppercase()
+            ),
+            entry_result(&self.entry),
+            String::new(),
+            format!("NAME: {}_", self.name),
+            String::new(),
+            "ENTER: SAVE  ESC: SKIP".to_string(),
+        ];
+    }
+    // Saves the replay and the entry. Replays of entries that drop off the
+    // table are deleted. Returns the entry's rank.
+    pub fn save(&self, scores: &mut High_Scores) -> Option<usize> {
+        let replay_file: String = format!("{}-{:016x}.replay", self.entry.date, self.replay.seed);
+        let mut entry: Score_Entry = self.entry.clone();
+        entry.name = clean_name(&self.name);
+        match self.replay.save(&replay_file) {
+            Ok(()) => entry.replay = replay_file,
+            Err(e) => eprintln!("failed to save replay: {}", e),
+        }
+        let (rank, dropped) = scores.insert(entry);
+        for old in dropped.iter().filter(|e| !e.replay.is_empty()) {
+            let _ = std::fs::remove_file(replay_path(&old.replay));
+        }
+        if let Err(e) = scores.save() {
+            eprintln!("failed to save {}: {}", HIGH_SCORES_FILE, e);
+        }
+        return rank;
+    }
+}
+
+// Browses every table. Left and right pick the mode, up and down the
+// ruleset.
+pub struct Leaderboard_Screen {
+    pub mode: Game_Mode,
+    pub ruleset: Game_Ruleset,
+}
+
+impl Leaderboard_Screen {
+    pub fn new(mode: Game_Mode, ruleset: Game_Ruleset) -> Self {
+        Leaderboard_Screen {
+            mode: mode,
+            ruleset: ruleset,
+        }
+    }
+    // Returns true when the screen should close.
+    pub fn key_pressed(&mut self, scancode: Scancode) -> bool {
+        let mode_count: usize = GAME_MODES.len();
+        let ruleset_count: usize = GAME_RULESETS.len();
+        match scancode {
+            Scancode::Left => {
+                self.mode = GAME_MODES[(self.mode as usize + mode_count - 1) % mode_count]
+            }
+            Scancode::Right => self.mode = GAME_MODES[(self.mode as usize + 1) % mode_count],
+            Scancode::Up | Scancode::Down => {
+                self.ruleset = GAME_RULESETS[(self.ruleset as usize + 1) % ruleset_count]
+            }
+            Scancode::Return => return true,
+            _ => {}
+        }
+        return false;
+    }
+    pub fn lines(&self, scores: &High_Scores) -> Vec<String> {
+        let mut lines: Vec<String> = vec![
+            format!(
+                "< {} - {} >",
+                self.mode.name().to_uppercase(),
+                self.ruleset.name().to_uppercase()
+            ),
+            String::new(),
+        ];
+        let entries: Vec<String> =
+            table_lines(scores, self.mode, self.ruleset, None, MAX_HIGH_SCORES, true);
+        if entries.is_empty() {
+            lines.push("NO SCORES YET".to_string());
+        }
+        lines.extend(entries);
+        lines.push(String::new());
+        lines.push("ARROWS: TABLE  ENTER: DONE".to_string());
+        return lines;
+    }
+}
@@ -0,0 +1,22 @@
+pub mod ai;
+pub mod animation;
+pub mod audio;
+pub mod config;
+pub mod env;
+pub mod export;
+pub mod finesse;
+pub mod framebuffer;
+pub mod game;
+pub mod highscores;
+pub mod layout;
+pub mod pieces;
+pub mod render;
+pub mod replay;
+pub mod settings;
+pub mod stats;
+pub mod tbp;
+pub mod tetromino;
+pub mod theme;
+#[cfg(unix)]
+pub mod tui;
+pub mod versus;
@@ -1,73 +1,628 @@
+mod bindings;
 mod display;
-mod game;
-mod tetromino;
+mod gamepad;
+mod leaderboard;
+mod sdl_audio;
+mod settings_screen;
+mod stats_screen;
+use bindings::*;
 use display::*;
-use game::Game_State;
-use game::Input_State;
-use game::*;
+use gamepad::*;
+use leaderboard::*;
+use sdl_audio::*;
+use settings_screen::*;
+use stats_screen::*;
+use tetris::ai::*;
+use tetris::animation::*;
+use tetris::audio::*;
+use tetris::finesse::*;
+use tetris::framebuffer::*;
+use tetris::game::Game_State;
+use tetris::game::Input_State;
+use tetris::game::*;
+use tetris::highscores::*;
+use tetris::layout::*;
+use tetris::pieces::*;
+use tetris::render::*;
+use tetris::replay::*;
+use tetris::settings::*;
+use tetris::stats::*;
+use tetris::tbp::*;
+use tetris::theme::*;
+use tetris::versus::*;
 
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use sdl2::keyboard::*;
-use sdl2::pixels::Color;
+use sdl2::render::Texture;
+use sdl2::video::FullscreenType;
 use std::time::Duration;
 
+// A human plays with the controller in the given slot; slot 0 also gets
+// the keyboard.
+enum Controller {
+    CONTROLLER_HUMAN(usize),
+    CONTROLLER_AI(Ai_Player),
+    CONTROLLER_BOT(Tbp_Player),
+    CONTROLLER_REPLAY(Replay_Player),
+}
+
+struct Options {
+    ai: Option<Ai_Difficulty>,
+    bot: Option<String>,
+    versus: bool,
+    finesse: bool,
+    players: usize,
+    replay: Option<String>,
+    // Directory to save the replay's frames in, and every how many frames.
+    frames: Option<String>,
+    frame_step: u32,
+    tui: bool,
+}
+
+fn parse_options() -> Options {
+    let mut options: Options = Options {
+        ai: None,
+        bot: None,
+        versus: false,
+        finesse: false,
+        players: 1,
+        replay: None,
+        frames: None,
+        frame_step: 1,
+        tui: false,
+    };
+    let args: Vec<String> = std::env::args().collect();
+    for i in 1..args.len() {
+        if args[i] == "--ai" {
+            let difficulty: Ai_Difficulty = args
+                .get(i + 1)
+                .and_then(|name| difficulty_from_name(name))
+                .unwrap_or(AI_MEDIUM);
+            options.ai = Some(difficulty);
+        }
+        if args[i] == "--bot" {
+            options.bot = args.get(i + 1).cloned();
+        }
+        if args[i] == "--versus" {
+            options.versus = true;
+        }
+        if args[i] == "--finesse" {
+            options.finesse = true;
+        }
+        if args[i] == "--players" {
+            options.players = args.get(i + 1).and_then(|n| n.parse().ok()).unwrap_or(2);
+            options.versus = options.players > 1;
+        }
+        if args[i] == "--replay" {
+            options.replay = args.get(i + 1).cloned();
+        }
+        if args[i] == "--frames" {
+            options.frames = args.get(i + 1).cloned();
+        }
+        if args[i] == "--frame-step" {
+            options.frame_step = args.get(i + 1).and_then(|n| n.parse().ok()).unwrap_or(1);
+        }
+        if args[i] == "--tui" {
+            options.tui = true;
+        }
+    }
+    return options;
+}
+
+// A replay that can't be read ends the program before anything opens.
+fn load_replay(path: &str) -> Replay {
+    match Replay::load(std::path::Path::new(path)) {
+        Ok(replay) => return replay,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// The human plays the first board in a versus match; the other board and
+// any solo autoplayer are driven by the AI or an external TBP bot. Local
+// multiplayer gives every board to a human.
+fn create_controllers(options: &Options) -> Vec<Controller> {
+    if let Some(path) = &options.replay {
+        let replay: Replay = load_replay(path);
+        return vec![Controller::CONTROLLER_REPLAY(Replay_Player::new(replay))];
+    }
+    if options.players > 1 {
+        return (0..options.players)
+            .map(Controller::CONTROLLER_HUMAN)
+            .collect();
+    }
+    let mut opponent: Controller = Controller::CONTROLLER_HUMAN(0);
+    if let Some(command) = &options.bot {
+        let bot: Tbp_Bot = match Tbp_Bot::launch(command) {
+            Ok(bot) => bot,
+            Err(e) => {
+                eprintln!("{}: {}", command, e);
+                std::process::exit(1);
+            }
+        };
+        opponent = Controller::CONTROLLER_BOT(Tbp_Player::new(bot));
+    } else if let Some(difficulty) = options.ai {
+        opponent = Controller::CONTROLLER_AI(Ai_Player::new(DEFAULT_WEIGHTS, difficulty));
+    } else if options.versus {
+        opponent = Controller::CONTROLLER_AI(Ai_Player::new(DEFAULT_WEIGHTS, AI_MEDIUM));
+    }
+    if options.versus {
+        return vec![Controller::CONTROLLER_HUMAN(0), opponent];
+    }
+    return vec![opponent];
+}
+
 fn main() {
+    // Clips of replays are drawn in memory, without a window.
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("render-replay") {
+        if let Err(e) = tetris::export::run_render_replay(&args[2..]) {
+            eprintln!("render-replay: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    let options: Options = parse_options();
+    // The terminal frontend starts none of SDL.
+    #[cfg(unix)]
+    if options.tui {
+        if let Err(e) = tetris::tui::run_tui() {
+            eprintln!("tui: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    // Frames of a replay are drawn in memory, without a window.
+    if let (Some(path), Some(dir)) = (&options.replay, &options.frames) {
+        let replay: Replay = load_replay(path);
+        let settings: Settings = Settings::load();
+        let themes: Vec<Theme> = load_themes();
+        let view: View = View::new(&settings, &themes[theme_index(&themes, &settings.theme)]);
+        let dir: &std::path::Path = std::path::Path::new(dir);
+        match save_replay_frames(&replay, &view, settings.grid_size, dir, options.frame_step) {
+            Ok(count) => println!("saved {} frames to {}", count, dir.display()),
+            Err(e) => {
+                eprintln!("{}: {}", dir.display(), e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    let mut controllers: Vec<Controller> = create_controllers(&options);
+    let players: u32 = controllers.len() as u32;
+    let mut settings: Settings = Settings::load();
+    let themes: Vec<Theme> = load_themes();
+    let piece_sets: Vec<Piece_Set> = load_piece_sets();
+    let mut theme: usize = theme_index(&themes, &settings.theme);
+    let mut view: View = View::new(&settings, &themes[theme]);
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
 
+    // The grid size setting picks the starting window size. After that the
+    // layout follows the window, and on HiDPI screens it is computed in
+    // pixels rather than window units.
+    let (window_width, window_height) =
+        layout_size(players as usize, view.grid_size, settings.board_size);
     let window = video_subsystem
-        .window(
-            "TETRIS",
-            WIDTH as u32 * GRID_SIZE as u32,
-            HEIGHT as u32 * GRID_SIZE as u32 + 60,
-        )
+        .window("TETRIS", window_width, window_height)
         .position_centered()
+        .resizable()
+        .allow_highdpi()
         .build()
         .unwrap();
 
     let mut canvas = window.into_canvas().build().unwrap();
+    // The window is resized with the board, like with the grid size.
+    let mut window_board: Board_Size = settings.board_size;
     let ttf_context = sdl2::ttf::init().unwrap();
-
-    let font_path = "assets/Gilroy-Light.ttf";
-    let font_size = 24;
-    let font = ttf_context.load_font(font_path, font_size).unwrap();
+    // The font is reloaded when the theme or the cell size changes.
+    let mut font_theme: usize = theme;
+    let mut font_size_loaded: u16 = themes[theme].font_size;
+    let texture_creator = canvas.texture_creator();
+    let mut cache: Render_Cache = Render_Cache::new(
+        &texture_creator,
+        load_font(&ttf_context, &themes[theme], font_size_loaded),
+    );
+    let skins: Vec<Option<Texture>> = themes
+        .iter()
+        .map(|t| load_skin(&texture_creator, t))
+        .collect();
 
     let mut event_pump = sdl_context.event_pump().unwrap();
-    let mut game: Game_State = Game_State::new();
-    let mut input: Input_State = Input_State::new();
+    // The game runs without sound if there is no audio device.
+    let _audio_subsystem = sdl_context.audio();
+    let backend: Box<dyn Audio_Backend> = match Sdl_Audio::open() {
+        Ok(backend) => Box::new(backend),
+        Err(e) => {
+            eprintln!("sound disabled: {}", e);
+            Box::new(Null_Audio)
+        }
+    };
+    let mut audio: Audio_Player<Box<dyn Audio_Backend>> = Audio_Player::new(backend);
+    let seed: u64 = rand::random();
+    let mut games: Vec<Game_State> = (0..players).map(|_| Game_State::new_seeded(seed)).collect();
+    for game in games.iter_mut() {
+        settings.apply(game, &piece_sets);
+    }
+    let mut animators: Vec<Animator> = (0..players).map(|_| Animator::new()).collect();
+    let mut motions: Vec<Piece_Motion> = (0..players).map(|_| Piece_Motion::new()).collect();
+    let mut versus: Option<Versus_Match> = None;
+    if options.versus {
+        versus = Some(Versus_Match::new(players as usize, seed));
+    }
+    // Finesse is only judged for a human on the first board.
+    let mut finesse: Option<Finesse_Tracker> = None;
+    if options.finesse && matches!(controllers[0], Controller::CONTROLLER_HUMAN(_)) {
+        finesse = Some(Finesse_Tracker::new());
+    }
+    let mut bindings: Key_Bindings = Key_Bindings::load();
+    let mut rebind: Option<Rebind_Screen> = None;
+    let mut settings_screen: Option<Settings_Screen> = None;
+    // Solo human games are recorded and can make the high score tables.
+    let record_scores: bool =
+        !options.versus && matches!(controllers[0], Controller::CONTROLLER_HUMAN(_));
+    let mut high_scores: High_Scores = High_Scores::load();
+    let mut recorder: Option<Replay> = None;
+    let mut name_entry: Option<Name_Entry> = None;
+    let mut leaderboard: Option<Leaderboard_Screen> = None;
+    let mut last_name: String = String::new();
+    let mut last_rank: Option<usize> = None;
+    let mut stats: Stats_Tracker = Stats_Tracker::new(Game_Stats::load());
+    let mut stats_screen: Option<Stats_Screen> = None;
+    let mut gamepads: Gamepads = Gamepads::new(sdl_context.game_controller().unwrap());
+    let human_count: usize = controllers
+        .iter()
+        .filter(|c| matches!(c, Controller::CONTROLLER_HUMAN(_)))
+        .count()
+        .max(1);
+    let mut queues: Vec<Input_Queue> = (0..human_count).map(|_| Input_Queue::new()).collect();
+    let mut screenshot: bool = false;
     'running: loop {
-        game.set_time((sdl_context.timer().unwrap().ticks() as f32) / 1000.0);
+        let time: f32 = (sdl_context.timer().unwrap().ticks() as f32) / 1000.0;
         for event in event_pump.poll_iter() {
+            gamepads.handle_event(&event, &mut queues);
             match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
+                Event::Quit { .. } => break 'running,
+                Event::TextInput { text, .. } => {
+                    if let Some(entry) = name_entry.as_mut() {
+                        entry.text_input(&text);
+                    }
+                }
+                Event::KeyDown {
                     keycode: Some(Keycode::Escape),
                     ..
-                } => break 'running,
+                } => {
+                    if name_entry.take().is_some()
+                        || leaderboard.take().is_some()
+                        || stats_screen.take().is_some()
+                    {
+                        continue;
+                    }
+                    if let Some(screen) = settings_screen.take() {
+                        settings = screen.settings;
+                        if let Err(e) = settings.save() {
+                            eprintln!("failed to save {}: {}", SETTINGS_FILE, e);
+                        }
+                        continue;
+                    }
+                    if rebind.is_none() {
+                        break 'running;
+                    }
+                    rebind = None;
+                }
+                Event::KeyDown {
+                    timestamp,
+                    scancode: Some(scancode),
+                    repeat: false,
+                    ..
+                } => {
+                    if let Some(entry) = name_entry.as_mut() {
+                        if entry.key_pressed(scancode) {
+                            last_rank = entry.save(&mut high_scores);
+                            last_name = entry.name.clone();
+                            name_entry = None;
+                        }
+                    } else if let Some(screen) = leaderboard.as_mut() {
+                        if screen.key_pressed(scancode) {
+                            leaderboard = None;
+                        }
+                    } else if let Some(screen) = stats_screen.as_mut() {
+                        if screen.key_pressed(scancode) {
+                            stats_screen = None;
+                        }
+                    } else if let Some(screen) = rebind.as_mut() {
+                        if screen.key_pressed(scancode) {
+                            bindings = screen.bindings.clone();
+                            if let Err(e) = bindings.save() {
+                                eprintln!("failed to save {}: {}", BINDINGS_FILE, e);
+                            }
+                            rebind = None;
+                        }
+                    } else if let Some(screen) = settings_screen.as_mut() {
+                        if screen.key_pressed(scancode) {
+                            settings = screen.settings.clone();
+                            if let Err(e) = settings.save() {
+                                eprintln!("failed to save {}: {}", SETTINGS_FILE, e);
+                            }
+                            settings_screen = None;
+                        }
+                    } else if scancode == Scancode::F1
+                        && games[0].phase != Game_Phase::GAME_PHASE_PLAY
+                    {
+                        rebind = Some(Rebind_Screen::new(&bindings));
+                    } else if scancode == Scancode::F2
+                        && games[0].phase != Game_Phase::GAME_PHASE_PLAY
+                    {
+                        settings_screen = Some(Settings_Screen::new(
+                            &settings,
+                            themes.iter().map(|t| t.name.clone()).collect(),
+                            piece_sets.iter().map(|p| p.name.clone()).collect(),
+                        ));
+                    } else if scancode == Scancode::F3
+                        && games[0].phase != Game_Phase::GAME_PHASE_PLAY
+                    {
+                        leaderboard =
+                            Some(Leaderboard_Screen::new(settings.mode, settings.ruleset));
+                    } else if scancode == Scancode::F4
+                        && games[0].phase != Game_Phase::GAME_PHASE_PLAY
+                    {
+                        stats_screen = Some(Stats_Screen::new(true));
+                    } else if scancode == Scancode::F12 {
+                        screenshot = true;
+                    } else if scancode == Scancode::F11 {
+                        settings.fullscreen = !settings.fullscreen;
+                        if let Err(e) = settings.save() {
+                            eprintln!("failed to save {}: {}", SETTINGS_FILE, e);
+                        }
+                    } else if scancode == Scancode::M && bindings.actions_for(scancode).is_empty() {
+                        settings.muted = !settings.muted;
+                        if let Err(e) = settings.save() {
+                            eprintln!("failed to save {}: {}", SETTINGS_FILE, e);
+                        }
+                    } else {
+                        for action in bindings.actions_for(scancode) {
+                            queues[0].push(Input_Event {
+                                action: action,
+                                pressed: true,
+                                time: event_time(timestamp),
+                            });
+                        }
+                    }
+                }
+                Event::KeyUp {
+                    timestamp,
+                    scancode: Some(scancode),
+                    ..
+                } if rebind.is_none() && settings_screen.is_none() && name_entry.is_none() => {
+                    for action in bindings.actions_for(scancode) {
+                        queues[0].push(Input_Event {
+                            action: action,
+                            pressed: false,
+                            time: event_time(timestamp),
+                        });
+                    }
+                }
                 _ => {}
             }
         }
-        let keyboard_state = event_pump.keyboard_state();
-        let prev_input: Input_State = input;
-        input.left = keyboard_state.is_scancode_pressed(Scancode::Left) as u8;
-        input.right = keyboard_state.is_scancode_pressed(Scancode::Right) as u8;
-        input.up = keyboard_state.is_scancode_pressed(Scancode::Up) as u8;
-        input.down = keyboard_state.is_scancode_pressed(Scancode::Down) as u8;
-        input.a = keyboard_state.is_scancode_pressed(Scancode::Space) as u8;
 
-        input.dleft = input.left as i8 - prev_input.left as i8;
-        input.dright = input.right as i8 - prev_input.right as i8;
-        input.dup = input.up as i8 - prev_input.up as i8;
-        input.ddown = input.down as i8 - prev_input.down as i8;
-        input.da = input.a as i8 - prev_input.a as i8;
+        // Changes from the options screen take effect here.
+        theme = theme_index(&themes, &settings.theme);
+        let new_view: View = View::new(&settings, &themes[theme]);
+        let fullscreen: bool = canvas.window().fullscreen_state() != FullscreenType::Off;
+        if settings.fullscreen != fullscreen {
+            let state: FullscreenType = if settings.fullscreen {
+                FullscreenType::Desktop
+            } else {
+                FullscreenType::Off
+            };
+            if let Err(e) = canvas.window_mut().set_fullscreen(state) {
+                eprintln!("fullscreen: {}", e);
+                settings.fullscreen = fullscreen;
+            }
+        } else if (new_view.grid_size != view.grid_size || games[0].board_size() != window_board)
+            && !fullscreen
+        {
+            let (width, height) =
+                layout_size(players as usize, new_view.grid_size, games[0].board_size());
+            canvas.window_mut().set_size(width, height).unwrap();
+        }
+        window_board = games[0].board_size();
+        view = new_view;
+        let (output_width, output_height) = canvas.output_size().unwrap();
+        let layouts: Vec<Board_Layout> = compute_layout(
+            output_width,
+            output_height,
+            players as usize,
+            games[0].board_size(),
+        );
+        if font_size(&themes[theme], layouts[0].cell) != font_size_loaded || theme != font_theme {
+            font_theme = theme;
+            font_size_loaded = font_size(&themes[theme], layouts[0].cell);
+            cache.set_font(load_font(&ttf_context, &themes[theme], font_size_loaded));
+        }
+        audio.start_frame();
+        audio.apply_settings(&settings);
+        audio.update_music(&games[0]);
+        // A replay keeps the settings it was recorded with.
+        if options.replay.is_none() {
+            for game in games.iter_mut() {
+                settings.apply(game, &piece_sets);
+            }
+        }
 
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
+        canvas.set_draw_color(sdl_color(view.theme.background));
         canvas.clear();
 
-        game.update(&input);
-        render_game(&mut game, &mut canvas, &font);
+        // Games are frozen while the controls or options are being changed,
+        // and input from that time is dropped.
+        let mut menu: Option<(&str, Vec<String>)> = None;
+        if let Some(screen) = &rebind {
+            menu = Some(("CONTROLS", screen.lines()));
+        }
+        if let Some(screen) = &settings_screen {
+            menu = Some(("OPTIONS", screen.lines()));
+        }
+        if let Some(screen) = &leaderboard {
+            menu = Some(("HIGH SCORES", screen.lines(&high_scores)));
+        }
+        if let Some(screen) = &stats_screen {
+            menu = Some(("STATISTICS", screen.lines(&stats)));
+        }
+        if let Some(entry) = &name_entry {
+            menu = Some(("NEW HIGH SCORE", entry.lines()));
+        }
+        if let Some((title, lines)) = menu {
+            queues = (0..human_count).map(|_| Input_Queue::new()).collect();
+            let mut target: Sdl_Target = Sdl_Target {
+                canvas: &mut canvas,
+                cache: &mut cache,
+                skin: skins[theme].as_ref(),
+            };
+            render_menu(&mut target, title, &lines);
+            if std::mem::take(&mut screenshot) {
+                save_screenshot(&canvas);
+            }
+            canvas.present();
+            cache.end_frame();
+            ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+            continue;
+        }
+
+        for (i, (game, controller)) in games.iter_mut().zip(controllers.iter_mut()).enumerate() {
+            let autoplay_input: Option<Input_State> = match controller {
+                Controller::CONTROLLER_HUMAN(_) => None,
+                Controller::CONTROLLER_AI(player) => Some(player.next_input(game)),
+                Controller::CONTROLLER_BOT(player) => Some(player.next_input(game)),
+                Controller::CONTROLLER_REPLAY(player) => {
+                    player.update(game, time);
+                    continue;
+                }
+            };
+            // A solo autoplayer waits for the human to start the game.
+            if let Some(autoplay_input) = autoplay_input
+                && (game.phase == Game_Phase::GAME_PHASE_PLAY || options.versus)
+            {
+                // The human's presses during the autoplayer's game are
+                // not replayed afterwards.
+                if !options.versus {
+                    while queues[0].pop_event(time).is_some() {}
+                }
+                game.set_time(time);
+                game.update(&autoplay_input);
+                continue;
+            }
+            let slot: usize = match controller {
+                Controller::CONTROLLER_HUMAN(slot) => *slot,
+                _ => 0,
+            };
+            let mut before_update = |game: &Game_State, input: &Input_State| {
+                if i != 0 {
+                    return;
+                }
+                if record_scores {
+                    stats.record_input(game, input);
+                }
+                let playing: bool = game.phase != Game_Phase::GAME_PHASE_START
+                    && game.phase != Game_Phase::GAME_PHASE_GAMEOVER;
+                if record_scores && playing {
+                    if recorder.as_ref().map(|r| r.seed) != Some(game.game_seed) {
+                        recorder = Some(Replay::new(game));
+                    }
+                    recorder.as_mut().unwrap().record(game, input);
+                }
+            };
+            let mut after_update =
+                |game: &Game_State, input: &Input_State, events: &[Game_Event]| {
+                    if let (0, Some(tracker)) = (i, finesse.as_mut()) {
+                        tracker.handle_update(game, input, events);
+                    }
+                };
+            game.update_queued(
+                &mut queues[slot],
+                time,
+                &mut before_update,
+                &mut after_update,
+            );
+        }
+        if let Some(versus_match) = versus.as_mut() {
+            versus_match.update(&mut games);
+        }
+        // Solo human games count towards the statistics, which are shown
+        // when the game ends. Computer players make no sound.
+        for (i, game) in games.iter_mut().enumerate() {
+            let events: Vec<Game_Event> = game.drain_events();
+            let silent: bool = matches!(
+                controllers[i],
+                Controller::CONTROLLER_AI(_) | Controller::CONTROLLER_BOT(_)
+            );
+            if !silent {
+                audio.handle_events(&events);
+            }
+            animators[i].enabled = settings.animations;
+            animators[i].handle_events(&events, game);
+            animators[i].update(game);
+            motions[i].handle_events(&events, game);
+            if i == 0 && record_scores && stats.handle_events(&events, game) {
+                if let Err(e) = stats.lifetime.save() {
+                    eprintln!("failed to save {}: {}", STATS_FILE, e);
+                }
+                stats_screen = Some(Stats_Screen::new(false));
+            }
+        }
+        // A finished game asks for a name if it made the table.
+        let ended: bool = games[0].phase == Game_Phase::GAME_PHASE_GAMEOVER
+            || games[0].phase == Game_Phase::GAME_PHASE_START;
+        if ended && let Some(replay) = recorder.take() {
+            last_rank = None;
+            if high_scores.qualifies(&games[0]) {
+                name_entry = Some(Name_Entry::new(&games[0], replay, &last_name));
+            }
+        }
+        // The starting level picked on the start screen is kept for next time.
+        if games[0].phase == Game_Phase::GAME_PHASE_START
+            && games[0].start_level != settings.start_level
+        {
+            settings.start_level = games[0].start_level;
+            if let Err(e) = settings.save() {
+                eprintln!("failed to save {}: {}", SETTINGS_FILE, e);
+            }
+        }
+        let mut target: Sdl_Target = Sdl_Target {
+            canvas: &mut canvas,
+            cache: &mut cache,
+            skin: skins[theme].as_ref(),
+        };
+        for (i, game) in games.iter().enumerate() {
+            let (shake_x, shake_y) = animators[i].shake_offset(game.get_time());
+            let layout: &Board_Layout = &layouts[i].offset(
+                (shake_x * layouts[i].cell as f32) as i32,
+                (shake_y * layouts[i].cell as f32) as i32,
+            );
+            render_game(game, &mut target, &view, &motions[i], layout);
+            render_animations(&mut target, &view, &animators[i], game, layout);
+            if let (0, Some(tracker)) = (i, &finesse) {
+                render_finesse(&mut target, game, tracker, layout);
+            }
+            if i == 0 && record_scores && game.phase == Game_Phase::GAME_PHASE_GAMEOVER {
+                let lines: Vec<String> =
+                    table_lines(&high_scores, game.mode, game.ruleset, last_rank, 5, false);
+                render_score_table(&mut target, &lines, layout);
+            }
+            if let Some(versus_match) = &versus
+                && versus_match.winner == Some(i)
+            {
+                render_banner(&mut target, "WINNER", layout);
+            }
+        }
+        if std::mem::take(&mut screenshot) {
+            save_screenshot(&canvas);
+        }
         canvas.present();
+        cache.end_frame();
 
         canvas.present();
         ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
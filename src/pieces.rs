@@ -0,0 +1,395 @@
+// Piece sets: the shapes a game is played with. The seven tetrominoes are
+// the standard set, a few others are built in, and more can be added as
+// toml files in the pieces directory of the config directory, for example:
+//
+//   name = "trominoes"
+//   kicks = [[1, 0], [-1, 0]]
+//
+//   [[piece]]
+//   name = "I"
+//   shape = """
+//   ...
+//   ###
+//   ...
+//   """
+//   color = "#2D9999"
+//
+//   [[piece]]
+//   name = "V"
+//   shape = """
+//   #.
+//   ##
+//   """
+//   spawn = [0, 1]
+//   kicks = []
+//
+// A shape is a grid of '#' for filled cells and '.' for empty ones. It
+// rotates around the middle of its square box, so empty rows and columns
+// around it move the center; a grid that isn't square is padded on the
+// right and bottom. Pieces spawn centered with the top of their box at the
+// top of the hidden rows, and `spawn` moves them [right, down] from there.
+// Kicks are [right, down] offsets tried in order when a rotation is
+// blocked. The set's kicks apply to every piece without its own.
+//
+// Pieces without a color take the theme's piece colors in turn. Level
+// palettes and skins apply to every piece.
+use super::config::*;
+use super::game::*;
+use super::tetromino::*;
+use super::theme::*;
+
+pub const PIECE_DIR: &str = "pieces";
+pub const STANDARD_PIECES: &str = "tetromino";
+pub const MAX_PIECES: usize = 64;
+pub const MAX_PIECE_SIDE: i32 = 8;
+
+// Names of the standard pieces, indexed like TETROMINOS.
+pub static PIECE_NAMES: [&str; 7] = ["I", "O", "T", "S", "Z", "J", "L"];
+
+#[derive(Clone, PartialEq)]
+pub struct Piece_Shape {
+    pub name: String,
+    pub side: i32,
+    // Row by row, filled cells hold the piece index plus one, which is the
+    // value the piece leaves on the board.
+    pub cells: Vec<u8>,
+    pub color: Option<Theme_Color>,
+    pub spawn: (i32, i32),
+    pub kicks: Vec<(i32, i32)>,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Piece_Set {
+    pub name: String,
+    pub shapes: Vec<Piece_Shape>,
+}
+
+// Reads a '#' and '.' grid into a square box of `value` cells.
+fn parse_shape(text: &str, value: u8) -> Result<(i32, Vec<u8>), String> {
+    let rows: Vec<&str> = text
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .collect();
+    let side: i32 = rows
+        .iter()
+        .map(|r| r.chars().count())
+        .chain(std::iter::once(rows.len()))
+        .max()
+        .unwrap_or(0) as i32;
+    if side == 0 || side > MAX_PIECE_SIDE {
+        return Err(format!(
+            "shapes must be 1 to {} cells across",
+            MAX_PIECE_SIDE
+        ));
+    }
+    let mut cells: Vec<u8> = vec![0; (side * side) as usize];
+    for (row, text) in rows.iter().enumerate() {
+        for (col, c) in text.chars().enumerate() {
+            match c {
+                '#' => cells[row * side as usize + col] = value,
+                '.' => {}
+                _ => return Err(format!("unexpected {:?} in shape", c)),
+            }
+        }
+    }
+    if cells.iter().all(|v| *v == 0) {
+        return Err("empty shape".to_string());
+    }
+    return Ok((side, cells));
+}
+
+fn parse_offset(value: &toml::Value) -> Option<(i32, i32)> {
+    let pair: &Vec<toml::Value> = value.as_array()?;
+    if pair.len() != 2 {
+        return None;
+    }
+    return Some((pair[0].as_integer()? as i32, pair[1].as_integer()? as i32));
+}
+
+fn parse_kicks(value: &toml::Value) -> Option<Vec<(i32, i32)>> {
+    return value.as_array()?.iter().map(parse_offset).collect();
+}
+
+impl Piece_Shape {
+    fn from_text(name: &str, text: &str, index: usize) -> Piece_Shape {
+        let (side, cells) = parse_shape(text, index as u8 + 1).unwrap();
+        return Piece_Shape {
+            name: name.to_string(),
+            side: side,
+            cells: cells,
+            color: None,
+            spawn: (0, 0),
+            kicks: Vec::new(),
+        };
+    }
+}
+
+impl Piece_Set {
+    // Built in sets are written the same way as the files.
+    fn from_shapes(name: &str, shapes: &[(&str, &str)], kicks: &[(i32, i32)]) -> Piece_Set {
+        let mut set: Piece_Set = Piece_Set {
+            name: name.to_string(),
+            shapes: Vec::new(),
+        };
+        for (i, (piece_name, text)) in shapes.iter().enumerate() {
+            let mut shape: Piece_Shape = Piece_Shape::from_text(piece_name, text, i);
+            shape.kicks = kicks.to_vec();
+            set.shapes.push(shape);
+        }
+        return set;
+    }
+    pub fn from_table(table: &toml::Table) -> Result<Piece_Set, String> {
+        let name: &str = table
+            .get("name")
+            .and_then(|v| v.as_str())
+            .ok_or("missing name")?;
+        let mut kicks: Vec<(i32, i32)> = Vec::new();
+        if let Some(value) = table.get("kicks") {
+            kicks = parse_kicks(value).ok_or("bad kicks")?;
+        }
+        let pieces: &Vec<toml::Value> = table
+            .get("piece")
+            .and_then(|v| v.as_array())
+            .ok_or("missing pieces")?;
+        if pieces.is_empty() || pieces.len() > MAX_PIECES {
+            return Err(format!("sets need 1 to {} pieces", MAX_PIECES));
+        }
+        let mut set: Piece_Set = Piece_Set {
+            name: name.to_string(),
+            shapes: Vec::new(),
+        };
+        for (i, piece) in pieces.iter().enumerate() {
+            let piece: &toml::Table = piece.as_table().ok_or("bad piece")?;
+            let piece_name: String = piece
+                .get("name")
+                .and_then(|v| v.as_str())
+                .map(|n| n.to_string())
+                .unwrap_or_else(|| format!("{}", i + 1));
+            let text: &str = piece
+                .get("shape")
+                .and_then(|v| v.as_str())
+                .ok_or(format!("piece {} has no shape", piece_name))?;
+            let (side, cells) = parse_shape(text, i as u8 + 1)
+                .map_err(|e| format!("piece {}: {}", piece_name, e))?;
+            let mut shape: Piece_Shape = Piece_Shape {
+                name: piece_name,
+                side: side,
+                cells: cells,
+                color: None,
+                spawn: (0, 0),
+                kicks: kicks.clone(),
+            };
+            if let Some(color) = piece.get("color") {
+                shape.color = Some(
+                    color
+                        .as_str()
+                        .and_then(Theme_Color::parse)
+                        .ok_or(format!("piece {} has a bad color", shape.name))?,
+                );
+            }
+            if let Some(spawn) = piece.get("spawn") {
+                shape.spawn =
+                    parse_offset(spawn).ok_or(format!("piece {} has a bad spawn", shape.name))?;
+            }
+            if let Some(value) = piece.get("kicks") {
+                shape.kicks =
+                    parse_kicks(value).ok_or(format!("piece {} has bad kicks", shape.name))?;
+            }
+            set.shapes.push(shape);
+        }
+        return Ok(set);
+    }
+    pub fn len(&self) -> usize {
+        return self.shapes.len();
+    }
+    pub fn is_empty(&self) -> bool {
+        return self.shapes.is_empty();
+    }
+    pub fn shape(&self, index: u8) -> Tetromino<'_> {
+        let shape: &Piece_Shape = &self.shapes[index as usize];
+        return Tetromino {
+            data: &shape.cells,
+            side: shape.side,
+        };
+    }
+    pub fn spawn_col(&self, index: u8, width: i32) -> i32 {
+        let shape: &Piece_Shape = &self.shapes[index as usize];
+        return width / 2 - shape.side / 2 + shape.spawn.0;
+    }
+    pub fn spawn_row(&self, index: u8) -> i32 {
+        return self.shapes[index as usize].spawn.1;
+    }
+    pub fn kicks(&self, index: u8) -> &[(i32, i32)] {
+        return &self.shapes[index as usize].kicks;
+    }
+    // Plays exactly like the seven tetrominoes, whatever its colors. Only
+    // these games make the high score tables and the statistics.
+    pub fn is_standard(&self) -> bool {
+        return self.len() == TETROMINOS.len()
+            && self.shapes.iter().zip(TETROMINOS.iter()).all(|(s, t)| {
+                s.side == t.side && s.cells == t.data && s.spawn == (0, 0) && s.kicks.is_empty()
+            });
+    }
+    // Whether every piece spawns inside the board and is no wider than it
+    // in any rotation.
+    pub fn fits(&self, size: Board_Size) -> bool {
+        for index in 0..self.len() as u8 {
+            let shape: Tetromino = self.shape(index);
+            let (mut top, mut bottom, mut left, mut right) = (shape.side, -1, shape.side, -1);
+            for row in 0..shape.side {
+                for col in 0..shape.side {
+                    if shape.tetromino_get(row, col, 0) != 0 {
+                        top = top.min(row);
+                        bottom = bottom.max(row);
+                        left = left.min(col);
+                        right = right.max(col);
+                    }
+                }
+            }
+            let spawn_col: i32 = self.spawn_col(index, size.width);
+            let spawn_row: i32 = self.spawn_row(index);
+            if spawn_col + left < 0
+                || spawn_col + right >= size.width
+                || spawn_row + top < 0
+                || spawn_row + bottom >= size.rows()
+                || bottom - top >= size.width
+            {
+                return false;
+            }
+        }
+        return true;
+    }
+    // Every cell of every piece blown up to `factor` by `factor` cells.
+    pub fn scaled(&self, name: &str, factor: i32) -> Piece_Set {
+        let mut set: Piece_Set = self.clone();
+        set.name = name.to_string();
+        for shape in set.shapes.iter_mut() {
+            let side: i32 = shape.side * factor;
+            let mut cells: Vec<u8> = vec![0; (side * side) as usize];
+            for row in 0..side {
+                for col in 0..side {
+                    cells[(row * side + col) as usize] =
+                        shape.cells[(row / factor * shape.side + col / factor) as usize];
+                }
+            }
+            shape.side = side;
+            shape.cells = cells;
+        }
+        return set;
+    }
+    // Colors of a cell value on a board of this set.
+    pub fn cell_colors(&self, theme: &Theme, value: u8, level: i32) -> Cell_Colors {
+        if value != 0 && value != GARBAGE_CELL && theme.level_palettes.is_empty() {
+            let color: Option<Theme_Color> =
+                self.shapes.get(value as usize - 1).and_then(|s| s.color);
+            if let Some(color) = color {
+                return shades(color);
+            }
+        }
+        return theme.cell_colors(value, level);
+    }
+}
+
+pub fn standard_pieces() -> Piece_Set {
+    let mut set: Piece_Set = Piece_Set {
+        name: STANDARD_PIECES.to_string(),
+        shapes: Vec::new(),
+    };
+    for (name, tetromino) in PIECE_NAMES.iter().zip(TETROMINOS.iter()) {
+        set.shapes.push(Piece_Shape {
+            name: name.to_string(),
+            side: tetromino.side,
+            cells: tetromino.data.to_vec(),
+            color: None,
+            spawn: (0, 0),
+            kicks: Vec::new(),
+        });
+    }
+    return set;
+}
+
+// Pieces that don't fit their box after a rotation try a step or two
+// sideways.
+const WALL_KICKS: [(i32, i32); 4] = [(1, 0), (-1, 0), (2, 0), (-2, 0)];
+
+// The eighteen pentominoes, mirror images counted apart.
+fn pentomino_pieces() -> Piece_Set {
+    let shapes: [(&str, &str); 18] = [
+        ("F", ".##\n##.\n.#."),
+        ("F'", "##.\n.##\n.#."),
+        ("I", ".....\n.....\n#####\n.....\n....."),
+        ("L", "....\n...#\n####\n...."),
+        ("L'", "....\n#...\n####\n...."),
+        ("N", "....\n..##\n###.\n...."),
+        ("N'", "....\n##..\n.###\n...."),
+        ("P", "##.\n###\n..."),
+        ("P'", ".##\n###\n..."),
+        ("T", "###\n.#.\n.#."),
+        ("U", "#.#\n###\n..."),
+        ("V", "#..\n#..\n###"),
+        ("W", "#..\n##.\n.##"),
+        ("X", ".#.\n###\n.#."),
+        ("Y", "....\n..#.\n####\n...."),
+        ("Y'", "....\n.#..\n####\n...."),
+        ("Z", "##.\n.#.\n.##"),
+        ("Z'", ".##\n.#.\n##."),
+    ];
+    return Piece_Set::from_shapes("pentomino", &shapes, &WALL_KICKS);
+}
+
+pub fn builtin_piece_sets() -> Vec<Piece_Set> {
+    let mut big: Piece_Set = standard_pieces().scaled("big", 2);
+    for shape in big.shapes.iter_mut() {
+        shape.kicks = WALL_KICKS.to_vec();
+    }
+    return vec![
+        standard_pieces(),
+        pentomino_pieces(),
+        Piece_Set::from_shapes("monomino", &[("1", "#")], &[]),
+        big,
+    ];
+}
+
+// The built in sets followed by every readable file in the pieces
+// directory. Replays name their set, so the built in names can't be taken.
+pub fn load_piece_sets() -> Vec<Piece_Set> {
+    let mut sets: Vec<Piece_Set> = builtin_piece_sets();
+    let builtin_count: usize = sets.len();
+    let dir: std::path::PathBuf = config_path(PIECE_DIR);
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return sets;
+    };
+    let mut paths: Vec<std::path::PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "toml"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        let set: Result<Piece_Set, String> = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| text.parse::<toml::Table>().map_err(|e| e.to_string()))
+            .and_then(|table| Piece_Set::from_table(&table));
+        match set {
+            Ok(set) => match sets.iter().position(|s| s.name == set.name) {
+                Some(i) if i < builtin_count => {
+                    eprintln!("{}: {} is a built in set", path.display(), set.name);
+                }
+                Some(i) => sets[i] = set,
+                None => sets.push(set),
+            },
+            Err(e) => eprintln!("{}: {}", path.display(), e),
+        }
+    }
+    return sets;
+}
+
+// Index of the named set, or of the standard set.
+pub fn piece_set_index(sets: &[Piece_Set], name: &str) -> usize {
+    return sets.iter().position(|s| s.name == name).unwrap_or(0);
+}
+
+pub fn find_piece_set(name: &str) -> Option<Piece_Set> {
+    return load_piece_sets().into_iter().find(|s| s.name == name);
+}
@@ -0,0 +1,709 @@
+// Draws the boards, panels and menus onto a Draw_Target: the SDL window in
+// the game, or a Frame_Buffer in memory for screenshots and tests. Every
+// layout and drawing decision is made here, so all targets show the same
+// picture and only differ in how they put pixels and text on it.
+use super::animation::*;
+use super::finesse::*;
+use super::game::*;
+use super::layout::*;
+use super::pieces::*;
+use super::settings::*;
+use super::tetromino::*;
+use super::theme::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Draw_Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+pub const fn color(r: u8, g: u8, b: u8, a: u8) -> Draw_Color {
+    let result: Draw_Color = Draw_Color {
+        r: r,
+        g: g,
+        b: b,
+        a: a,
+    };
+    return result;
+}
+
+pub fn theme_color(theme_color: Theme_Color) -> Draw_Color {
+    return color(theme_color.r, theme_color.g, theme_color.b, 0xFF);
+}
+
+// The theme's font size is for cells of GRID_SIZE pixels and scales with
+// the cells.
+pub fn font_size(theme: &Theme, cell: i32) -> u16 {
+    return (theme.font_size as i32 * cell / GRID_SIZE as i32).max(6) as u16;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Cell_Look {
+    LOOK_BEVEL,
+    LOOK_FLAT,
+    LOOK_OUTLINE,
+    // Only the border, for the ghost piece.
+    LOOK_GHOST,
+}
+
+// The rectangles a cell of `size` pixels is filled with, in drawing order,
+// each with the index of its color in base, light, dark. Whatever they
+// leave uncovered is transparent.
+pub fn cell_fills(size: i32, look: Cell_Look) -> Vec<(Layout_Rect, usize)> {
+    let size: i32 = size.max(1);
+    let edge: i32 = size / 8;
+    let full: Layout_Rect = Layout_Rect::new(0, 0, size, size);
+    match look {
+        Cell_Look::LOOK_BEVEL => {
+            let mut fills: Vec<(Layout_Rect, usize)> = vec![
+                (full, 2),
+                (Layout_Rect::new(edge, 0, size - edge, size - edge), 1),
+            ];
+            if size > edge * 2 {
+                fills.push((
+                    Layout_Rect::new(edge, edge, size - edge * 2, size - edge * 2),
+                    0,
+                ));
+            }
+            return fills;
+        }
+        Cell_Look::LOOK_FLAT => return vec![(full, 0)],
+        Cell_Look::LOOK_OUTLINE => {
+            let mut fills: Vec<(Layout_Rect, usize)> = vec![(full, 2)];
+            if size > 2 {
+                fills.push((Layout_Rect::new(1, 1, size - 2, size - 2), 0));
+            }
+            return fills;
+        }
+        Cell_Look::LOOK_GHOST => {
+            return vec![
+                (Layout_Rect::new(0, 0, size, 1), 2),
+                (Layout_Rect::new(0, size - 1, size, 1), 2),
+                (Layout_Rect::new(0, 0, 1, size), 2),
+                (Layout_Rect::new(size - 1, 0, 1, size), 2),
+            ];
+        }
+    }
+}
+
+pub enum Text_Allignment {
+    TEXT_ALLIGN_LEFT,
+    TEXT_ALLIGN_CENTER,
+    TEXT_ALLIGN_RIGHT,
+}
+
+// Something the game can be drawn on. Colors with an alpha below 0xFF are
+// blended over what is already there.
+pub trait Draw_Target {
+    fn size(&self) -> (u32, u32);
+    fn fill_rects(&mut self, rects: &[Layout_Rect], color: Draw_Color);
+    // Colors are base, light and dark.
+    fn draw_cell(&mut self, rect: Layout_Rect, colors: [Draw_Color; 3], look: Cell_Look);
+    // Draws tile `value` of the theme's skin, if the target has one and the
+    // skin has that tile.
+    fn draw_skin_tile(&mut self, _rect: Layout_Rect, _value: u8) -> bool {
+        return false;
+    }
+    // Nothing is drawn outside the clip rectangle while one is set.
+    fn set_clip(&mut self, clip: Option<Layout_Rect>);
+    // Space between two lines of text.
+    fn line_height(&self) -> i32;
+    fn text_size(&mut self, text: &str) -> (i32, i32);
+    // Draws text with its top left corner at x, y.
+    fn draw_text(&mut self, text: &str, x: i32, y: i32, color: Draw_Color);
+}
+
+// How the boards are drawn, from the visual settings and the theme. Piece
+// colors can depend on the level and the piece set of the game being drawn.
+#[derive(Clone, Copy)]
+pub struct View<'a> {
+    pub grid_size: i32,
+    pub ghost: bool,
+    pub animations: bool,
+    pub smooth_motion: bool,
+    pub theme: &'a Theme,
+    pub level: i32,
+    pub pieces: Option<&'a Piece_Set>,
+}
+
+impl<'a> View<'a> {
+    pub fn new(settings: &Settings, theme: &'a Theme) -> Self {
+        View {
+            grid_size: settings.grid_size,
+            ghost: settings.ghost,
+            animations: settings.animations,
+            smooth_motion: settings.smooth_motion,
+            theme: theme,
+            level: 0,
+            pieces: None,
+        }
+    }
+    pub fn for_game<'b>(&self, game: &'b Game_State) -> View<'b>
+    where
+        'a: 'b,
+    {
+        let mut view: View<'b> = *self;
+        view.level = game.level;
+        view.pieces = Some(game.pieces());
+        return view;
+    }
+    fn cell_colors(&self, value: u8) -> Cell_Colors {
+        match self.pieces {
+            Some(pieces) => return pieces.cell_colors(self.theme, value, self.level),
+            None => return self.theme.cell_colors(value, self.level),
+        }
+    }
+}
+
+fn draw_string(
+    target: &mut dyn Draw_Target,
+    text: &str,
+    x: i32,
+    y: i32,
+    allignment: Text_Allignment,
+    color: Draw_Color,
+) {
+    if text.is_empty() {
+        return;
+    }
+    let (width, _) = target.text_size(text);
+    let x: i32 = match allignment {
+        Text_Allignment::TEXT_ALLIGN_LEFT => x,
+        Text_Allignment::TEXT_ALLIGN_CENTER => x - width / 2,
+        Text_Allignment::TEXT_ALLIGN_RIGHT => x - width,
+    };
+    target.draw_text(text, x, y, color);
+}
+
+// Draws one cell into `rect`. Outlined cells are the ghost piece.
+fn draw_cell(
+    target: &mut dyn Draw_Target,
+    view: &View,
+    rect: Layout_Rect,
+    value: u8,
+    outline: bool,
+) {
+    let colors: Cell_Colors = view.cell_colors(value);
+    let colors: [Draw_Color; 3] = [
+        theme_color(colors.base),
+        theme_color(colors.light),
+        theme_color(colors.dark),
+    ];
+    let look: Cell_Look = match (outline, view.theme.cell_style) {
+        (true, _) => Cell_Look::LOOK_GHOST,
+        (false, Cell_Style::CELL_SKIN) => {
+            if target.draw_skin_tile(rect, view.theme.skin_tile(value)) {
+                return;
+            }
+            Cell_Look::LOOK_BEVEL
+        }
+        (false, Cell_Style::CELL_FLAT) => Cell_Look::LOOK_FLAT,
+        (false, Cell_Style::CELL_OUTLINE) => Cell_Look::LOOK_OUTLINE,
+        _ => Cell_Look::LOOK_BEVEL,
+    };
+    target.draw_cell(rect, colors, look);
+}
+
+// Where a cell of the board is drawn. Rows above the visible part aren't.
+fn board_cell_rect(layout: &Board_Layout, row: i32, col: i32) -> Option<Layout_Rect> {
+    let visible_row: i32 = row - HIDDEN_ROWS;
+    if visible_row < 0 {
+        return None;
+    }
+    return Some(Layout_Rect::new(
+        layout.board.x + col * layout.cell,
+        layout.board.y + visible_row * layout.cell,
+        layout.cell,
+        layout.cell,
+    ));
+}
+
+// Draws a piece moved by `shift` pixels from its cell. Cells sliding in
+// from the hidden rows are cut off at the top of the board.
+fn draw_piece(
+    target: &mut dyn Draw_Target,
+    view: &View,
+    pieces: &Piece_Set,
+    piece: &Piece_State,
+    layout: &Board_Layout,
+    shift: (i32, i32),
+    outline: bool,
+) {
+    let tetromino: Tetromino = pieces.shape(piece.get_tetr_index());
+    let board: &Layout_Rect = &layout.board;
+    target.set_clip(Some(*board));
+    for row in 0..tetromino.side {
+        for col in 0..tetromino.side {
+            let value: u8 = tetromino.tetromino_get(row, col, piece.get_rotation());
+            let visible_row: i32 = row + piece.get_offset_row() - HIDDEN_ROWS;
+            let col: i32 = col + piece.get_offset_col();
+            // The row above the board can slide into view.
+            if value != 0 && visible_row >= -1 {
+                let rect = Layout_Rect::new(
+                    board.x + col * layout.cell + shift.0,
+                    board.y + visible_row * layout.cell + shift.1,
+                    layout.cell,
+                    layout.cell,
+                );
+                draw_cell(target, view, rect, value, outline);
+            }
+        }
+    }
+    target.set_clip(None);
+}
+
+// Draws a label at the top of a panel and a piece centered below it, at
+// three quarters of the board's cell size or smaller if it wouldn't fit. A
+// dimmed piece is drawn flat in its dark color.
+fn draw_preview(
+    target: &mut dyn Draw_Target,
+    view: &View,
+    pieces: &Piece_Set,
+    label: &str,
+    tetromino_index: Option<u8>,
+    area: &Layout_Rect,
+    dimmed: bool,
+) {
+    let text_y: i32 = area.y;
+    draw_string(
+        target,
+        label,
+        area.center_x(),
+        text_y,
+        Text_Allignment::TEXT_ALLIGN_CENTER,
+        color(0xFF, 0xFF, 0xFF, 0xFF),
+    );
+    let Some(index) = tetromino_index else {
+        return;
+    };
+    let tetromino: Tetromino = pieces.shape(index);
+    // Bounding box of the piece in its spawn rotation.
+    let mut min_row: i32 = tetromino.side;
+    let mut max_row: i32 = 0;
+    let mut min_col: i32 = tetromino.side;
+    let mut max_col: i32 = 0;
+    for row in 0..tetromino.side {
+        for col in 0..tetromino.side {
+            if tetromino.tetromino_get(row, col, 0) != 0 {
+                min_row = min_row.min(row);
+                max_row = max_row.max(row);
+                min_col = min_col.min(col);
+                max_col = max_col.max(col);
+            }
+        }
+    }
+    let top: i32 = text_y + target.line_height();
+    let cell_size: i32 = (area.w / PANEL_WIDTH * 3 / 4)
+        .min(area.w * 4 / 5 / (max_col - min_col + 1))
+        .min((area.bottom() - top) / (max_row - min_row + 1));
+    let width: i32 = (max_col - min_col + 1) * cell_size;
+    let height: i32 = (max_row - min_row + 1) * cell_size;
+    let x: i32 = area.center_x() - width / 2 - min_col * cell_size;
+    let y: i32 = top + (area.bottom() - top - height) / 2 - min_row * cell_size;
+    for row in 0..tetromino.side {
+        for col in 0..tetromino.side {
+            let value: u8 = tetromino.tetromino_get(row, col, 0);
+            if value == 0 {
+                continue;
+            }
+            let rect = Layout_Rect::new(
+                x + col * cell_size,
+                y + row * cell_size,
+                cell_size,
+                cell_size,
+            );
+            if dimmed {
+                let dark: Draw_Color = theme_color(view.cell_colors(value).dark);
+                target.draw_cell(rect, [dark, dark, dark], Cell_Look::LOOK_FLAT);
+            } else {
+                draw_cell(target, view, rect, value, false);
+            }
+        }
+    }
+}
+
+fn draw_board(target: &mut dyn Draw_Target, view: &View, game: &Game_State, layout: &Board_Layout) {
+    let board: &Layout_Rect = &layout.board;
+    let size: Board_Size = game.board_size();
+    target.fill_rects(&[*board], theme_color(view.theme.board));
+    // The grid lines go out in one call as one pixel wide rectangles.
+    if let Some(grid_color) = view.theme.grid_lines {
+        let mut lines: Vec<Layout_Rect> = Vec::new();
+        for col in 1..size.width {
+            lines.push(Layout_Rect::new(
+                board.x + col * layout.cell,
+                board.y,
+                1,
+                board.h,
+            ));
+        }
+        for row in 1..size.height {
+            lines.push(Layout_Rect::new(
+                board.x,
+                board.y + row * layout.cell,
+                board.w,
+                1,
+            ));
+        }
+        target.fill_rects(&lines, theme_color(grid_color));
+    }
+    for row in 0..size.rows() {
+        for col in 0..size.width {
+            let value: u8 = game.matrix_get(row, col);
+            if value != 0
+                && let Some(rect) = board_cell_rect(layout, row, col)
+            {
+                draw_cell(target, view, rect, value, false);
+            }
+        }
+    }
+}
+
+// Draws lines of text centered on the board, starting `first_line` lines
+// below its middle.
+fn draw_board_text(
+    target: &mut dyn Draw_Target,
+    layout: &Board_Layout,
+    lines: &[String],
+    first_line: i32,
+    color: Draw_Color,
+) {
+    let x: i32 = layout.board.center_x();
+    let mut y: i32 = layout.board.center_y() + first_line * target.line_height();
+    for line in lines {
+        draw_string(
+            target,
+            line,
+            x,
+            y,
+            Text_Allignment::TEXT_ALLIGN_CENTER,
+            color,
+        );
+        y += target.line_height();
+    }
+}
+
+pub fn render_game(
+    game: &Game_State,
+    target: &mut dyn Draw_Target,
+    view: &View,
+    motion: &Piece_Motion,
+    layout: &Board_Layout,
+) {
+    let view: &View = &view.for_game(game);
+    let highlight_color = color(0xFF, 0xFF, 0xFF, 0xFF);
+    draw_board(target, view, game, layout);
+    if game.phase == Game_Phase::GAME_PHASE_PLAY {
+        // Smooth motion draws the piece part way to where it is going. The
+        // ghost only slides sideways.
+        let mut shift: (i32, i32) = (0, 0);
+        if view.smooth_motion {
+            shift = (
+                (-motion.column_lag(game.get_time()) * layout.cell as f32) as i32,
+                (motion.row_offset(game) * layout.cell as f32) as i32,
+            );
+        }
+        draw_piece(
+            target,
+            view,
+            game.pieces(),
+            &game.piece,
+            layout,
+            shift,
+            false,
+        );
+        if view.ghost {
+            let mut piece: Piece_State = game.piece;
+            while piece.check_piece_valid(game) {
+                piece.move_down();
+            }
+            piece.move_up();
+            draw_piece(
+                target,
+                view,
+                game.pieces(),
+                &piece,
+                layout,
+                (shift.0, 0),
+                true,
+            );
+        }
+    }
+    if game.phase == Game_Phase::GAME_PHASE_LINE {
+        // With animations on, the flash narrows to nothing as the rows
+        // are removed.
+        let mut height: i32 = layout.cell;
+        if view.animations {
+            let left: f32 = (game.highlight_end_time - game.get_time()) / LINE_CLEAR_TIME;
+            height = (layout.cell as f32 * left.clamp(0.0, 1.0)) as i32;
+        }
+        let mut rows: Vec<Layout_Rect> = Vec::new();
+        for row in HIDDEN_ROWS..game.board_size().rows() {
+            if game.lines[row as usize] > 0 && height > 0 {
+                let y: i32 = layout.board.y + (row - HIDDEN_ROWS) * layout.cell;
+                rows.push(Layout_Rect::new(
+                    layout.board.x,
+                    y + (layout.cell - height) / 2,
+                    layout.board.w,
+                    height,
+                ));
+            }
+        }
+        target.fill_rects(&rows, highlight_color);
+    } else if game.phase == Game_Phase::GAME_PHASE_PAUSE {
+        draw_board_text(target, layout, &["PAUSED".to_string()], 0, highlight_color);
+    } else if game.phase == Game_Phase::GAME_PHASE_GAMEOVER {
+        let mut title: &str = "GAME OVER";
+        if game.finished {
+            title = "FINISHED";
+        }
+        draw_board_text(target, layout, &[title.to_string()], 0, highlight_color);
+    } else if game.phase == Game_Phase::GAME_PHASE_START {
+        let start_level_text = format!(
+            "{} - LEVEL {}",
+            game.mode.name().to_uppercase(),
+            game.start_level
+        );
+        let lines: [String; 4] = [
+            "PRESS START".to_string(),
+            start_level_text,
+            "F1 KEYS  F2 OPTIONS".to_string(),
+            "F3 HIGH SCORES  F4 STATS".to_string(),
+        ];
+        draw_board_text(target, layout, &lines, 0, highlight_color);
+    }
+    draw_preview(
+        target,
+        view,
+        game.pieces(),
+        "NEXT",
+        Some(game.piece_next.get_tetr_index()),
+        &layout.next,
+        false,
+    );
+    draw_preview(
+        target,
+        view,
+        game.pieces(),
+        "HOLD",
+        game.piece_hold,
+        &layout.hold,
+        game.hold_used,
+    );
+    // Timed modes show the clock instead of the level.
+    let mut level_string = ("LEVEL", format!("{}", game.level));
+    match game.mode {
+        Game_Mode::MODE_MARATHON => {}
+        Game_Mode::MODE_SPRINT => {
+            level_string = ("TIME", format_time(game.get_play_time()));
+        }
+        Game_Mode::MODE_ULTRA => {
+            let left: f32 = (ULTRA_TIME - game.get_play_time()).max(0.0);
+            level_string = ("TIME", format_time(left));
+        }
+    }
+    let mut lines_string: String = format!("{}", game.line_count);
+    if game.mode == Game_Mode::MODE_SPRINT {
+        lines_string = format!("{}/{}", game.line_count, SPRINT_LINES);
+    }
+    let stats: [(&str, String); 3] = [
+        level_string,
+        ("SCORE", format!("{}", game.points)),
+        ("LINES", lines_string),
+    ];
+    // Labels on the left of the panel with their values below them on the
+    // right.
+    let margin: i32 = layout.cell / 2;
+    let mut y: i32 = layout.stats.y;
+    for (label, value) in stats.iter() {
+        draw_string(
+            target,
+            label,
+            layout.stats.x + margin,
+            y,
+            Text_Allignment::TEXT_ALLIGN_LEFT,
+            highlight_color,
+        );
+        y += target.line_height();
+        draw_string(
+            target,
+            value,
+            layout.stats.right() - margin,
+            y,
+            Text_Allignment::TEXT_ALLIGN_RIGHT,
+            highlight_color,
+        );
+        y += target.line_height() * 3 / 2;
+    }
+}
+
+// Draws the effects running on a board over it.
+pub fn render_animations(
+    target: &mut dyn Draw_Target,
+    view: &View,
+    animator: &Animator,
+    game: &Game_State,
+    layout: &Board_Layout,
+) {
+    let view: &View = &view.for_game(game);
+    let time: f32 = game.get_time();
+    let cell: i32 = layout.cell;
+    // A fading streak above each column of a hard dropped piece.
+    for trail in animator.trails.iter() {
+        let fade: f32 = 1.0 - progress(trail.start, TRAIL_TIME, time);
+        let piece: &Piece_State = &trail.piece;
+        let tetromino: Tetromino = game.pieces().shape(piece.get_tetr_index());
+        let mut streaks: Vec<Layout_Rect> = Vec::new();
+        for col in 0..tetromino.side {
+            let top = (0..tetromino.side)
+                .find(|row| tetromino.tetromino_get(*row, col, piece.get_rotation()) != 0);
+            let Some(top) = top else {
+                continue;
+            };
+            let bottom: i32 = piece.get_offset_row() + top - HIDDEN_ROWS;
+            let start: i32 = (bottom - trail.distance).max(0);
+            if bottom > start {
+                streaks.push(Layout_Rect::new(
+                    layout.board.x + (piece.get_offset_col() + col) * cell,
+                    layout.board.y + start * cell,
+                    cell,
+                    (bottom - start) * cell,
+                ));
+            }
+        }
+        let mut trail_color: Draw_Color =
+            theme_color(view.cell_colors(piece.get_tetr_index() + 1).base);
+        trail_color.a = (fade * 112.0) as u8;
+        target.fill_rects(&streaks, trail_color);
+    }
+    // Locked pieces flash white.
+    for flash in animator.flashes.iter() {
+        let fade: f32 = 1.0 - progress(flash.start, LOCK_FLASH_TIME, time);
+        let piece: &Piece_State = &flash.piece;
+        let tetromino: Tetromino = game.pieces().shape(piece.get_tetr_index());
+        let mut cells: Vec<Layout_Rect> = Vec::new();
+        for row in 0..tetromino.side {
+            for col in 0..tetromino.side {
+                if tetromino.tetromino_get(row, col, piece.get_rotation()) != 0
+                    && let Some(rect) = board_cell_rect(
+                        layout,
+                        piece.get_offset_row() + row,
+                        piece.get_offset_col() + col,
+                    )
+                {
+                    cells.push(rect);
+                }
+            }
+        }
+        target.fill_rects(&cells, color(0xFF, 0xFF, 0xFF, (fade * 192.0) as u8));
+    }
+    let size: i32 = (cell / 4).max(2);
+    for particle in animator.particles.iter() {
+        let fade: f32 = 1.0 - progress(particle.start, PARTICLE_TIME, time);
+        let (x, y) = particle.position(time);
+        let mut particle_color: Draw_Color = theme_color(view.cell_colors(particle.value).base);
+        particle_color.a = (fade * 255.0) as u8;
+        target.fill_rects(
+            &[Layout_Rect::new(
+                layout.board.x + (x * cell as f32) as i32 - size / 2,
+                layout.board.y + (y * cell as f32) as i32 - size / 2,
+                size,
+                size,
+            )],
+            particle_color,
+        );
+    }
+    if let Some(banner) = animator.banner {
+        draw_board_text(
+            target,
+            layout,
+            &[format!("LEVEL {}", banner.level)],
+            -2,
+            color(0xFF, 0xFF, 0xFF, 0xFF),
+        );
+    }
+}
+
+// Draws a line of text centered under the middle of a board, e.g. the
+// outcome of a versus match.
+pub fn render_banner(target: &mut dyn Draw_Target, text: &str, layout: &Board_Layout) {
+    draw_board_text(
+        target,
+        layout,
+        &[text.to_string()],
+        1,
+        color(0xFF, 0xFF, 0xFF, 0xFF),
+    );
+}
+
+// Lists the high score table below the game over text.
+pub fn render_score_table(target: &mut dyn Draw_Target, lines: &[String], layout: &Board_Layout) {
+    draw_board_text(target, layout, lines, 3, color(0xFF, 0xFF, 0xFF, 0xFF));
+}
+
+pub fn render_finesse(
+    target: &mut dyn Draw_Target,
+    game: &Game_State,
+    tracker: &Finesse_Tracker,
+    layout: &Board_Layout,
+) {
+    let fault_color = color(0xE5, 0x44, 0x44, 0xFF);
+    let highlight_color = color(0xFF, 0xFF, 0xFF, 0xFF);
+    if game.phase == Game_Phase::GAME_PHASE_GAMEOVER {
+        let summary = format!(
+            "FINESSE: {} FAULTS IN {}/{}",
+            tracker.total_faults, tracker.faulted_pieces, tracker.pieces
+        );
+        draw_board_text(target, layout, &[summary], 2, highlight_color);
+        return;
+    }
+    let shown: bool = game.get_time() - tracker.last_lock_time < FINESSE_OVERLAY_TIME;
+    if shown && tracker.last_faults > 0 {
+        let margin: i32 = layout.cell / 6;
+        let fault_string = format!("FINESSE +{}", tracker.last_faults);
+        draw_string(
+            target,
+            &fault_string,
+            layout.board.right() - margin,
+            layout.board.y + margin,
+            Text_Allignment::TEXT_ALLIGN_RIGHT,
+            fault_color,
+        );
+    }
+}
+
+// Full window screen with a title and one line of text per entry.
+pub fn render_menu(target: &mut dyn Draw_Target, title: &str, lines: &[String]) {
+    let highlight_color = color(0xFF, 0xFF, 0xFF, 0xFF);
+    let (width, height) = target.size();
+    target.fill_rects(
+        &[Layout_Rect::new(0, 0, width as i32, height as i32)],
+        color(0x00, 0x00, 0x00, 0xFF),
+    );
+    let x: i32 = width as i32 / 2;
+    let mut y: i32 = target.line_height();
+    draw_string(
+        target,
+        title,
+        x,
+        y,
+        Text_Allignment::TEXT_ALLIGN_CENTER,
+        highlight_color,
+    );
+    y += target.line_height() * 2;
+    for line in lines {
+        if !line.is_empty() {
+            draw_string(
+                target,
+                line,
+                x,
+                y,
+                Text_Allignment::TEXT_ALLIGN_CENTER,
+                highlight_color,
+            );
+        }
+        y += target.line_height();
+    }
+}
@@ -0,0 +1,289 @@
+// Replays keep the seed and settings of a game and every update it was
+// given. The engine is deterministic, so playing the updates back into a new
+// game gives the same game again.
+use super::config::*;
+use super::game::*;
+use super::pieces::*;
+
+pub const REPLAY_DIR: &str = "replays";
+// Version 2 added the board size, version 3 the piece set and version 4
+// handling changes during the game. Sets are saved by name, so a replay of a
+// custom set needs the same piece file to play.
+pub const REPLAY_VERSION: u32 = 4;
+
+// Game clock times come from SDL ticks, so they are whole milliseconds and
+// survive the trip through the file exactly.
+pub fn time_to_ms(time: f32) -> u32 {
+    return (time * 1000.0).round() as u32;
+}
+
+pub fn ms_to_time(ms: u32) -> f32 {
+    return ms as f32 / 1000.0;
+}
+
+// Bit i is action i held, bit 9 + i a press and bit 18 + i a release.
+pub fn encode_input(input: &Input_State) -> u32 {
+    let mut bits: u32 = 0;
+    for (i, action) in GAME_ACTIONS.iter().enumerate() {
+        if input.get_held(*action) {
+            bits |= 1 << i;
+        }
+        if input.get_edge(*action) > 0 {
+            bits |= 1 << (GAME_ACTION_COUNT + i);
+        }
+        if input.get_edge(*action) < 0 {
+            bits |= 1 << (2 * GAME_ACTION_COUNT + i);
+        }
+    }
+    return bits;
+}
+
+pub fn decode_input(bits: u32) -> Input_State {
+    let mut input: Input_State = Input_State::new();
+    for (i, action) in GAME_ACTIONS.iter().enumerate() {
+        input.set_held(*action, bits & (1 << i) != 0);
+        if bits & (1 << (GAME_ACTION_COUNT + i)) != 0 {
+            input.set_edge(*action, 1);
+        }
+        if bits & (1 << (2 * GAME_ACTION_COUNT + i)) != 0 {
+            input.set_edge(*action, -1);
+        }
+    }
+    return input;
+}
+
+// The handling is kept with every step, since DAS and ARR can be changed
+// from the options screen while the game is paused.
+#[derive(Clone, Copy)]
+pub struct Replay_Step {
+    pub time_ms: u32,
+    pub input: Input_State,
+    pub das_ms: u32,
+    pub arr_ms: u32,
+}
+
+#[derive(Clone)]
+pub struct Replay {
+    pub seed: u64,
+    pub mode: Game_Mode,
+    pub ruleset: Game_Ruleset,
+    pub start_level: i32,
+    pub board_size: Board_Size,
+    pub pieces: Piece_Set,
+    pub das_ms: u32,
+    pub arr_ms: u32,
+    pub start_ms: u32,
+    pub steps: Vec<Replay_Step>,
+}
+
+impl Replay {
+    // Call once the game has started.
+    pub fn new(game: &Game_State) -> Self {
+        Replay {
+            seed: game.game_seed,
+            mode: game.mode,
+            ruleset: game.ruleset,
+            start_level: game.start_level,
+            board_size: game.board_size(),
+            pieces: game.pieces().clone(),
+            das_ms: time_to_ms(game.das),
+            arr_ms: time_to_ms(game.arr),
+            start_ms: time_to_ms(game.get_start_time()),
+            steps: Vec::new(),
+        }
+    }
+    // Call before every update with the game clock already set.
+    pub fn record(&mut self, game: &Game_State, input: &Input_State) {
+        self.steps.push(Replay_Step {
+            time_ms: time_to_ms(game.get_time()),
+            input: *input,
+            das_ms: time_to_ms(game.das),
+            arr_ms: time_to_ms(game.arr),
+        });
+    }
+    // A fresh game started the same way as the recorded one.
+    pub fn start_game(&self) -> Game_State {
+        let mut game: Game_State = Game_State::new_seeded(self.seed);
+        game.mode = self.mode;
+        game.ruleset = self.ruleset;
+        game.start_level = self.start_level;
+        game.set_board_size(self.board_size);
+        game.set_piece_set(self.pieces.clone());
+        game.das = ms_to_time(self.das_ms);
+        game.arr = ms_to_time(self.arr_ms);
+        game.set_time(ms_to_time(self.start_ms));
+        game.start_seeded(self.seed);
+        return game;
+    }
+    pub fn play_step(&self, game: &mut Game_State, index: usize) {
+        let step: &Replay_Step = &self.steps[index];
+        game.set_time(ms_to_time(step.time_ms));
+        game.das = ms_to_time(step.das_ms);
+        game.arr = ms_to_time(step.arr_ms);
+        game.update(&step.input);
+    }
+    pub fn play_to_end(&self) -> Game_State {
+        let mut game: Game_State = self.start_game();
+        for i in 0..self.steps.len() {
+            self.play_step(&mut game, i);
+            game.drain_events();
+        }
+        return game;
+    }
+    // A header of `key value` lines, then one line per step with the time
+    // since the previous step and the encoded input. A `handling DAS ARR`
+    // line before a step changes the handling from that step on.
+    pub fn to_text(&self) -> String {
+        let mut text: String = String::new();
+        text.push_str(&format!("tetris-replay {}\n", REPLAY_VERSION));
+        text.push_str(&format!("seed {}\n", self.seed));
+        text.push_str(&format!("mode {}\n", self.mode.name()));
+        text.push_str(&format!("ruleset {}\n", self.ruleset.name()));
+        text.push_str(&format!("start_level {}\n", self.start_level));
+        text.push_str(&format!("board {}\n", self.board_size.name()));
+        text.push_str(&format!("pieces {}\n", self.pieces.name));
+        text.push_str(&format!("das_ms {}\n", self.das_ms));
+        text.push_str(&format!("arr_ms {}\n", self.arr_ms));
+        text.push_str(&format!("start_ms {}\n", self.start_ms));
+        text.push_str("steps\n");
+        let mut prev_ms: u32 = self.start_ms;
+        let mut handling: (u32, u32) = (self.das_ms, self.arr_ms);
+        for step in self.steps.iter() {
+            if (step.das_ms, step.arr_ms) != handling {
+                handling = (step.das_ms, step.arr_ms);
+                text.push_str(&format!("handling {} {}\n", step.das_ms, step.arr_ms));
+            }
+            text.push_str(&format!(
+                "{} {:x}\n",
+                step.time_ms.saturating_sub(prev_ms),
+                encode_input(&step.input)
+            ));
+            prev_ms = step.time_ms;
+        }
+        return text;
+    }
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        let header: &str = lines.next().unwrap_or("");
+        let version: u32 = header
+            .strip_prefix("tetris-replay ")
+            .and_then(|v| v.trim().parse().ok())
+            .ok_or("not a replay file")?;
+        if version > REPLAY_VERSION {
+            return Err(format!("replay version {} is not supported", version));
+        }
+        let mut replay: Replay = Replay {
+            seed: 0,
+            mode: Game_Mode::MODE_MARATHON,
+            ruleset: Game_Ruleset::RULESET_MODERN,
+            start_level: 0,
+            board_size: DEFAULT_BOARD_SIZE,
+            pieces: standard_pieces(),
+            das_ms: 0,
+            arr_ms: 0,
+            start_ms: 0,
+            steps: Vec::new(),
+        };
+        for line in lines.by_ref() {
+            if line == "steps" {
+                break;
+            }
+            let (key, value) = line.split_once(' ').ok_or(format!("bad line: {}", line))?;
+            let bad_value = || format!("bad value for {}: {}", key, value);
+            match key {
+                "seed" => replay.seed = value.parse().map_err(|_| bad_value())?,
+                "mode" => replay.mode = Game_Mode::from_name(value).ok_or_else(bad_value)?,
+                "ruleset" => {
+                    replay.ruleset = Game_Ruleset::from_name(value).ok_or_else(bad_value)?
+                }
+                "start_level" => replay.start_level = value.parse().map_err(|_| bad_value())?,
+                "board" => {
+                    replay.board_size = Board_Size::from_name(value).ok_or_else(bad_value)?
+                }
+                "pieces" => replay.pieces = find_piece_set(value).ok_or_else(bad_value)?,
+                "das_ms" => replay.das_ms = value.parse().map_err(|_| bad_value())?,
+                "arr_ms" => replay.arr_ms = value.parse().map_err(|_| bad_value())?,
+                "start_ms" => replay.start_ms = value.parse().map_err(|_| bad_value())?,
+                _ => {}
+            }
+        }
+        let mut time_ms: u32 = replay.start_ms;
+        let mut handling: (u32, u32) = (replay.das_ms, replay.arr_ms);
+        for line in lines {
+            let bad_step = || format!("bad step: {}", line);
+            if let Some(values) = line.strip_prefix("handling ") {
+                let (das_ms, arr_ms) = values.split_once(' ').ok_or_else(bad_step)?;
+                handling = (
+                    das_ms.parse().map_err(|_| bad_step())?,
+                    arr_ms.parse().map_err(|_| bad_step())?,
+                );
+                continue;
+            }
+            let (delta, bits) = line.split_once(' ').ok_or_else(bad_step)?;
+            let delta: u32 = delta.parse().map_err(|_| bad_step())?;
+            let bits: u32 = u32::from_str_radix(bits, 16).map_err(|_| bad_step())?;
+            time_ms = time_ms.checked_add(delta).ok_or_else(bad_step)?;
+            replay.steps.push(Replay_Step {
+                time_ms: time_ms,
+                input: decode_input(bits),
+                das_ms: handling.0,
+                arr_ms: handling.1,
+            });
+        }
+        return Ok(replay);
+    }
+    // `file_name` is relative to the replay directory.
+    pub fn save(&self, file_name: &str) -> std::io::Result<()> {
+        let path: String = format!("{}/{}", REPLAY_DIR, file_name);
+        return write_config_file(&path, &self.to_text());
+    }
+    pub fn load(path: &std::path::Path) -> Result<Self, String> {
+        let text: String = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        return Replay::from_text(&text);
+    }
+}
+
+pub fn replay_path(file_name: &str) -> std::path::PathBuf {
+    return config_path(REPLAY_DIR).join(file_name);
+}
+
+// Plays a replay back in real time, starting on the first call to update.
+pub struct Replay_Player {
+    pub replay: Replay,
+    next_step: usize,
+    start_time: Option<f32>,
+}
+
+impl Replay_Player {
+    pub fn new(replay: Replay) -> Self {
+        Replay_Player {
+            replay: replay,
+            next_step: 0,
+            start_time: None,
+        }
+    }
+    pub fn is_finished(&self) -> bool {
+        return self.next_step >= self.replay.steps.len();
+    }
+    // Plays every step that is due by wall clock `time`.
+    pub fn update(&mut self, game: &mut Game_State, time: f32) {
+        let start_time: f32 = match self.start_time {
+            Some(start_time) => start_time,
+            None => {
+                *game = self.replay.start_game();
+                self.start_time = Some(time);
+                time
+            }
+        };
+        let elapsed_ms: u32 = time_to_ms(time - start_time);
+        while !self.is_finished()
+            && self.replay.steps[self.next_step]
+                .time_ms
+                .saturating_sub(self.replay.start_ms)
+                <= elapsed_ms
+        {
+            self.replay.play_step(game, self.next_step);
+            self.next_step += 1;
+        }
+    }
+}
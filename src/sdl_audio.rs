@@ -0,0 +1,173 @@
+// Audio backend on SDL_mixer. The music loops on a channel of its own, and
+// the fast track is a sped up copy of the normal one.
+use sdl2::mixer;
+use tetris::audio::*;
+use tetris::settings::MAX_VOLUME;
+
+const MUSIC_PATH: &str = "assets/korobeiniki.mp3";
+const BEEP_PATH: &str = "assets/beep.wav";
+const MUSIC_CHANNEL: mixer::Channel = mixer::Channel(0);
+const MUSIC_FAST_SPEED: f32 = 1.25;
+
+// Notes as (frequency in Hz, length in seconds) for the synthesized effects.
+fn effect_notes(effect: Sound_Effect) -> &'static [(f32, f32)] {
+    match effect {
+        Sound_Effect::SFX_MOVE => return &[(880.0, 0.02)],
+        Sound_Effect::SFX_ROTATE => return &[(1320.0, 0.03)],
+        Sound_Effect::SFX_LOCK => return &[(110.0, 0.06)],
+        Sound_Effect::SFX_LINE_CLEAR => return &[(660.0, 0.08), (880.0, 0.08)],
+        Sound_Effect::SFX_TETRIS => {
+            return &[(523.0, 0.07), (659.0, 0.07), (784.0, 0.07), (1047.0, 0.15)];
+        }
+        Sound_Effect::SFX_LEVEL_UP => return &[(784.0, 0.08), (1175.0, 0.12)],
+        Sound_Effect::SFX_GAME_OVER => {
+            return &[(392.0, 0.15), (330.0, 0.15), (262.0, 0.15), (196.0, 0.3)];
+        }
+    }
+}
+
+// Square wave notes with a falling envelope, as interleaved 16 bit samples.
+fn synthesize(notes: &[(f32, f32)], rate: i32, channels: i32) -> Vec<i16> {
+    let mut samples: Vec<i16> = Vec::new();
+    for (frequency, length) in notes.iter() {
+        let frames: usize = (length * rate as f32) as usize;
+        for i in 0..frames {
+            let phase: f32 = (i as f32 * frequency / rate as f32).fract();
+            let envelope: f32 = 1.0 - i as f32 / frames as f32;
+            let level: f32 = if phase < 0.5 { 0.25 } else { -0.25 };
+            let sample: i16 = (level * envelope * i16::MAX as f32) as i16;
+            for _ in 0..channels {
+                samples.push(sample);
+            }
+        }
+    }
+    return samples;
+}
+
+// SDL_mixer decodes a whole file into the chunk, in the format the device
+// was opened with. An empty or missing buffer gives no samples.
+fn chunk_samples(chunk: &mixer::Chunk) -> &[i16] {
+    if chunk.raw.is_null() {
+        return &[];
+    }
+    unsafe {
+        let raw: &sdl2::sys::mixer::Mix_Chunk = &*chunk.raw;
+        if raw.abuf.is_null() || raw.alen < 2 || !(raw.abuf as usize).is_multiple_of(2) {
+            return &[];
+        }
+        return std::slice::from_raw_parts(raw.abuf as *const i16, raw.alen as usize / 2);
+    }
+}
+
+// Plays the samples back `speed` times faster, which also raises the pitch.
+// Anything shorter than two frames is returned as it is.
+fn speed_up(samples: &[i16], channels: i32, speed: f32) -> Vec<i16> {
+    if channels < 1 || speed <= 0.0 {
+        return samples.to_vec();
+    }
+    let channels: usize = channels as usize;
+    let frames: usize = samples.len() / channels;
+    if frames < 2 {
+        return samples.to_vec();
+    }
+    let fast_frames: usize = (frames as f32 / speed) as usize;
+    let mut fast: Vec<i16> = Vec::with_capacity(fast_frames * channels);
+    for i in 0..fast_frames {
+        let frame: usize = ((i as f32 * speed) as usize).min(frames - 1);
+        fast.extend_from_slice(&samples[frame * channels..(frame + 1) * channels]);
+    }
+    return fast;
+}
+
+fn to_mixer_volume(volume: i32, muted: bool) -> i32 {
+    if muted {
+        return 0;
+    }
+    return volume * mixer::MAX_VOLUME / MAX_VOLUME;
+}
+
+pub struct Sdl_Audio {
+    _context: Option<mixer::Sdl2MixerContext>,
+    effects: Vec<mixer::Chunk>,
+    music: Option<mixer::Chunk>,
+    fast_music: Option<mixer::Chunk>,
+}
+
+impl Sdl_Audio {
+    // Needs the SDL audio subsystem to be initialised. Missing music only
+    // costs the music, the effects are synthesized.
+    pub fn open() -> Result<Self, String> {
+        let context: Option<mixer::Sdl2MixerContext> = mixer::init(mixer::InitFlag::MP3)
+            .map_err(|e| eprintln!("no mp3 support: {}", e))
+            .ok();
+        mixer::open_audio(44100, mixer::AUDIO_S16SYS, 2, 1024)?;
+        mixer::allocate_channels(16);
+        mixer::reserve_channels(1);
+        let (rate, _, channels) = mixer::query_spec()?;
+        let mut effects: Vec<mixer::Chunk> = Vec::new();
+        for effect in SOUND_EFFECTS.iter() {
+            let chunk: Option<mixer::Chunk> = match effect {
+                Sound_Effect::SFX_LINE_CLEAR => mixer::Chunk::from_file(BEEP_PATH)
+                    .map_err(|e| eprintln!("{}: {}", BEEP_PATH, e))
+                    .ok(),
+                _ => None,
+            };
+            let chunk: mixer::Chunk = match chunk {
+                Some(chunk) => chunk,
+                None => {
+                    let samples: Vec<i16> = synthesize(effect_notes(*effect), rate, channels);
+                    mixer::Chunk::from_raw_buffer(samples.into_boxed_slice())?
+                }
+            };
+            effects.push(chunk);
+        }
+        let mut audio: Sdl_Audio = Sdl_Audio {
+            _context: context,
+            effects: effects,
+            music: None,
+            fast_music: None,
+        };
+        match mixer::Chunk::from_file(MUSIC_PATH) {
+            Ok(music) => {
+                let fast: Vec<i16> = speed_up(chunk_samples(&music), channels, MUSIC_FAST_SPEED);
+                audio.fast_music = mixer::Chunk::from_raw_buffer(fast.into_boxed_slice()).ok();
+                audio.music = Some(music);
+            }
+            Err(e) => eprintln!("{}: {}", MUSIC_PATH, e),
+        }
+        return Ok(audio);
+    }
+}
+
+impl Audio_Backend for Sdl_Audio {
+    fn play_effect(&mut self, effect: Sound_Effect) {
+        // Running out of free channels just drops the effect.
+        let _ = mixer::Channel::all().play(&self.effects[effect as usize], 0);
+    }
+    fn play_music(&mut self, track: Option<Music_Track>) {
+        let chunk: Option<&mixer::Chunk> = match track {
+            Some(Music_Track::MUSIC_NORMAL) => self.music.as_ref(),
+            Some(Music_Track::MUSIC_FAST) => self.fast_music.as_ref(),
+            None => None,
+        };
+        match chunk {
+            Some(chunk) => {
+                let _ = MUSIC_CHANNEL.play(chunk, -1);
+            }
+            None => MUSIC_CHANNEL.halt(),
+        }
+    }
+    fn pause_music(&mut self, paused: bool) {
+        if paused {
+            MUSIC_CHANNEL.pause();
+        } else {
+            MUSIC_CHANNEL.resume();
+        }
+    }
+    fn set_volumes(&mut self, music_volume: i32, sfx_volume: i32, muted: bool) {
+        MUSIC_CHANNEL.set_volume(to_mixer_volume(music_volume, muted));
+        for chunk in self.effects.iter_mut() {
+            chunk.set_volume(to_mixer_volume(sfx_volume, muted));
+        }
+    }
+}
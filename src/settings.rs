@@ -0,0 +1,272 @@
+// Player preferences, stored in settings.toml in the config directory.
+// Every value has a default, so a missing or partly broken file still loads.
+use super::config::*;
+use super::game::*;
+use super::pieces::*;
+
+pub const SETTINGS_FILE: &str = "settings.toml";
+
+// Written to the file so later layouts can tell old files apart.
+pub const SETTINGS_VERSION: i64 = 1;
+
+#[derive(Clone, PartialEq)]
+pub struct Settings {
+    pub das_ms: i32,
+    pub arr_ms: i32,
+    pub ghost: bool,
+    pub grid_size: i32,
+    pub theme: String,
+    pub fullscreen: bool,
+    pub animations: bool,
+    pub smooth_motion: bool,
+    pub music_volume: i32,
+    pub sfx_volume: i32,
+    pub muted: bool,
+    pub mode: Game_Mode,
+    pub ruleset: Game_Ruleset,
+    pub start_level: i32,
+    pub board_size: Board_Size,
+    pub pieces: String,
+}
+
+pub const MIN_GRID_SIZE: i32 = 24;
+pub const MAX_GRID_SIZE: i32 = 48;
+pub const MAX_VOLUME: i32 = 100;
+
+fn get_value<'a>(table: &'a toml::Table, section: &str, key: &str) -> Option<&'a toml::Value> {
+    return table.get(section)?.as_table()?.get(key);
+}
+
+fn get_int(table: &toml::Table, section: &str, key: &str, min: i32, max: i32) -> Option<i32> {
+    let value: i64 = get_value(table, section, key)?.as_integer()?;
+    return Some(value.clamp(min as i64, max as i64) as i32);
+}
+
+fn get_bool(table: &toml::Table, section: &str, key: &str) -> Option<bool> {
+    return get_value(table, section, key)?.as_bool();
+}
+
+fn get_str<'a>(table: &'a toml::Table, section: &str, key: &str) -> Option<&'a str> {
+    return get_value(table, section, key)?.as_str();
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Settings {
+            das_ms: 167,
+            arr_ms: 33,
+            ghost: true,
+            grid_size: GRID_SIZE as i32,
+            theme: "default".to_string(),
+            fullscreen: false,
+            animations: true,
+            smooth_motion: false,
+            music_volume: 70,
+            sfx_volume: 80,
+            muted: false,
+            mode: Game_Mode::MODE_MARATHON,
+            ruleset: Game_Ruleset::RULESET_MODERN,
+            start_level: 1,
+            board_size: DEFAULT_BOARD_SIZE,
+            pieces: STANDARD_PIECES.to_string(),
+        }
+    }
+    pub fn load() -> Self {
+        let Ok(contents) = std::fs::read_to_string(config_path(SETTINGS_FILE)) else {
+            return Settings::new();
+        };
+        match contents.parse::<toml::Table>() {
+            Ok(table) => return Settings::from_table(table),
+            Err(e) => {
+                eprintln!("{}: {}", SETTINGS_FILE, e);
+                return Settings::new();
+            }
+        }
+    }
+    // Values that are missing or of the wrong type keep their defaults,
+    // numbers out of range are clamped.
+    pub fn from_table(table: toml::Table) -> Self {
+        let mut settings: Settings = Settings::new();
+        let version: i64 = table
+            .get("version")
+            .and_then(|v| v.as_integer())
+            .unwrap_or(SETTINGS_VERSION);
+        if version > SETTINGS_VERSION {
+            eprintln!(
+                "{}: version {} is newer than {}, some settings may be ignored",
+                SETTINGS_FILE, version, SETTINGS_VERSION
+            );
+        }
+        if let Some(das_ms) = get_int(&table, "handling", "das_ms", 0, 1000) {
+            settings.das_ms = das_ms;
+        }
+        if let Some(arr_ms) = get_int(&table, "handling", "arr_ms", 0, 1000) {
+            settings.arr_ms = arr_ms;
+        }
+        if let Some(ghost) = get_bool(&table, "visual", "ghost") {
+            settings.ghost = ghost;
+        }
+        if let Some(grid_size) =
+            get_int(&table, "visual", "grid_size", MIN_GRID_SIZE, MAX_GRID_SIZE)
+        {
+            settings.grid_size = grid_size;
+        }
+        if let Some(theme) = get_str(&table, "visual", "theme") {
+            settings.theme = theme.to_string();
+        }
+        if let Some(fullscreen) = get_bool(&table, "visual", "fullscreen") {
+            settings.fullscreen = fullscreen;
+        }
+        if let Some(animations) = get_bool(&table, "visual", "animations") {
+            settings.animations = animations;
+        }
+        if let Some(smooth_motion) = get_bool(&table, "visual", "smooth_motion") {
+            settings.smooth_motion = smooth_motion;
+        }
+        if let Some(volume) = get_int(&table, "audio", "music_volume", 0, MAX_VOLUME) {
+            settings.music_volume = volume;
+        }
+        if let Some(volume) = get_int(&table, "audio", "sfx_volume", 0, MAX_VOLUME) {
+            settings.sfx_volume = volume;
+        }
+        if let Some(muted) = get_bool(&table, "audio", "muted") {
+            settings.muted = muted;
+        }
+        if let Some(mode) = get_str(&table, "game", "mode").and_then(Game_Mode::from_name) {
+            settings.mode = mode;
+        }
+        if let Some(ruleset) = get_str(&table, "game", "ruleset").and_then(Game_Ruleset::from_name)
+        {
+            settings.ruleset = ruleset;
+        }
+        if let Some(start_level) = get_int(&table, "game", "start_level", 0, 29) {
+            settings.start_level = start_level;
+        }
+        if let Some(width) = get_int(
+            &table,
+            "game",
+            "board_width",
+            MIN_BOARD_WIDTH,
+            MAX_BOARD_WIDTH,
+        ) {
+            settings.board_size.width = width;
+        }
+        if let Some(height) = get_int(
+            &table,
+            "game",
+            "board_height",
+            MIN_BOARD_HEIGHT,
+            MAX_BOARD_HEIGHT,
+        ) {
+            settings.board_size.height = height;
+        }
+        if let Some(pieces) = get_str(&table, "game", "pieces") {
+            settings.pieces = pieces.to_string();
+        }
+        return settings;
+    }
+    pub fn to_table(&self) -> toml::Table {
+        let mut handling: toml::Table = toml::Table::new();
+        handling.insert(
+            "das_ms".to_string(),
+            toml::Value::Integer(self.das_ms as i64),
+        );
+        handling.insert(
+            "arr_ms".to_string(),
+            toml::Value::Integer(self.arr_ms as i64),
+        );
+        let mut visual: toml::Table = toml::Table::new();
+        visual.insert("ghost".to_string(), toml::Value::Boolean(self.ghost));
+        visual.insert(
+            "grid_size".to_string(),
+            toml::Value::Integer(self.grid_size as i64),
+        );
+        visual.insert("theme".to_string(), toml::Value::String(self.theme.clone()));
+        visual.insert(
+            "fullscreen".to_string(),
+            toml::Value::Boolean(self.fullscreen),
+        );
+        visual.insert(
+            "animations".to_string(),
+            toml::Value::Boolean(self.animations),
+        );
+        visual.insert(
+            "smooth_motion".to_string(),
+            toml::Value::Boolean(self.smooth_motion),
+        );
+        let mut audio: toml::Table = toml::Table::new();
+        audio.insert(
+            "music_volume".to_string(),
+            toml::Value::Integer(self.music_volume as i64),
+        );
+        audio.insert(
+            "sfx_volume".to_string(),
+            toml::Value::Integer(self.sfx_volume as i64),
+        );
+        audio.insert("muted".to_string(), toml::Value::Boolean(self.muted));
+        let mut game: toml::Table = toml::Table::new();
+        game.insert(
+            "mode".to_string(),
+            toml::Value::String(self.mode.name().to_string()),
+        );
+        game.insert(
+            "ruleset".to_string(),
+            toml::Value::String(self.ruleset.name().to_string()),
+        );
+        game.insert(
+            "start_level".to_string(),
+            toml::Value::Integer(self.start_level as i64),
+        );
+        game.insert(
+            "board_width".to_string(),
+            toml::Value::Integer(self.board_size.width as i64),
+        );
+        game.insert(
+            "board_height".to_string(),
+            toml::Value::Integer(self.board_size.height as i64),
+        );
+        game.insert(
+            "pieces".to_string(),
+            toml::Value::String(self.pieces.clone()),
+        );
+
+        let mut table: toml::Table = toml::Table::new();
+        table.insert(
+            "version".to_string(),
+            toml::Value::Integer(SETTINGS_VERSION),
+        );
+        table.insert("handling".to_string(), toml::Value::Table(handling));
+        table.insert("visual".to_string(), toml::Value::Table(visual));
+        table.insert("audio".to_string(), toml::Value::Table(audio));
+        table.insert("game".to_string(), toml::Value::Table(game));
+        return table;
+    }
+    pub fn save(&self) -> std::io::Result<()> {
+        return write_config_file(SETTINGS_FILE, &self.to_table().to_string());
+    }
+    // Copies the gameplay settings into a game. Handling changes straight
+    // away, the rest only between games. An unknown piece set, or one with
+    // pieces too big for the board, falls back to the first one, the
+    // standard tetrominoes.
+    pub fn apply(&self, game: &mut Game_State, piece_sets: &[Piece_Set]) {
+        game.das = self.das_ms as f32 / 1000.0;
+        game.arr = self.arr_ms as f32 / 1000.0;
+        if game.phase == Game_Phase::GAME_PHASE_START
+            || game.phase == Game_Phase::GAME_PHASE_GAMEOVER
+        {
+            game.mode = self.mode;
+            game.ruleset = self.ruleset;
+            game.start_level = self.start_level;
+            if game.board_size() != self.board_size {
+                game.set_board_size(self.board_size);
+            }
+            let mut pieces: &Piece_Set = &piece_sets[piece_set_index(piece_sets, &self.pieces)];
+            if !pieces.fits(self.board_size) {
+                pieces = &piece_sets[0];
+            }
+            if game.pieces() != pieces {
+                game.set_piece_set(pieces.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use sdl2::keyboard::Scancode;
+use tetris::game::*;
+use tetris::settings::*;
+
+const ENTRY_COUNT: usize = 17;
+
+fn step_in_range(value: i32, step: i32, min: i32, max: i32) -> i32 {
+    return (value + step).clamp(min, max);
+}
+
+// Steps through a list of names, wrapping around at both ends.
+fn cycle<T: Clone>(items: &[T], current: usize, step: i32) -> T {
+    let count: i32 = items.len() as i32;
+    let index: i32 = (current as i32 + step).rem_euclid(count);
+    return items[index as usize].clone();
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        return "ON";
+    }
+    return "OFF";
+}
+
+// Up and down pick a setting, left and right change it. Return or Escape
+// closes the screen.
+pub struct Settings_Screen {
+    pub settings: Settings,
+    pub index: usize,
+    theme_names: Vec<String>,
+    piece_set_names: Vec<String>,
+}
+
+impl Settings_Screen {
+    pub fn new(
+        settings: &Settings,
+        theme_names: Vec<String>,
+        piece_set_names: Vec<String>,
+    ) -> Self {
+        Settings_Screen {
+            settings: settings.clone(),
+            index: 0,
+            theme_names: theme_names,
+            piece_set_names: piece_set_names,
+        }
+    }
+    fn change(&mut self, step: i32) {
+        let settings: &mut Settings = &mut self.settings;
+        match self.index {
+            0 => settings.das_ms = step_in_range(settings.das_ms, step * 10, 0, 500),
+            1 => settings.arr_ms = step_in_range(settings.arr_ms, step * 5, 0, 200),
+            2 => settings.ghost = !settings.ghost,
+            3 => {
+                settings.grid_size =
+                    step_in_range(settings.grid_size, step * 2, MIN_GRID_SIZE, MAX_GRID_SIZE)
+            }
+            4 => {
+                let names: &[String] = &self.theme_names;
+                let current: usize = names.iter().position(|n| *n == settings.theme).unwrap_or(0);
+                settings.theme = cycle(names, current, step).clone();
+            }
+            5 => settings.fullscreen = !settings.fullscreen,
+            6 => settings.animations = !settings.animations,
+            7 => settings.smooth_motion = !settings.smooth_motion,
+            8 => {
+                settings.music_volume =
+                    step_in_range(settings.music_volume, step * 10, 0, MAX_VOLUME)
+            }
+            9 => settings.sfx_volume = step_in_range(settings.sfx_volume, step * 10, 0, MAX_VOLUME),
+            10 => settings.muted = !settings.muted,
+            11 => settings.mode = cycle(&GAME_MODES, settings.mode as usize, step),
+            12 => settings.ruleset = cycle(&GAME_RULESETS, settings.ruleset as usize, step),
+            13 => settings.start_level = step_in_range(settings.start_level, step, 0, 29),
+            14 => {
+                settings.board_size.width = step_in_range(
+                    settings.board_size.width,
+                    step,
+                    MIN_BOARD_WIDTH,
+                    MAX_BOARD_WIDTH,
+                )
+            }
+            15 => {
+                settings.board_size.height = step_in_range(
+                    settings.board_size.height,
+                    step,
+                    MIN_BOARD_HEIGHT,
+                    MAX_BOARD_HEIGHT,
+                )
+            }
+            16 => {
+                let names: &[String] = &self.piece_set_names;
+                let current: usize = names
+                    .iter()
+                    .position(|n| *n == settings.pieces)
+                    .unwrap_or(0);
+                settings.pieces = cycle(names, current, step).clone();
+            }
+            _ => {}
+        }
+    }
+    // Returns true when the screen should close.
+    pub fn key_pressed(&mut self, scancode: Scancode) -> bool {
+        match scancode {
+            Scancode::Up => self.index = (self.index + ENTRY_COUNT - 1) % ENTRY_COUNT,
+            Scancode::Down => self.index = (self.index + 1) % ENTRY_COUNT,
+            Scancode::Left => self.change(-1),
+            Scancode::Right => self.change(1),
+            Scancode::Return => return true,
+            _ => {}
+        }
+        return false;
+    }
+    pub fn lines(&self) -> Vec<String> {
+        let settings: &Settings = &self.settings;
+        let entries: [String; ENTRY_COUNT] = [
+            format!("DAS: {} MS", settings.das_ms),
+            format!("ARR: {} MS", settings.arr_ms),
+            format!("GHOST PIECE: {}", on_off(settings.ghost)),
+            format!("GRID SIZE: {}", settings.grid_size),
+            format!("THEME: {}", settings.theme.to_uppercase()),
+            format!("FULLSCREEN: {}", on_off(settings.fullscreen)),
+            format!("ANIMATIONS: {}", on_off(settings.animations)),
+            format!("SMOOTH MOTION: {}", on_off(settings.smooth_motion)),
+            format!("MUSIC VOLUME: {}", settings.music_volume),
+            format!("SOUND VOLUME: {}", settings.sfx_volume),
+            format!("MUTE: {}", on_off(settings.muted)),
+            format!("MODE: {}", settings.mode.name().to_uppercase()),
+            format!("RULES: {}", settings.ruleset.name().to_uppercase()),
+            format!("START LEVEL: {}", settings.start_level),
+            format!("BOARD WIDTH: {}", settings.board_size.width),
+            format!("BOARD HEIGHT: {}", settings.board_size.height),
+            format!("PIECES: {}", settings.pieces.to_uppercase()),
+        ];
+        let mut lines: Vec<String> = Vec::new();
+        for (i, entry) in entries.iter().enumerate() {
+            if i == self.index {
+                lines.push(format!("> {}", entry));
+            } else {
+                lines.push(entry.clone());
+            }
+        }
+        lines.push(String::new());
+        lines.push("ARROWS: CHANGE  ENTER: DONE".to_string());
+        return lines;
+    }
+}
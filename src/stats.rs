@@ -0,0 +1,253 @@
+// Player statistics, built from the events a game emits. The current game's
+// numbers are shown when it ends, and every finished game is added to the
+// lifetime totals in stats.toml.
+use super::config::*;
+use super::game::*;
+
+pub const STATS_FILE: &str = "stats.toml";
+
+// Index of the I piece in TETROMINOS.
+pub const I_PIECE: u8 = 0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Game_Stats {
+    pub games: u32,
+    pub top_outs: u32,
+    // Pieces placed, by tetromino index.
+    pub pieces: [u32; 7],
+    // Clears of one to four lines.
+    pub clears: [u32; 4],
+    pub keys: u32,
+    pub time_played: f32,
+    // Pieces spawned between two I pieces.
+    pub longest_drought: u32,
+    pub droughts: u32,
+    pub drought_pieces: u32,
+}
+
+fn get_int(table: &toml::Table, key: &str) -> u32 {
+    let value: i64 = table.get(key).and_then(|v| v.as_integer()).unwrap_or(0);
+    return value.clamp(0, u32::MAX as i64) as u32;
+}
+
+fn get_float(table: &toml::Table, key: &str) -> f32 {
+    let value: Option<&toml::Value> = table.get(key);
+    let seconds: f64 = value
+        .and_then(|v| v.as_float().or(v.as_integer().map(|i| i as f64)))
+        .unwrap_or(0.0);
+    return seconds.max(0.0) as f32;
+}
+
+impl Game_Stats {
+    pub fn new() -> Self {
+        Game_Stats {
+            games: 0,
+            top_outs: 0,
+            pieces: [0; 7],
+            clears: [0; 4],
+            keys: 0,
+            time_played: 0.0,
+            longest_drought: 0,
+            droughts: 0,
+            drought_pieces: 0,
+        }
+    }
+    pub fn total_pieces(&self) -> u32 {
+        return self.pieces.iter().sum();
+    }
+    pub fn total_lines(&self) -> u32 {
+        return self
+            .clears
+            .iter()
+            .enumerate()
+            .map(|(i, count)| (i as u32 + 1) * count)
+            .sum();
+    }
+    // Share of cleared lines that came from tetrises.
+    pub fn tetris_rate(&self) -> f32 {
+        let lines: u32 = self.total_lines();
+        if lines == 0 {
+            return 0.0;
+        }
+        return (4 * self.clears[3]) as f32 / lines as f32;
+    }
+    pub fn pps(&self) -> f32 {
+        if self.time_played <= 0.0 {
+            return 0.0;
+        }
+        return self.total_pieces() as f32 / self.time_played;
+    }
+    pub fn kpp(&self) -> f32 {
+        let pieces: u32 = self.total_pieces();
+        if pieces == 0 {
+            return 0.0;
+        }
+        return self.keys as f32 / pieces as f32;
+    }
+    pub fn average_drought(&self) -> f32 {
+        if self.droughts == 0 {
+            return 0.0;
+        }
+        return self.drought_pieces as f32 / self.droughts as f32;
+    }
+    pub fn merge(&mut self, other: &Game_Stats) {
+        self.games += other.games;
+        self.top_outs += other.top_outs;
+        for i in 0..self.pieces.len() {
+            self.pieces[i] += other.pieces[i];
+        }
+        for i in 0..self.clears.len() {
+            self.clears[i] += other.clears[i];
+        }
+        self.keys += other.keys;
+        self.time_played += other.time_played;
+        self.longest_drought = self.longest_drought.max(other.longest_drought);
+        self.droughts += other.droughts;
+        self.drought_pieces += other.drought_pieces;
+    }
+    pub fn load() -> Self {
+        let Ok(contents) = std::fs::read_to_string(config_path(STATS_FILE)) else {
+            return Game_Stats::new();
+        };
+        match contents.parse::<toml::Table>() {
+            Ok(table) => return Game_Stats::from_table(&table),
+            Err(e) => {
+                eprintln!("{}: {}", STATS_FILE, e);
+                return Game_Stats::new();
+            }
+        }
+    }
+    // Missing values count as zero.
+    pub fn from_table(table: &toml::Table) -> Self {
+        let mut stats: Game_Stats = Game_Stats::new();
+        stats.games = get_int(table, "games");
+        stats.top_outs = get_int(table, "top_outs");
+        if let Some(pieces) = table.get("pieces").and_then(|v| v.as_array()) {
+            for (i, count) in pieces.iter().take(stats.pieces.len()).enumerate() {
+                stats.pieces[i] = count.as_integer().unwrap_or(0).max(0) as u32;
+            }
+        }
+        if let Some(clears) = table.get("clears").and_then(|v| v.as_array()) {
+            for (i, count) in clears.iter().take(stats.clears.len()).enumerate() {
+                stats.clears[i] = count.as_integer().unwrap_or(0).max(0) as u32;
+            }
+        }
+        stats.keys = get_int(table, "keys");
+        stats.time_played = get_float(table, "time_played");
+        stats.longest_drought = get_int(table, "longest_drought");
+        stats.droughts = get_int(table, "droughts");
+        stats.drought_pieces = get_int(table, "drought_pieces");
+        return stats;
+    }
+    pub fn to_table(&self) -> toml::Table {
+        let counts = |values: &[u32]| {
+            toml::Value::Array(
+                values
+                    .iter()
+                    .map(|v| toml::Value::Integer(*v as i64))
+                    .collect(),
+            )
+        };
+        let mut table: toml::Table = toml::Table::new();
+        table.insert("games".to_string(), toml::Value::Integer(self.games as i64));
+        table.insert(
+            "top_outs".to_string(),
+            toml::Value::Integer(self.top_outs as i64),
+        );
+        table.insert("pieces".to_string(), counts(&self.pieces));
+        table.insert("clears".to_string(), counts(&self.clears));
+        table.insert("keys".to_string(), toml::Value::Integer(self.keys as i64));
+        table.insert(
+            "time_played".to_string(),
+            toml::Value::Float(self.time_played as f64),
+        );
+        table.insert(
+            "longest_drought".to_string(),
+            toml::Value::Integer(self.longest_drought as i64),
+        );
+        table.insert(
+            "droughts".to_string(),
+            toml::Value::Integer(self.droughts as i64),
+        );
+        table.insert(
+            "drought_pieces".to_string(),
+            toml::Value::Integer(self.drought_pieces as i64),
+        );
+        return table;
+    }
+    pub fn save(&self) -> std::io::Result<()> {
+        return write_config_file(STATS_FILE, &self.to_table().to_string());
+    }
+}
+
+// Follows one game through its events and adds it to the lifetime totals
+// when it ends.
+pub struct Stats_Tracker {
+    pub current: Game_Stats,
+    pub lifetime: Game_Stats,
+    // Pieces spawned since the last I piece.
+    drought: u32,
+}
+
+impl Stats_Tracker {
+    pub fn new(lifetime: Game_Stats) -> Self {
+        Stats_Tracker {
+            current: Game_Stats::new(),
+            lifetime: lifetime,
+            drought: 0,
+        }
+    }
+    // Call with the input before it is passed to Game_State::update.
+    pub fn record_input(&mut self, game: &Game_State, input: &Input_State) {
+        if game.phase != Game_Phase::GAME_PHASE_PLAY {
+            return;
+        }
+        for action in GAME_ACTIONS.iter() {
+            if *action != Game_Action::ACTION_PAUSE && input.get_edge(*action) > 0 {
+                self.current.keys += 1;
+            }
+        }
+    }
+    // Returns true when a game ended and was added to the lifetime totals.
+    pub fn handle_events(&mut self, events: &[Game_Event], game: &Game_State) -> bool {
+        // The counts are per tetromino, so other piece sets aren't tracked.
+        if !game.pieces().is_standard() {
+            return false;
+        }
+        let mut ended: bool = false;
+        for event in events.iter() {
+            match *event {
+                Game_Event::EVENT_GAME_START => {
+                    self.current = Game_Stats::new();
+                    self.drought = 0;
+                }
+                Game_Event::EVENT_PIECE_SPAWNED { tetromino_index } => {
+                    if tetromino_index != I_PIECE {
+                        self.drought += 1;
+                        self.current.longest_drought =
+                            self.current.longest_drought.max(self.drought);
+                    } else if self.drought > 0 {
+                        self.current.droughts += 1;
+                        self.current.drought_pieces += self.drought;
+                        self.drought = 0;
+                    }
+                }
+                Game_Event::EVENT_PIECE_LOCKED { tetromino_index } => {
+                    self.current.pieces[tetromino_index as usize] += 1;
+                }
+                Game_Event::EVENT_LINES_CLEARED { kind, .. } => {
+                    self.current.clears[kind as usize] += 1;
+                }
+                Game_Event::EVENT_GAME_OVER { finished } => {
+                    self.current.games = 1;
+                    self.current.top_outs = !finished as u32;
+                    self.current.time_played = game.get_play_time();
+                    self.lifetime.merge(&self.current);
+                    ended = true;
+                }
+                _ => {}
+            }
+        }
+        return ended;
+    }
+}
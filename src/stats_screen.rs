@@ -0,0 +1,71 @@
+use sdl2::keyboard::Scancode;
+use tetris::game::format_time;
+use tetris::pieces::PIECE_NAMES;
+use tetris::stats::*;
+
+// Left and right switch between the last game and the all-time totals.
+pub struct Stats_Screen {
+    pub all_time: bool,
+}
+
+fn stats_lines(stats: &Game_Stats, all_time: bool) -> Vec<String> {
+    let piece_count = |range: std::ops::Range<usize>| {
+        range
+            .map(|i| format!("{}:{}", PIECE_NAMES[i], stats.pieces[i]))
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+    let mut lines: Vec<String> = vec![
+        format!("PIECES: {}", stats.total_pieces()),
+        piece_count(0..4),
+        piece_count(4..7),
+        format!("LINES: {}", stats.total_lines()),
+        format!("SINGLE: {}  DOUBLE: {}", stats.clears[0], stats.clears[1]),
+        format!("TRIPLE: {}  TETRIS: {}", stats.clears[2], stats.clears[3]),
+        format!("TETRIS RATE: {:.0}%", stats.tetris_rate() * 100.0),
+        format!(
+            "I DROUGHT MAX: {}  AVG: {:.1}",
+            stats.longest_drought,
+            stats.average_drought()
+        ),
+        format!("PPS: {:.2}  KPP: {:.2}", stats.pps(), stats.kpp()),
+        format!("TIME: {}", format_time(stats.time_played)),
+    ];
+    if all_time {
+        lines.push(format!(
+            "GAMES: {}  TOP OUTS: {}",
+            stats.games, stats.top_outs
+        ));
+    }
+    return lines;
+}
+
+impl Stats_Screen {
+    pub fn new(all_time: bool) -> Self {
+        Stats_Screen { all_time: all_time }
+    }
+    // Returns true when the screen should close.
+    pub fn key_pressed(&mut self, scancode: Scancode) -> bool {
+        match scancode {
+            Scancode::Left | Scancode::Right => self.all_time = !self.all_time,
+            Scancode::Return => return true,
+            _ => {}
+        }
+        return false;
+    }
+    pub fn lines(&self, tracker: &Stats_Tracker) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        if self.all_time {
+            lines.push("< ALL TIME >".to_string());
+            lines.push(String::new());
+            lines.extend(stats_lines(&tracker.lifetime, true));
+        } else {
+            lines.push("< LAST GAME >".to_string());
+            lines.push(String::new());
+            lines.extend(stats_lines(&tracker.current, false));
+        }
+        lines.push(String::new());
+        lines.push("ARROWS: PAGE  ENTER: DONE".to_string());
+        return lines;
+    }
+}
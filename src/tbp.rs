@@ -0,0 +1,320 @@
+// Frontend side of the Tetris Bot Protocol (TBP): launches an external bot,
+// talks JSON lines over its stdin/stdout and plays its suggestions.
+use super::ai::*;
+use super::game::*;
+use super::pieces::PIECE_NAMES;
+use serde_json::Value;
+use serde_json::json;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::process::Child;
+use std::process::ChildStdin;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::channel;
+use std::time::Duration;
+use std::time::Instant;
+
+pub const TBP_BOARD_HEIGHT: usize = 40;
+
+pub static ORIENTATIONS: [&str; 4] = ["north", "east", "south", "west"];
+
+pub fn piece_name(index: u8) -> &'static str {
+    return PIECE_NAMES[index as usize];
+}
+
+pub fn piece_index(name: &str) -> Option<u8> {
+    return PIECE_NAMES.iter().position(|n| *n == name).map(|i| i as u8);
+}
+
+fn cell_value(value: u8) -> Value {
+    if value == 0 {
+        return Value::Null;
+    }
+    if value as usize <= PIECE_NAMES.len() {
+        return json!(PIECE_NAMES[value as usize - 1]);
+    }
+    return json!("G");
+}
+
+// TBP rows go bottom up and the board is 40 rows tall; everything above our
+// matrix is empty. Taller boards send all their rows.
+pub fn board_to_tbp(game: &Game_State) -> Value {
+    let size: Board_Size = game.board_size();
+    let mut rows: Vec<Value> = Vec::new();
+    for y in 0..TBP_BOARD_HEIGHT.max(size.rows() as usize) as i32 {
+        let mut row: Vec<Value> = Vec::new();
+        for col in 0..size.width {
+            if y < size.rows() {
+                let board_row: i32 = size.rows() - 1 - y;
+                row.push(cell_value(game.matrix_get(board_row, col)));
+            } else {
+                row.push(Value::Null);
+            }
+        }
+        rows.push(Value::Array(row));
+    }
+    return Value::Array(rows);
+}
+
+// Cell of the tetromino matrix, as (row, col), that TBP uses as the piece
+// location. Our rotations are plain matrix rotations, which match SRS.
+fn piece_center(index: u8, rotation: i32) -> (i32, i32) {
+    match (PIECE_NAMES[index as usize], rotation) {
+        ("I", 0) => return (1, 1),
+        ("I", 1) => return (1, 2),
+        ("I", 2) => return (2, 2),
+        ("I", 3) => return (2, 1),
+        ("O", _) => return (1, 0),
+        _ => {}
+    }
+    return (1, 1);
+}
+
+pub struct Tbp_Move {
+    pub tetromino_index: u8,
+    pub rotation: i32,
+    pub offset_row: i32,
+    pub offset_col: i32,
+}
+
+// `rows` is the height of our board, hidden rows included.
+pub fn parse_move(value: &Value, rows: i32) -> Option<Tbp_Move> {
+    let location: &Value = &value["location"];
+    let index: u8 = piece_index(location["type"].as_str()?)?;
+    let rotation: i32 = ORIENTATIONS
+        .iter()
+        .position(|o| Some(*o) == location["orientation"].as_str())? as i32;
+    let x: i32 = location["x"].as_i64()? as i32;
+    let y: i32 = location["y"].as_i64()? as i32;
+    let (center_row, center_col) = piece_center(index, rotation);
+    return Some(Tbp_Move {
+        tetromino_index: index,
+        rotation: rotation,
+        offset_row: rows - 1 - y - center_row,
+        offset_col: x - center_col,
+    });
+}
+
+pub struct Tbp_Bot {
+    child: Child,
+    stdin: ChildStdin,
+    messages: Receiver<Value>,
+    pub name: String,
+}
+
+impl Tbp_Bot {
+    // `command` is the bot executable followed by its arguments.
+    pub fn launch(command: &str) -> std::io::Result<Self> {
+        let mut parts = command.split_whitespace();
+        let program: &str = parts.next().unwrap_or("");
+        let mut child: Child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin: ChildStdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                match serde_json::from_str::<Value>(&line) {
+                    Ok(message) => {
+                        if sender.send(message).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => eprintln!("tbp: bad message from bot: {}", e),
+                }
+            }
+        });
+        return Ok(Tbp_Bot {
+            child: child,
+            stdin: stdin,
+            messages: receiver,
+            name: program.to_string(),
+        });
+    }
+    pub fn send(&mut self, message: &Value) {
+        let result = writeln!(self.stdin, "{}", message).and_then(|_| self.stdin.flush());
+        if let Err(e) = result {
+            eprintln!("tbp: failed to write to bot: {}", e);
+        }
+    }
+    pub fn poll(&self) -> Option<Value> {
+        return self.messages.try_recv().ok();
+    }
+}
+
+// Bots get this long to quit before they are killed.
+const TBP_QUIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+impl Drop for Tbp_Bot {
+    fn drop(&mut self) {
+        self.send(&json!({"type": "quit"}));
+        let deadline: Instant = Instant::now() + TBP_QUIT_TIMEOUT;
+        while Instant::now() < deadline {
+            match self.child.try_wait() {
+                Ok(None) => std::thread::sleep(Duration::from_millis(10)),
+                _ => return,
+            }
+        }
+        eprintln!("tbp: {} did not quit, killing it", self.name);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[derive(PartialEq)]
+enum Tbp_Phase {
+    WAIT_INFO,
+    WAIT_READY,
+    // Ready for the next piece.
+    WAIT_TURN,
+    WAIT_SUGGESTION,
+}
+
+// Drives a game from an external bot. The bot is restarted with the full
+// game state whenever the boards may have diverged, e.g. after garbage.
+// TBP only knows the seven tetrominoes, so other piece sets aren't played.
+pub struct Tbp_Player {
+    bot: Tbp_Bot,
+    phase: Tbp_Phase,
+    started: bool,
+    resync: bool,
+    synced_piece: u32,
+    garbage_seen: u32,
+    warned_pieces: bool,
+    driver: Placement_Driver,
+}
+
+impl Tbp_Player {
+    pub fn new(bot: Tbp_Bot) -> Self {
+        Tbp_Player {
+            bot: bot,
+            phase: Tbp_Phase::WAIT_INFO,
+            started: false,
+            resync: false,
+            synced_piece: 0,
+            garbage_seen: 0,
+            warned_pieces: false,
+            driver: Placement_Driver::new(),
+        }
+    }
+    fn send_start(&mut self, game: &Game_State) {
+        let queue: Vec<&str> = vec![
+            piece_name(game.piece.get_tetr_index()),
+            piece_name(game.piece_next.get_tetr_index()),
+        ];
+        self.bot.send(&json!({
+            "type": "start",
+            "hold": null,
+            "queue": queue,
+            "combo": 0,
+            "back_to_back": false,
+            "board": board_to_tbp(game),
+        }));
+        self.started = true;
+        self.resync = false;
+    }
+    fn play_suggestion(&mut self, message: &Value, game: &Game_State) {
+        let suggested: &Value = &message["moves"][0];
+        let Some(mv) = parse_move(suggested, game.board_size().rows()) else {
+            eprintln!("tbp: {} made no usable suggestion", self.bot.name);
+            self.driver.set_target(None);
+            self.resync = true;
+            return;
+        };
+        if mv.tetromino_index != game.piece.get_tetr_index() {
+            self.resync = true;
+        }
+        // Only rotate, shift and drop are played, so tucks and spins land
+        // somewhere else and the bot has to be told the real board.
+        let landing: Option<i32> = find_landing_row(
+            game.get_board(),
+            game.pieces(),
+            mv.tetromino_index,
+            mv.rotation,
+            mv.offset_col,
+        );
+        if landing != Some(mv.offset_row) {
+            self.resync = true;
+        }
+        self.driver.set_target(Some(Placement {
+            rotation: mv.rotation,
+            col: mv.offset_col,
+            score: 0.0,
+        }));
+        self.bot.send(&json!({"type": "play", "move": suggested}));
+    }
+    fn handle_message(&mut self, message: Value, game: &Game_State) {
+        match message["type"].as_str().unwrap_or("") {
+            "info" => {
+                if let Some(name) = message["name"].as_str() {
+                    self.bot.name = name.to_string();
+                }
+                self.bot.send(&json!({"type": "rules"}));
+                self.phase = Tbp_Phase::WAIT_READY;
+            }
+            "ready" => self.phase = Tbp_Phase::WAIT_TURN,
+            "error" => {
+                eprintln!("tbp: {} reported {}", self.bot.name, message["reason"]);
+                self.phase = Tbp_Phase::WAIT_TURN;
+            }
+            "suggestion" if self.phase == Tbp_Phase::WAIT_SUGGESTION => {
+                self.play_suggestion(&message, game);
+                self.phase = Tbp_Phase::WAIT_TURN;
+            }
+            _ => {}
+        }
+    }
+    pub fn next_input(&mut self, game: &Game_State) -> Input_State {
+        while let Some(message) = self.bot.poll() {
+            self.handle_message(message, game);
+        }
+        if !game.pieces().is_standard() {
+            if !self.warned_pieces {
+                eprintln!("tbp: the {} piece set is not supported", game.pieces().name);
+                self.warned_pieces = true;
+            }
+            return self.driver.next_input(game, false);
+        }
+        if game.garbage_received != self.garbage_seen {
+            self.garbage_seen = game.garbage_received;
+            self.resync = true;
+        }
+        if game.phase != Game_Phase::GAME_PHASE_PLAY && game.phase != Game_Phase::GAME_PHASE_LINE {
+            if self.started && self.phase == Tbp_Phase::WAIT_TURN {
+                self.bot.send(&json!({"type": "stop"}));
+                self.started = false;
+            }
+            return self.driver.next_input(game, false);
+        }
+        if game.phase == Game_Phase::GAME_PHASE_PLAY
+            && self.phase == Tbp_Phase::WAIT_TURN
+            && self.synced_piece != game.piece_count
+        {
+            if !self.started || self.resync {
+                if self.started {
+                    self.bot.send(&json!({"type": "stop"}));
+                }
+                self.send_start(game);
+            } else {
+                let revealed: &str = piece_name(game.piece_next.get_tetr_index());
+                self.bot
+                    .send(&json!({"type": "new_piece", "piece": revealed}));
+            }
+            self.bot.send(&json!({"type": "suggest"}));
+            self.phase = Tbp_Phase::WAIT_SUGGESTION;
+            self.synced_piece = game.piece_count;
+            self.driver.set_target(None);
+        }
+        let waiting: bool = self.phase != Tbp_Phase::WAIT_TURN || !self.driver.has_target();
+        return self.driver.next_input(game, !waiting);
+    }
+}
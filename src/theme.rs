@@ -0,0 +1,376 @@
+// Colors, font and cell style of the boards. A few themes are built in and
+// more can be added as toml files in the themes directory of the config
+// directory, for example:
+//
+//   name = "ocean"
+//   background = "#000010"
+//   board = "#101828"
+//   grid_lines = "#182438"
+//   font = "assets/Gilroy-Light.ttf"
+//   font_size = 24
+//   cell_style = "skin"            # bevel, flat, outline or skin
+//   skin = "ocean.bmp"             # relative to the themes directory
+//   pieces = ["#2D9999", "#99992D", "#992D99", "#2D9951", "#992D2D", "#2D6399", "#99632D"]
+//   garbage = "#707070"
+//   level_palettes = [["#0058F8", "#3CBCFC"], ["#00A800", "#B8F818"]]
+//
+// Pieces are listed in the order I, O, T, S, Z, J, L. More than seven
+// colors can be given for piece sets with more pieces, which otherwise reuse
+// the colors in turn. A skin is a BMP with square tiles side by side: empty,
+// one per piece color and garbage. Cells without a tile are drawn beveled.
+// With level palettes the piece colors change every level, cycling through
+// the list.
+use super::config::*;
+use super::game::GARBAGE_CELL;
+
+pub const THEME_DIR: &str = "themes";
+pub const DEFAULT_FONT: &str = "assets/Gilroy-Light.ttf";
+pub const DEFAULT_FONT_SIZE: u16 = 24;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Theme_Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+const fn rgb(hex: u32) -> Theme_Color {
+    return Theme_Color {
+        r: (hex >> 16) as u8,
+        g: (hex >> 8) as u8,
+        b: hex as u8,
+    };
+}
+
+pub const WHITE: Theme_Color = rgb(0xFFFFFF);
+
+impl Theme_Color {
+    // "#RRGGBB", with or without the '#'.
+    pub fn parse(text: &str) -> Option<Theme_Color> {
+        let hex: &str = text.strip_prefix('#').unwrap_or(text);
+        if hex.len() != 6 {
+            return None;
+        }
+        return Some(rgb(u32::from_str_radix(hex, 16).ok()?));
+    }
+    pub fn lighten(&self) -> Theme_Color {
+        let channel = |c: u8| (c as u32 * 3 / 2).min(0xFF) as u8;
+        return Theme_Color {
+            r: channel(self.r),
+            g: channel(self.g),
+            b: channel(self.b),
+        };
+    }
+    pub fn darken(&self) -> Theme_Color {
+        let channel = |c: u8| (c as u32 * 2 / 3) as u8;
+        return Theme_Color {
+            r: channel(self.r),
+            g: channel(self.g),
+            b: channel(self.b),
+        };
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Cell_Style {
+    CELL_BEVEL,
+    CELL_FLAT,
+    CELL_OUTLINE,
+    CELL_SKIN,
+}
+
+pub static CELL_STYLE_NAMES: [&str; 4] = ["bevel", "flat", "outline", "skin"];
+
+pub static CELL_STYLES: [Cell_Style; 4] = [
+    Cell_Style::CELL_BEVEL,
+    Cell_Style::CELL_FLAT,
+    Cell_Style::CELL_OUTLINE,
+    Cell_Style::CELL_SKIN,
+];
+
+impl Cell_Style {
+    pub fn from_name(name: &str) -> Option<Cell_Style> {
+        let index: usize = CELL_STYLE_NAMES.iter().position(|n| *n == name)?;
+        return Some(CELL_STYLES[index]);
+    }
+}
+
+// Base, light and dark colors of a cell.
+#[derive(Clone, Copy)]
+pub struct Cell_Colors {
+    pub base: Theme_Color,
+    pub light: Theme_Color,
+    pub dark: Theme_Color,
+}
+
+pub fn shades(base: Theme_Color) -> Cell_Colors {
+    return Cell_Colors {
+        base: base,
+        light: base.lighten(),
+        dark: base.darken(),
+    };
+}
+
+#[derive(Clone)]
+pub struct Theme {
+    pub name: String,
+    pub background: Theme_Color,
+    pub board: Theme_Color,
+    pub grid_lines: Option<Theme_Color>,
+    pub font: String,
+    pub font_size: u16,
+    pub cell_style: Cell_Style,
+    // Full path of the skin image.
+    pub skin: Option<std::path::PathBuf>,
+    // Empty, the pieces, then garbage.
+    pub cells: Vec<Cell_Colors>,
+    pub level_palettes: Vec<[Theme_Color; 2]>,
+}
+
+// Board colors only, the pieces are filled in by the caller.
+fn plain_theme(name: &str, board: Theme_Color) -> Theme {
+    let cells: Vec<Cell_Colors> = vec![
+        Cell_Colors {
+            base: board,
+            light: board,
+            dark: board,
+        };
+        9
+    ];
+    return Theme {
+        name: name.to_string(),
+        background: rgb(0x000000),
+        board: board,
+        grid_lines: None,
+        font: DEFAULT_FONT.to_string(),
+        font_size: DEFAULT_FONT_SIZE,
+        cell_style: Cell_Style::CELL_BEVEL,
+        skin: None,
+        cells: cells,
+        level_palettes: Vec::new(),
+    };
+}
+
+// Piece and garbage colors given shade by shade.
+fn set_cells(theme: &mut Theme, base: [u32; 8], light: [u32; 8], dark: [u32; 8]) {
+    for i in 0..8 {
+        theme.cells[i + 1] = Cell_Colors {
+            base: rgb(base[i]),
+            light: rgb(light[i]),
+            dark: rgb(dark[i]),
+        };
+    }
+}
+
+fn default_theme() -> Theme {
+    let mut theme: Theme = plain_theme("default", rgb(0x282828));
+    set_cells(
+        &mut theme,
+        [
+            0x2D9999, 0x99992D, 0x992D99, 0x2D9951, 0x992D2D, 0x2D6399, 0x99632D, 0x707070,
+        ],
+        [
+            0x44E5E5, 0xE5E544, 0xE544E5, 0x44E57A, 0xE54444, 0x4495E5, 0xE59544, 0xA8A8A8,
+        ],
+        [
+            0x1E6666, 0x66661E, 0x661E66, 0x1E6636, 0x661E1E, 0x1E4266, 0x66421E, 0x4A4A4A,
+        ],
+    );
+    return theme;
+}
+
+// Grays only, told apart by brightness.
+fn mono_theme() -> Theme {
+    let mut theme: Theme = plain_theme("mono", rgb(0x282828));
+    set_cells(
+        &mut theme,
+        [
+            0xB0B0B0, 0x989898, 0x808080, 0xA4A4A4, 0x747474, 0x8C8C8C, 0x686868, 0x505050,
+        ],
+        [
+            0xE8E8E8, 0xD0D0D0, 0xB8B8B8, 0xDCDCDC, 0xACACAC, 0xC4C4C4, 0xA0A0A0, 0x808080,
+        ],
+        [
+            0x707070, 0x606060, 0x505050, 0x686868, 0x484848, 0x585858, 0x404040, 0x383838,
+        ],
+    );
+    return theme;
+}
+
+// The NES colors, two per level, repeating every ten levels.
+fn nes_theme() -> Theme {
+    let mut theme: Theme = default_theme();
+    theme.name = "nes".to_string();
+    theme.board = rgb(0x000000);
+    theme.cells[0] = Cell_Colors {
+        base: theme.board,
+        light: theme.board,
+        dark: theme.board,
+    };
+    theme.level_palettes = vec![
+        [rgb(0x0058F8), rgb(0x3CBCFC)],
+        [rgb(0x00A800), rgb(0xB8F818)],
+        [rgb(0xD800CC), rgb(0xF878F8)],
+        [rgb(0x0058F8), rgb(0x58D854)],
+        [rgb(0xE40058), rgb(0x58F898)],
+        [rgb(0x58F898), rgb(0x6888FC)],
+        [rgb(0xF83800), rgb(0x7C7C7C)],
+        [rgb(0x6844FC), rgb(0xA80020)],
+        [rgb(0x0058F8), rgb(0xF83800)],
+        [rgb(0xF83800), rgb(0xFCA044)],
+    ];
+    return theme;
+}
+
+pub fn builtin_themes() -> Vec<Theme> {
+    return vec![default_theme(), mono_theme(), nes_theme()];
+}
+
+fn get_color(table: &toml::Table, key: &str) -> Option<Theme_Color> {
+    return Theme_Color::parse(table.get(key)?.as_str()?);
+}
+
+fn get_colors(value: &toml::Value) -> Option<Vec<Theme_Color>> {
+    let mut colors: Vec<Theme_Color> = Vec::new();
+    for item in value.as_array()?.iter() {
+        colors.push(Theme_Color::parse(item.as_str()?)?);
+    }
+    return Some(colors);
+}
+
+impl Theme {
+    // Starts from the default theme, so a file only needs the values it
+    // changes.
+    pub fn from_table(table: &toml::Table, dir: &std::path::Path) -> Result<Theme, String> {
+        let mut theme: Theme = default_theme();
+        theme.name = table
+            .get("name")
+            .and_then(|v| v.as_str())
+            .ok_or("missing name")?
+            .to_string();
+        if let Some(color) = get_color(table, "background") {
+            theme.background = color;
+        }
+        if let Some(color) = get_color(table, "board") {
+            theme.board = color;
+            theme.cells[0] = Cell_Colors {
+                base: color,
+                light: color,
+                dark: color,
+            };
+        }
+        theme.grid_lines = get_color(table, "grid_lines");
+        if let Some(font) = table.get("font").and_then(|v| v.as_str()) {
+            theme.font = font.to_string();
+        }
+        if let Some(size) = table.get("font_size").and_then(|v| v.as_integer()) {
+            theme.font_size = size.clamp(8, 96) as u16;
+        }
+        if let Some(style) = table.get("cell_style").and_then(|v| v.as_str()) {
+            theme.cell_style =
+                Cell_Style::from_name(style).ok_or(format!("unknown cell style {}", style))?;
+        }
+        if let Some(skin) = table.get("skin").and_then(|v| v.as_str()) {
+            theme.skin = Some(dir.join(skin));
+        }
+        if let Some(pieces) = table.get("pieces") {
+            let colors: Vec<Theme_Color> = get_colors(pieces).ok_or("bad pieces")?;
+            if colors.len() < 7 {
+                return Err("pieces needs at least 7 colors".to_string());
+            }
+            let garbage: Cell_Colors = theme.cells[theme.cells.len() - 1];
+            theme.cells.truncate(1);
+            theme.cells.extend(colors.iter().map(|c| shades(*c)));
+            theme.cells.push(garbage);
+        }
+        if let Some(color) = get_color(table, "garbage") {
+            let last: usize = theme.cells.len() - 1;
+            theme.cells[last] = shades(color);
+        }
+        if let Some(palettes) = table.get("level_palettes").and_then(|v| v.as_array()) {
+            for palette in palettes.iter() {
+                let colors: Vec<Theme_Color> = get_colors(palette).ok_or("bad level palette")?;
+                if colors.len() != 2 {
+                    return Err("level palettes need 2 colors".to_string());
+                }
+                theme.level_palettes.push([colors[0], colors[1]]);
+            }
+        }
+        return Ok(theme);
+    }
+    // Colors of a cell value at a level. Level palettes color the pieces
+    // like the NES: I, O and T white with an edge in the first color, S and
+    // J in the first color and Z and L in the second.
+    pub fn cell_colors(&self, value: u8, level: i32) -> Cell_Colors {
+        let garbage: usize = self.cells.len() - 1;
+        if value == 0 {
+            return self.cells[0];
+        }
+        if value == GARBAGE_CELL {
+            return self.cells[garbage];
+        }
+        let piece: usize = (value as usize - 1) % (garbage - 1);
+        if self.level_palettes.is_empty() {
+            return self.cells[piece + 1];
+        }
+        let palette: [Theme_Color; 2] =
+            self.level_palettes[level.max(0) as usize % self.level_palettes.len()];
+        match (value - 1) % 7 + 1 {
+            1..=3 => {
+                return Cell_Colors {
+                    base: WHITE,
+                    light: WHITE,
+                    dark: palette[0],
+                };
+            }
+            4 | 6 => return shades(palette[0]),
+            _ => return shades(palette[1]),
+        }
+    }
+    // The skin tile of a cell value, in the same order as `cells`, so
+    // pieces past the theme's colors reuse the tiles in turn.
+    pub fn skin_tile(&self, value: u8) -> u8 {
+        let garbage: usize = self.cells.len() - 1;
+        let tile: usize = match value {
+            0 => 0,
+            GARBAGE_CELL => garbage,
+            _ => (value as usize - 1) % (garbage - 1) + 1,
+        };
+        return tile.min(u8::MAX as usize) as u8;
+    }
+}
+
+// The built in themes followed by every readable file in the themes
+// directory. A file can replace a built in theme by using its name.
+pub fn load_themes() -> Vec<Theme> {
+    let mut themes: Vec<Theme> = builtin_themes();
+    let dir: std::path::PathBuf = config_path(THEME_DIR);
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return themes;
+    };
+    let mut paths: Vec<std::path::PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "toml"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        let theme: Result<Theme, String> = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| text.parse::<toml::Table>().map_err(|e| e.to_string()))
+            .and_then(|table| Theme::from_table(&table, &dir));
+        match theme {
+            Ok(theme) => match themes.iter().position(|t| t.name == theme.name) {
+                Some(i) => themes[i] = theme,
+                None => themes.push(theme),
+            },
+            Err(e) => eprintln!("{}: {}", path.display(), e),
+        }
+    }
+    return themes;
+}
+
+// Index of the named theme, or of the default theme.
+pub fn theme_index(themes: &[Theme], name: &str) -> usize {
+    return themes.iter().position(|t| t.name == name).unwrap_or(0);
+}
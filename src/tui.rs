@@ -0,0 +1,442 @@
+// A frontend for terminals, for playing over SSH or anywhere SDL is not
+// available. The board is drawn with 24-bit ANSI colors and Unicode half
+// blocks, two rows of cells to a line of text, and keys are read from
+// stdin in raw mode.
+//
+// Terminals only report key presses, so every key is a tap that presses
+// and releases its action at once. Holding a key repeats the tap at the
+// terminal's own key repeat rate rather than with DAS and ARR.
+use super::game::*;
+use super::pieces::*;
+use super::settings::*;
+use super::tetromino::*;
+use super::theme::*;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+// Text columns taken by the panels either side of the board.
+const TUI_PANEL_WIDTH: usize = 12;
+const RESET: &str = "\x1b[0m";
+
+pub enum Tui_Key {
+    KEY_ACTION(Game_Action),
+    KEY_QUIT,
+}
+
+// Puts the terminal in raw mode on the alternate screen, and puts it back
+// when dropped, even if the game panics.
+struct Raw_Terminal {
+    saved: libc::termios,
+}
+
+impl Raw_Terminal {
+    fn enable() -> std::io::Result<Raw_Terminal> {
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut saved) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        // Reads return straight away, with or without input.
+        let mut raw: libc::termios = saved;
+        unsafe { libc::cfmakeraw(&mut raw) };
+        raw.c_cc[libc::VMIN] = 0;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let mut out = std::io::stdout();
+        write!(out, "\x1b[?1049h\x1b[?25l\x1b[2J")?;
+        out.flush()?;
+        return Ok(Raw_Terminal { saved: saved });
+    }
+    // Everything typed since the last call.
+    fn read_input(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::new();
+        let mut buffer: [u8; 64] = [0; 64];
+        loop {
+            let count: isize = unsafe {
+                libc::read(
+                    libc::STDIN_FILENO,
+                    buffer.as_mut_ptr() as *mut libc::c_void,
+                    buffer.len(),
+                )
+            };
+            if count <= 0 {
+                break;
+            }
+            bytes.extend_from_slice(&buffer[..count as usize]);
+        }
+        return bytes;
+    }
+}
+
+impl Drop for Raw_Terminal {
+    fn drop(&mut self) {
+        let mut out = std::io::stdout();
+        let _ = write!(out, "{}\x1b[?25h\x1b[?1049l", RESET);
+        let _ = out.flush();
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.saved) };
+    }
+}
+
+// Arrow keys arrive as escape sequences, everything else as single bytes.
+// The letters follow the default keyboard bindings, with vi keys as a
+// second set of arrows.
+pub fn parse_keys(bytes: &[u8]) -> Vec<Tui_Key> {
+    let mut keys: Vec<Tui_Key> = Vec::new();
+    let mut i: usize = 0;
+    while i < bytes.len() {
+        if bytes[i] == 0x1b {
+            if i + 2 < bytes.len() && (bytes[i + 1] == b'[' || bytes[i + 1] == b'O') {
+                let action: Option<Game_Action> = match bytes[i + 2] {
+                    b'A' => Some(Game_Action::ACTION_ROTATE_CW),
+                    b'B' => Some(Game_Action::ACTION_SOFT_DROP),
+                    b'C' => Some(Game_Action::ACTION_MOVE_RIGHT),
+                    b'D' => Some(Game_Action::ACTION_MOVE_LEFT),
+                    _ => None,
+                };
+                if let Some(action) = action {
+                    keys.push(Tui_Key::KEY_ACTION(action));
+                }
+                i += 3;
+            } else {
+                i += 1;
+            }
+            continue;
+        }
+        let action: Option<Game_Action> = match bytes[i].to_ascii_lowercase() {
+            b'q' | 0x03 => {
+                keys.push(Tui_Key::KEY_QUIT);
+                None
+            }
+            b' ' | b'\r' => Some(Game_Action::ACTION_HARD_DROP),
+            b'h' => Some(Game_Action::ACTION_MOVE_LEFT),
+            b'l' => Some(Game_Action::ACTION_MOVE_RIGHT),
+            b'j' => Some(Game_Action::ACTION_SOFT_DROP),
+            b'k' | b'x' => Some(Game_Action::ACTION_ROTATE_CW),
+            b'z' => Some(Game_Action::ACTION_ROTATE_CCW),
+            b'a' => Some(Game_Action::ACTION_ROTATE_180),
+            b'c' => Some(Game_Action::ACTION_HOLD),
+            b'p' => Some(Game_Action::ACTION_PAUSE),
+            _ => None,
+        };
+        if let Some(action) = action {
+            keys.push(Tui_Key::KEY_ACTION(action));
+        }
+        i += 1;
+    }
+    return keys;
+}
+
+fn foreground(color: Theme_Color) -> String {
+    return format!("\x1b[38;2;{};{};{}m", color.r, color.g, color.b);
+}
+
+fn background(color: Theme_Color) -> String {
+    return format!("\x1b[48;2;{};{};{}m", color.r, color.g, color.b);
+}
+
+// Two cells stacked in one character, normally two characters wide so
+// cells come out roughly square. None leaves the terminal's own background.
+fn push_cells(
+    out: &mut String,
+    top: Option<Theme_Color>,
+    bottom: Option<Theme_Color>,
+    width: usize,
+) {
+    match (top, bottom) {
+        (Some(top), Some(bottom)) => {
+            out.push_str(&foreground(top));
+            out.push_str(&background(bottom));
+            out.push_str(&"▀".repeat(width));
+        }
+        (Some(top), None) => {
+            out.push_str(RESET);
+            out.push_str(&foreground(top));
+            out.push_str(&"▀".repeat(width));
+        }
+        (None, Some(bottom)) => {
+            out.push_str(RESET);
+            out.push_str(&foreground(bottom));
+            out.push_str(&"▄".repeat(width));
+        }
+        (None, None) => {
+            out.push_str(RESET);
+            out.push_str(&" ".repeat(width));
+        }
+    }
+}
+
+fn paint_piece(
+    cells: &mut [Vec<Theme_Color>],
+    pieces: &Piece_Set,
+    piece: &Piece_State,
+    color: Theme_Color,
+) {
+    let tetromino: Tetromino = pieces.shape(piece.get_tetr_index());
+    for row in 0..tetromino.side {
+        for col in 0..tetromino.side {
+            if tetromino.tetromino_get(row, col, piece.get_rotation()) == 0 {
+                continue;
+            }
+            let board_row: i32 = piece.get_offset_row() + row - HIDDEN_ROWS;
+            let board_col: i32 = piece.get_offset_col() + col;
+            if board_row >= 0
+                && (board_row as usize) < cells.len()
+                && board_col >= 0
+                && (board_col as usize) < cells[0].len()
+            {
+                cells[board_row as usize][board_col as usize] = color;
+            }
+        }
+    }
+}
+
+// The color of every visible cell, with the falling piece and its ghost.
+fn board_colors(game: &Game_State, theme: &Theme, ghost: bool) -> Vec<Vec<Theme_Color>> {
+    let size: Board_Size = game.board_size();
+    let hidden: usize = HIDDEN_ROWS as usize;
+    let mut cells: Vec<Vec<Theme_Color>> =
+        vec![vec![theme.board; size.width as usize]; size.height as usize];
+    for row in 0..size.height as usize {
+        for col in 0..size.width as usize {
+            let value: u8 = game.matrix_get((row + hidden) as i32, col as i32);
+            if game.phase == Game_Phase::GAME_PHASE_LINE && game.lines[row + hidden] > 0 {
+                cells[row][col] = WHITE;
+            } else if value > 0 {
+                cells[row][col] = game.pieces().cell_colors(theme, value, game.level).base;
+            }
+        }
+    }
+    if game.phase == Game_Phase::GAME_PHASE_PLAY {
+        let colors: Cell_Colors =
+            game.pieces()
+                .cell_colors(theme, game.piece.get_tetr_index() + 1, game.level);
+        if ghost {
+            let mut piece: Piece_State = game.piece;
+            while piece.check_piece_valid(game) {
+                piece.move_down();
+            }
+            piece.move_up();
+            paint_piece(&mut cells, game.pieces(), &piece, colors.dark);
+        }
+        paint_piece(&mut cells, game.pieces(), &game.piece, colors.base);
+    }
+    return cells;
+}
+
+// A piece in its spawn rotation, centered in the panel, two rows of cells
+// to a line. Pieces too wide for the panel get one column per cell.
+fn preview_lines(
+    theme: &Theme,
+    game: &Game_State,
+    tetromino_index: Option<u8>,
+    dimmed: bool,
+) -> Vec<String> {
+    let Some(index) = tetromino_index else {
+        return vec![" ".repeat(TUI_PANEL_WIDTH)];
+    };
+    let tetromino: Tetromino = game.pieces().shape(index);
+    let mut min_row: i32 = tetromino.side;
+    let mut max_row: i32 = 0;
+    let mut min_col: i32 = tetromino.side;
+    let mut max_col: i32 = 0;
+    for row in 0..tetromino.side {
+        for col in 0..tetromino.side {
+            if tetromino.tetromino_get(row, col, 0) != 0 {
+                min_row = min_row.min(row);
+                max_row = max_row.max(row);
+                min_col = min_col.min(col);
+                max_col = max_col.max(col);
+            }
+        }
+    }
+    let colors: Cell_Colors = game.pieces().cell_colors(theme, index + 1, game.level);
+    let mut color: Theme_Color = colors.base;
+    if dimmed {
+        color = colors.dark;
+    }
+    let cols: usize = (max_col - min_col + 1) as usize;
+    let mut cell_width: usize = 2;
+    if cols * 2 > TUI_PANEL_WIDTH {
+        cell_width = 1;
+    }
+    let width: usize = cols * cell_width;
+    let left: usize = (TUI_PANEL_WIDTH - width) / 2;
+    let mut lines: Vec<String> = Vec::new();
+    for top in (min_row..=max_row).step_by(2) {
+        let mut out: String = " ".repeat(left);
+        for col in min_col..=max_col {
+            let mut cells: [Option<Theme_Color>; 2] = [None, None];
+            for (i, cell) in cells.iter_mut().enumerate() {
+                let row: i32 = top + i as i32;
+                if row <= max_row && tetromino.tetromino_get(row, col, 0) != 0 {
+                    *cell = Some(color);
+                }
+            }
+            push_cells(&mut out, cells[0], cells[1], cell_width);
+        }
+        out.push_str(RESET);
+        out.push_str(&" ".repeat(TUI_PANEL_WIDTH - width - left));
+        lines.push(out);
+    }
+    return lines;
+}
+
+fn panel_text(text: &str) -> String {
+    return format!(" {:<width$}", text, width = TUI_PANEL_WIDTH - 1);
+}
+
+// The whole screen as text, from the top left corner.
+pub fn render_frame(game: &Game_State, theme: &Theme, ghost: bool) -> String {
+    // Timed modes show the clock instead of the level.
+    let mut level_string = ("LEVEL", format!("{}", game.level));
+    match game.mode {
+        Game_Mode::MODE_MARATHON => {}
+        Game_Mode::MODE_SPRINT => {
+            level_string = ("TIME", format_time(game.get_play_time()));
+        }
+        Game_Mode::MODE_ULTRA => {
+            let left: f32 = (ULTRA_TIME - game.get_play_time()).max(0.0);
+            level_string = ("TIME", format_time(left));
+        }
+    }
+    let mut lines_string: String = format!("{}", game.line_count);
+    if game.mode == Game_Mode::MODE_SPRINT {
+        lines_string = format!("{}/{}", game.line_count, SPRINT_LINES);
+    }
+    let mut left: Vec<String> = vec![panel_text("HOLD")];
+    left.extend(preview_lines(theme, game, game.piece_hold, game.hold_used));
+    left.extend([
+        panel_text(""),
+        panel_text(level_string.0),
+        panel_text(&level_string.1),
+        panel_text(""),
+        panel_text("SCORE"),
+        panel_text(&format!("{}", game.points)),
+        panel_text(""),
+        panel_text("LINES"),
+        panel_text(&lines_string),
+    ]);
+
+    let mut messages: Vec<String> = Vec::new();
+    match game.phase {
+        Game_Phase::GAME_PHASE_START => {
+            messages.push(game.mode.name().to_uppercase());
+            messages.push(format!("LEVEL {}", game.start_level));
+            messages.push(String::new());
+            messages.push("SPACE START".to_string());
+            messages.push("UP/DOWN LEVEL".to_string());
+        }
+        Game_Phase::GAME_PHASE_PAUSE => {
+            messages.push("PAUSED".to_string());
+            messages.push(String::new());
+            messages.push("P RESUME".to_string());
+        }
+        Game_Phase::GAME_PHASE_GAMEOVER => {
+            if game.finished {
+                messages.push("FINISHED".to_string());
+            } else {
+                messages.push("GAME OVER".to_string());
+            }
+            messages.push(String::new());
+            messages.push("SPACE AGAIN".to_string());
+        }
+        _ => {
+            messages.push("Z X A ROTATE".to_string());
+            messages.push("C HOLD".to_string());
+            messages.push("P PAUSE".to_string());
+        }
+    }
+    messages.push("Q QUIT".to_string());
+    let mut right: Vec<String> = vec![" NEXT".to_string()];
+    right.extend(preview_lines(
+        theme,
+        game,
+        Some(game.piece_next.get_tetr_index()),
+        false,
+    ));
+    right.push(String::new());
+    for message in messages.iter() {
+        right.push(format!(" {}", message));
+    }
+
+    let width: usize = game.board_size().width as usize;
+    let cells: Vec<Vec<Theme_Color>> = board_colors(game, theme, ghost);
+    let mut board: Vec<String> = Vec::new();
+    board.push(format!("┌{}┐", "─".repeat(width * 2)));
+    for pair in cells.chunks(2) {
+        let mut line: String = "│".to_string();
+        for col in 0..width {
+            push_cells(
+                &mut line,
+                Some(pair[0][col]),
+                pair.get(1).map(|row| row[col]),
+                2,
+            );
+        }
+        line.push_str(RESET);
+        line.push('│');
+        board.push(line);
+    }
+    board.push(format!("└{}┘", "─".repeat(width * 2)));
+
+    let mut frame: String = "\x1b[H".to_string();
+    for (i, board_line) in board.iter().enumerate() {
+        let blank: String = panel_text("");
+        frame.push_str(left.get(i).unwrap_or(&blank));
+        frame.push_str(board_line);
+        frame.push_str(right.get(i).map(|s| s.as_str()).unwrap_or(""));
+        frame.push_str(RESET);
+        frame.push_str("\x1b[K\r\n");
+    }
+    frame.push_str("\x1b[J");
+    return frame;
+}
+
+// Plays in the terminal until the player quits.
+pub fn run_tui() -> std::io::Result<()> {
+    let settings: Settings = Settings::load();
+    let themes: Vec<Theme> = load_themes();
+    let theme: &Theme = &themes[theme_index(&themes, &settings.theme)];
+    let piece_sets: Vec<Piece_Set> = load_piece_sets();
+    let terminal: Raw_Terminal = Raw_Terminal::enable()?;
+    let mut out = std::io::stdout();
+    let mut game: Game_State = Game_State::new();
+    settings.apply(&mut game, &piece_sets);
+    let mut queue: Input_Queue = Input_Queue::new();
+    let clock: Instant = Instant::now();
+    // Frames are only written when they change, and once a second anyway
+    // in case the terminal was cleared or resized.
+    let mut last_frame: String = String::new();
+    let mut last_write: f32 = 0.0;
+    'running: loop {
+        let time: f32 = clock.elapsed().as_secs_f32();
+        for key in parse_keys(&terminal.read_input()) {
+            match key {
+                Tui_Key::KEY_QUIT => break 'running,
+                Tui_Key::KEY_ACTION(action) => {
+                    for pressed in [true, false] {
+                        queue.push(Input_Event {
+                            action: action,
+                            pressed: pressed,
+                            time: time,
+                        });
+                    }
+                }
+            }
+        }
+        settings.apply(&mut game, &piece_sets);
+        game.update_queued(&mut queue, time, &mut |_, _| {}, &mut |_, _, _| {});
+        game.drain_events();
+
+        let frame: String = render_frame(&game, theme, settings.ghost);
+        if frame != last_frame || time - last_write >= 1.0 {
+            out.write_all(frame.as_bytes())?;
+            out.flush()?;
+            last_frame = frame;
+            last_write = time;
+        }
+        std::thread::sleep(Duration::from_secs_f32(TARGET_SECONDS_PER_FRAME));
+    }
+    return Ok(());
+}
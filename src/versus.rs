@@ -0,0 +1,106 @@
+use super::game::*;
+use rand::Rng;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+pub fn garbage_for_lines(lines: i32) -> i32 {
+    match lines {
+        2 => return 1,
+        3 => return 2,
+        4 => return 4,
+        _ => {}
+    }
+    return 0;
+}
+
+// Keeps several games in lockstep: they start and reset together, cleared
+// lines are sent to every opponent as garbage, and the last player standing
+// wins.
+pub struct Versus_Match {
+    prev_lines: Vec<i32>,
+    pending_garbage: Vec<i32>,
+    pub winner: Option<usize>,
+    rng: StdRng,
+}
+
+impl Versus_Match {
+    pub fn new(players: usize, seed: u64) -> Self {
+        Versus_Match {
+            prev_lines: vec![0; players],
+            pending_garbage: vec![0; players],
+            winner: None,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+    fn sync_phases(&mut self, games: &mut [Game_State]) {
+        let any_playing: bool = games.iter().any(|g| g.phase == Game_Phase::GAME_PHASE_PLAY);
+        let any_waiting: bool = games
+            .iter()
+            .any(|g| g.phase == Game_Phase::GAME_PHASE_START);
+        if any_playing && any_waiting {
+            let start_level: i32 = games
+                .iter()
+                .find(|g| g.phase == Game_Phase::GAME_PHASE_PLAY)
+                .map(|g| g.start_level)
+                .unwrap_or(0);
+            for (i, game) in games.iter_mut().enumerate() {
+                if game.phase == Game_Phase::GAME_PHASE_START {
+                    game.start_level = start_level;
+                    game.start();
+                    self.prev_lines[i] = 0;
+                    self.pending_garbage[i] = 0;
+                }
+            }
+            self.winner = None;
+        }
+        let any_over: bool = games
+            .iter()
+            .any(|g| g.phase == Game_Phase::GAME_PHASE_GAMEOVER);
+        if any_over && self.winner.is_none() {
+            let alive: Vec<usize> = games
+                .iter()
+                .enumerate()
+                .filter(|(_, g)| g.phase != Game_Phase::GAME_PHASE_GAMEOVER)
+                .map(|(i, _)| i)
+                .collect();
+            if alive.len() <= 1 {
+                self.winner = alive.first().copied();
+                for game in games.iter_mut() {
+                    game.stop();
+                }
+            }
+        }
+        if any_waiting && !any_playing {
+            for game in games.iter_mut() {
+                if game.phase == Game_Phase::GAME_PHASE_GAMEOVER {
+                    game.phase = Game_Phase::GAME_PHASE_START;
+                }
+            }
+        }
+    }
+    // Call once per frame after every game has been updated.
+    pub fn update(&mut self, games: &mut [Game_State]) {
+        self.sync_phases(games);
+        for (i, game) in games.iter().enumerate() {
+            let cleared: i32 = game.line_count - self.prev_lines[i];
+            self.prev_lines[i] = game.line_count;
+            let attack: i32 = garbage_for_lines(cleared);
+            if attack > 0 {
+                for (j, pending) in self.pending_garbage.iter_mut().enumerate() {
+                    if j != i {
+                        *pending += attack;
+                    }
+                }
+            }
+        }
+        // Garbage waits while a line clear is highlighted so the marked rows
+        // don't move under it.
+        for (game, pending) in games.iter_mut().zip(self.pending_garbage.iter_mut()) {
+            if *pending > 0 && game.phase == Game_Phase::GAME_PHASE_PLAY {
+                let hole_col: i32 = self.rng.random_range(0..game.board_size().width);
+                game.add_garbage(*pending, hole_col);
+                *pending = 0;
+            }
+        }
+    }
+}
@@ -0,0 +1,191 @@
+// Plays seeded games through an Audio_Player with a Recording_Audio backend
+// and checks which sound effects they trigger, frame by frame.
+use tetris::audio::*;
+use tetris::game::*;
+use tetris::tetromino::*;
+
+struct Audio_Test {
+    game: Game_State,
+    audio: Audio_Player<Recording_Audio>,
+    frame: u32,
+}
+
+impl Audio_Test {
+    fn new(seed: u64) -> Self {
+        let mut game: Game_State = Game_State::new_seeded(seed);
+        game.start_level = 0;
+        game.set_time(0.0);
+        game.start_seeded(seed);
+        game.drain_events();
+        return Audio_Test {
+            game: game,
+            audio: Audio_Player::new(Recording_Audio::new()),
+            frame: 0,
+        };
+    }
+    // Starts on the first seed whose game opens with an I piece, stood up
+    // on end so it fits a one column gap.
+    fn standing_i() -> Self {
+        let mut seed: u64 = 0;
+        let mut test: Audio_Test = Audio_Test::new(seed);
+        while test.game.piece.get_tetr_index() != 0 {
+            seed += 1;
+            test = Audio_Test::new(seed);
+        }
+        assert_eq!(
+            test.press(Game_Action::ACTION_ROTATE_CW),
+            vec![Sound_Effect::SFX_ROTATE]
+        );
+        return test;
+    }
+    // Runs one frame and returns the effects it played.
+    fn step(&mut self, input: &Input_State) -> Vec<Sound_Effect> {
+        self.frame += 1;
+        self.audio.start_frame();
+        self.game
+            .set_time(self.frame as f32 * TARGET_SECONDS_PER_FRAME);
+        self.game.update(input);
+        let events: Vec<Game_Event> = self.game.drain_events();
+        self.audio.handle_events(&events);
+        self.audio.update_music(&self.game);
+        let frame: u64 = self.audio.frame;
+        return self
+            .audio
+            .backend
+            .effects()
+            .iter()
+            .filter(|(f, _)| *f == frame)
+            .map(|(_, effect)| *effect)
+            .collect();
+    }
+    fn press(&mut self, action: Game_Action) -> Vec<Sound_Effect> {
+        let mut input: Input_State = Input_State::new();
+        input.set_held(action, true);
+        input.set_edge(action, 1);
+        return self.step(&input);
+    }
+    // Runs frames without input until the line clear is over, and returns
+    // every effect played on the way.
+    fn finish_clear(&mut self) -> Vec<Sound_Effect> {
+        let mut effects: Vec<Sound_Effect> = Vec::new();
+        while self.game.phase == Game_Phase::GAME_PHASE_LINE {
+            assert!(self.frame < 10_000);
+            effects.extend(self.step(&Input_State::new()));
+        }
+        return effects;
+    }
+    // Puts `rows` rows of garbage under the falling piece, with the gap
+    // in the leftmost column the piece covers, and hard drops into it.
+    fn clear_garbage(&mut self, rows: i32) -> Vec<Sound_Effect> {
+        let piece: Piece_State = self.game.piece;
+        let shape: &Tetromino = &TETROMINOS[piece.get_tetr_index() as usize];
+        let mut hole_col: i32 = piece.get_offset_col();
+        'found: for col in 0..shape.side {
+            for row in 0..shape.side {
+                if shape.tetromino_get(row, col, piece.get_rotation()) != 0 {
+                    hole_col += col;
+                    break 'found;
+                }
+            }
+        }
+        self.game.add_garbage(rows, hole_col);
+        let mut effects: Vec<Sound_Effect> = self.press(Game_Action::ACTION_HARD_DROP);
+        assert!(self.game.phase == Game_Phase::GAME_PHASE_LINE);
+        effects.extend(self.finish_clear());
+        return effects;
+    }
+}
+
+#[test]
+fn move_and_rotate() {
+    let mut test: Audio_Test = Audio_Test::new(1);
+    assert_eq!(
+        test.press(Game_Action::ACTION_MOVE_LEFT),
+        vec![Sound_Effect::SFX_MOVE]
+    );
+    assert_eq!(test.step(&Input_State::new()), vec![]);
+    assert_eq!(
+        test.press(Game_Action::ACTION_ROTATE_CW),
+        vec![Sound_Effect::SFX_ROTATE]
+    );
+}
+
+#[test]
+fn gravity_is_silent() {
+    let mut test: Audio_Test = Audio_Test::new(1);
+    let count: u32 = test.game.piece_count;
+    while test.game.piece_count == count {
+        assert!(test.frame < 10_000);
+        let effects: Vec<Sound_Effect> = test.step(&Input_State::new());
+        if test.game.piece_count == count {
+            assert_eq!(effects, vec![]);
+        } else {
+            assert_eq!(effects, vec![Sound_Effect::SFX_LOCK]);
+        }
+    }
+}
+
+#[test]
+fn lock() {
+    let mut test: Audio_Test = Audio_Test::new(2);
+    assert_eq!(
+        test.press(Game_Action::ACTION_HARD_DROP),
+        vec![Sound_Effect::SFX_LOCK]
+    );
+}
+
+#[test]
+fn line_clear() {
+    let mut test: Audio_Test = Audio_Test::standing_i();
+    assert_eq!(
+        test.clear_garbage(1),
+        vec![Sound_Effect::SFX_LOCK, Sound_Effect::SFX_LINE_CLEAR]
+    );
+}
+
+#[test]
+fn tetris() {
+    let mut test: Audio_Test = Audio_Test::standing_i();
+    assert_eq!(
+        test.clear_garbage(4),
+        vec![Sound_Effect::SFX_LOCK, Sound_Effect::SFX_TETRIS]
+    );
+}
+
+#[test]
+fn level_up() {
+    // The first level up from level 0 comes after 10 lines.
+    let mut test: Audio_Test = Audio_Test::standing_i();
+    test.game.line_count = 9;
+    assert_eq!(
+        test.clear_garbage(1),
+        vec![
+            Sound_Effect::SFX_LOCK,
+            Sound_Effect::SFX_LINE_CLEAR,
+            Sound_Effect::SFX_LEVEL_UP
+        ]
+    );
+    assert_eq!(test.game.level, 1);
+}
+
+#[test]
+fn game_over() {
+    let mut test: Audio_Test = Audio_Test::new(6);
+    // Hard drops stack the pieces up in the middle until they top out.
+    while test.game.phase == Game_Phase::GAME_PHASE_PLAY {
+        assert!(test.frame < 10_000);
+        let effects: Vec<Sound_Effect> = test.press(Game_Action::ACTION_HARD_DROP);
+        if test.game.phase == Game_Phase::GAME_PHASE_GAMEOVER {
+            assert_eq!(effects.last(), Some(&Sound_Effect::SFX_GAME_OVER));
+        } else {
+            assert_eq!(effects, vec![Sound_Effect::SFX_LOCK]);
+        }
+        test.step(&Input_State::new());
+    }
+    assert!(test.game.phase == Game_Phase::GAME_PHASE_GAMEOVER);
+    let calls: &[(u64, Audio_Call)] = &test.audio.backend.calls;
+    assert_eq!(
+        calls.last().map(|c| c.1),
+        Some(Audio_Call::CALL_MUSIC(None))
+    );
+}
@@ -0,0 +1,158 @@
+// Renders known boards into a Frame_Buffer and compares them with the
+// reference images in tests/golden, so changes to the themes, the layout or
+// the renderer that alter the picture don't go unnoticed.
+//
+// After an intended change, regenerate the references with
+//   UPDATE_GOLDEN=1 cargo test --test render_golden
+// and look over the new images before committing them. A failing test saves
+// what it rendered in the target directory and prints where.
+use std::path::{Path, PathBuf};
+use tetris::ai::*;
+use tetris::animation::*;
+use tetris::framebuffer::*;
+use tetris::game::*;
+use tetris::layout::*;
+use tetris::render::*;
+use tetris::settings::*;
+use tetris::theme::*;
+
+const CELL: i32 = 20;
+// A pixel differs when any channel is further off than this, and an image
+// fails when more than MAX_DIFFERENT_PIXELS of its pixels differ. Text is
+// antialiased, so a rasterizer update may move a few edge pixels.
+const CHANNEL_TOLERANCE: u8 = 24;
+const MAX_DIFFERENT_PIXELS: f32 = 0.002;
+
+fn render(game: &Game_State, theme: &Theme) -> Frame_Buffer {
+    let settings: Settings = Settings::new();
+    let view: View = View::new(&settings, theme);
+    let (width, height) = layout_size(1, CELL, game.board_size());
+    let layout: Board_Layout = compute_layout(width, height, 1, game.board_size())[0];
+    let mut buffer: Frame_Buffer = Frame_Buffer::new(width, height);
+    buffer.set_theme(theme, CELL);
+    buffer.clear(theme_color(theme.background));
+    render_game(game, &mut buffer, &view, &Piece_Motion::new(), &layout);
+    return buffer;
+}
+
+fn count_different_pixels(a: &Frame_Buffer, b: &Frame_Buffer) -> usize {
+    return a
+        .pixels
+        .chunks(4)
+        .zip(b.pixels.chunks(4))
+        .filter(|(p, q)| {
+            p.iter()
+                .zip(q.iter())
+                .any(|(x, y)| x.abs_diff(*y) > CHANNEL_TOLERANCE)
+        })
+        .count();
+}
+
+fn check_golden(name: &str, buffer: &Frame_Buffer) {
+    let file_name: String = format!("{}.png", name);
+    let path: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(&file_name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        buffer.save_png(&path).unwrap();
+        return;
+    }
+    let expected: Frame_Buffer = load_png(&path).unwrap_or_else(|e| {
+        panic!(
+            "{}: {} (run with UPDATE_GOLDEN=1 to create it)",
+            path.display(),
+            e
+        )
+    });
+    let actual_path: PathBuf = Path::new(env!("CARGO_TARGET_TMPDIR")).join(&file_name);
+    if (expected.width, expected.height) != (buffer.width, buffer.height) {
+        buffer.save_png(&actual_path).unwrap();
+        panic!(
+            "{}: rendered {}x{}, the reference is {}x{}, rendered image saved to {}",
+            name,
+            buffer.width,
+            buffer.height,
+            expected.width,
+            expected.height,
+            actual_path.display()
+        );
+    }
+    let different: usize = count_different_pixels(&expected, buffer);
+    let total: usize = (buffer.width * buffer.height) as usize;
+    if different as f32 > total as f32 * MAX_DIFFERENT_PIXELS {
+        buffer.save_png(&actual_path).unwrap();
+        panic!(
+            "{}: {} of {} pixels differ from the reference, rendered image saved to {}",
+            name,
+            different,
+            total,
+            actual_path.display()
+        );
+    }
+}
+
+fn started_game(seed: u64) -> Game_State {
+    let mut game: Game_State = Game_State::new_seeded(seed);
+    game.set_time(0.0);
+    game.start_seeded(seed);
+    return game;
+}
+
+// Lets the AI play one frame at a time until `stop` holds.
+fn play_until(game: &mut Game_State, stop: &dyn Fn(&Game_State) -> bool) {
+    let mut player: Ai_Player = Ai_Player::new(DEFAULT_WEIGHTS, AI_MAX);
+    let mut frame: u32 = (game.get_time() / TARGET_SECONDS_PER_FRAME) as u32;
+    while !stop(game) {
+        assert!(game.phase != Game_Phase::GAME_PHASE_GAMEOVER);
+        assert!(frame < 100_000);
+        game.set_time(frame as f32 * TARGET_SECONDS_PER_FRAME);
+        let input: Input_State = player.next_input(game);
+        game.update(&input);
+        frame += 1;
+    }
+}
+
+#[test]
+fn start_screen() {
+    let game: Game_State = Game_State::new_seeded(1);
+    check_golden("start_screen", &render(&game, &builtin_themes()[0]));
+}
+
+#[test]
+fn mid_game() {
+    for theme in builtin_themes().iter() {
+        let mut game: Game_State = started_game(2);
+        game.add_garbage(3, 4);
+        play_until(&mut game, &|game| {
+            game.piece_count >= 14 && game.phase == Game_Phase::GAME_PHASE_PLAY
+        });
+        let name: String = format!("mid_game_{}", theme.name.to_lowercase());
+        check_golden(&name, &render(&game, theme));
+    }
+}
+
+#[test]
+fn line_highlight() {
+    let mut game: Game_State = started_game(3);
+    play_until(&mut game, &|game| game.phase == Game_Phase::GAME_PHASE_LINE);
+    check_golden("line_highlight", &render(&game, &builtin_themes()[0]));
+}
+
+#[test]
+fn game_over() {
+    let mut game: Game_State = started_game(4);
+    let mut input: Input_State = Input_State::new();
+    let mut frame: u32 = 0;
+    // Hard drops every other frame stack the pieces up in the middle.
+    while game.phase != Game_Phase::GAME_PHASE_GAMEOVER {
+        assert!(frame < 10_000);
+        game.set_time(frame as f32 * TARGET_SECONDS_PER_FRAME);
+        input.a = frame.is_multiple_of(2) as u8;
+        input.da = if input.a == 1 { 1 } else { -1 };
+        game.update(&input);
+        frame += 1;
+    }
+    check_golden("game_over", &render(&game, &builtin_themes()[0]));
+}